@@ -20,56 +20,50 @@
 use rand::Rng;
 use rand::seq::IteratorRandom;
 use rand::seq::SliceRandom;
-use serde::{de, Deserialize, Deserializer};
-use serde::de::Unexpected;
 use uuid::Uuid;
 
 use crate::allocation_map::AgentLocationMap;
-use crate::config::StartingInfections;
+use crate::config::{age_bounds, AgeGroup, SchoolAge, StartingInfections};
 use crate::constants;
-use crate::custom_types::{Count, Day, Hour, Percentage};
-use crate::disease::Disease;
+use crate::custom_types::{CoOrdinate, Count, Day, Hour, Percentage};
+use crate::disease::{Disease, RiskFactor};
 use crate::disease_state_machine::DiseaseStateMachine;
-use crate::geography::{Area, Grid, Point};
+use crate::geography::{Area, Bed, Grid, HospitalOccupancy, Point};
 use crate::random_wrapper::RandomWrapper;
 use crate::travel_plan::Migrator;
 use crate::commute::{CommutePlan, Commuter};
 use crate::kafka_consumer::TravelPlanConfig;
-
-#[derive(Deserialize)]
-pub struct PopulationRecord {
-    //TODO move to a better place
-    pub ind: u32,
-    pub age: String,
-    #[serde(deserialize_with = "bool_from_string")]
-    pub working: bool,
-    #[serde(deserialize_with = "bool_from_string")]
-    pub pub_transport: bool,
-}
-
-/// Deserialize bool from String with custom value mapping
-fn bool_from_string<'de, D>(deserializer: D) -> Result<bool, D::Error>
-    where
-        D: Deserializer<'de>,
-{
-    match String::deserialize(deserializer)?.as_ref() {
-        "True" => Ok(true),
-        "False" => Ok(false),
-        other => Err(de::Error::invalid_value(
-            Unexpected::Str(other),
-            &"True or False",
-        )),
-    }
-}
+use crate::population::PopulationRecord;
+use crate::schedule::{Activity, Schedule, Schedules, Segment};
+use crate::transmission::Setting;
+use crate::interventions::masks::MaskUse;
+use crate::isolation::{Isolation, SelfIsolation};
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum WorkStatus {
     Normal,
     Essential,
     HospitalStaff { work_start_at: Hour },
+    Student { school_closed: bool },
     NA,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Sex {
+    Male,
+    Female,
+}
+
+/// Attributes of a citizen given by the population file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attributes {
+    pub household: Option<String>,
+    pub workplace: Option<String>,
+    pub ward: Option<String>,
+    pub occupation: Option<String>,
+    pub comorbidities: Vec<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Citizen {
     pub id: Uuid,
@@ -78,13 +72,24 @@ pub struct Citizen {
     pub work_location: Area,
     vaccinated: bool,
     pub uses_public_transport: bool,
-    hospitalized: bool,
+    hospital_bed: Option<Bed>,
+    untreated: bool,
     pub transport_location: Point,
     pub state_machine: DiseaseStateMachine,
     isolated: bool,
     current_area: Area,
     work_status: WorkStatus,
     work_quarantined: bool,
+    age: Option<u32>,
+    sex: Option<Sex>,
+    #[serde(default)]
+    attributes: Attributes,
+    #[serde(default)]
+    contact_hours: Hour,
+    #[serde(default)]
+    mask_use: Option<MaskUse>,
+    #[serde(default)]
+    isolation: Option<Isolation>,
 }
 
 impl Citizen {
@@ -106,12 +111,19 @@ impl Citizen {
             transport_location,
             vaccinated: false,
             uses_public_transport,
-            hospitalized: false,
+            hospital_bed: None,
+            untreated: false,
             state_machine: DiseaseStateMachine::new(),
             isolated: false,
             current_area: home_location,
             work_status,
             work_quarantined: false,
+            age: None,
+            sex: None,
+            attributes: Attributes::default(),
+            contact_hours: 0,
+            mask_use: None,
+            isolation: None,
         }
     }
 
@@ -124,13 +136,20 @@ impl Citizen {
             work_location,
             vaccinated: migrator.vaccinated,
             uses_public_transport: migrator.uses_public_transport,
-            hospitalized: false,
+            hospital_bed: None,
+            untreated: false,
             transport_location,
             state_machine: migrator.state_machine,
             isolated: false,
             current_area,
             work_status: WorkStatus::NA {},
             work_quarantined: false,
+            age: migrator.age,
+            sex: migrator.sex,
+            attributes: Attributes { comorbidities: migrator.comorbidities.clone(), ..Attributes::default() },
+            contact_hours: 0,
+            mask_use: None,
+            isolation: None,
         }
     }
 
@@ -142,20 +161,31 @@ impl Citizen {
             work_location: if work_area == None { commuter.work_location.clone() } else { work_area.unwrap() },
             vaccinated: commuter.vaccinated,
             uses_public_transport: commuter.uses_public_transport,
-            hospitalized: false,
+            hospital_bed: None,
+            untreated: false,
             transport_location,
             state_machine: commuter.state_machine,
             isolated: false,
             current_area,
             work_status: WorkStatus::Normal {},
             work_quarantined: false,
+            age: commuter.age,
+            sex: commuter.sex,
+            attributes: Attributes { comorbidities: commuter.comorbidities.clone(), ..Attributes::default() },
+            contact_hours: 0,
+            mask_use: None,
+            isolation: None,
         }
     }
 
-    pub fn from_record(record: PopulationRecord, home_location: Area, work_location: Area,
+    /// A citizen with a school goes there instead of to the office
+    pub fn from_record(record: PopulationRecord, age: Option<u32>, home_location: Area, work_location: Area, school: Option<Area>,
                        transport_location: Point, rng: &mut RandomWrapper) -> Citizen {
         let disease_randomness_factor = Citizen::generate_disease_randomness_factor(rng);
-        let work_status = Citizen::derive_work_status(record.working, rng);
+        let (work_status, work_location) = match school {
+            Some(school) => (WorkStatus::Student { school_closed: false }, school),
+            None => (Citizen::derive_work_status(record.working, rng), work_location),
+        };
 
         Citizen {
             id: Uuid::new_v4(),
@@ -165,18 +195,27 @@ impl Citizen {
             transport_location,
             vaccinated: false,
             uses_public_transport: record.pub_transport,
-            hospitalized: false,
+            hospital_bed: None,
+            untreated: false,
             state_machine: DiseaseStateMachine::new(),
             isolated: false,
             current_area: home_location,
             work_status,
             work_quarantined: false,
+            age,
+            sex: record.sex,
+            attributes: record.attributes,
+            contact_hours: 0,
+            mask_use: None,
+            isolation: None,
         }
     }
 
-    pub fn get_infection_transmission_rate(&self, disease: &Disease) -> Percentage {
+    pub fn get_infection_transmission_rate(&self, sim_hr: Hour, disease: &Disease) -> Percentage {
         // why is there addition of infection day and immunity
-        disease.get_current_transmission_rate((self.state_machine.get_infection_day() as i32 + self.immunity) as Day)
+        let infection_day = (self.state_machine.get_infection_day() as i32 + self.immunity) as Day;
+        let rate = disease.get_infectiousness(infection_day, self.state_machine.hours_infected(sim_hr));
+        (rate * self.state_machine.relative_infectiousness(disease)).clamp(0.0, 1.0)
     }
 
     pub fn set_vaccination(&mut self, vaccinated: bool) {
@@ -184,7 +223,7 @@ impl Citizen {
     }
 
     pub fn can_move(&self) -> bool {
-        if self.state_machine.is_symptomatic() || self.hospitalized || self.state_machine.is_deceased() || self.isolated {
+        if self.state_machine.is_symptomatic() || self.is_hospitalized() || self.state_machine.is_deceased() || self.isolated {
             return false;
         }
         true
@@ -199,7 +238,17 @@ impl Citizen {
     }
 
     pub fn is_working(&self) -> bool {
-        !matches!(self.work_status, WorkStatus::NA)
+        !matches!(self.work_status, WorkStatus::NA | WorkStatus::Student { .. })
+    }
+
+    pub fn is_student(&self) -> bool {
+        matches!(self.work_status, WorkStatus::Student { .. })
+    }
+
+    pub fn set_school_closed(&mut self, closed: bool) {
+        if let WorkStatus::Student { .. } = self.work_status {
+            self.work_status = WorkStatus::Student { school_closed: closed };
+        }
     }
 
     pub fn get_immunity(&self) -> i32 {
@@ -216,32 +265,75 @@ impl Citizen {
     }
 
     pub fn perform_operation(&mut self, cell: Point, simulation_hour: Hour, grid: &Grid, map: &AgentLocationMap,
-                             rng: &mut RandomWrapper, disease: &Disease) -> Point {
-        self.routine(cell, simulation_hour, grid, map, rng, disease)
+                             hospitals: &mut HospitalOccupancy, schedules: &Schedules, day_off: bool,
+                             self_isolation: Option<&SelfIsolation>, rng: &mut RandomWrapper, disease: &Disease) -> Point {
+        self.routine(cell, simulation_hour, grid, map, hospitals, schedules, day_off, self_isolation, rng, disease)
     }
 
     fn routine(&mut self, cell: Point, simulation_hour: Hour, grid: &Grid, map: &AgentLocationMap,
-               rng: &mut RandomWrapper, disease: &Disease) -> Point {
-        let mut new_cell = cell;
-
+               hospitals: &mut HospitalOccupancy, schedules: &Schedules, day_off: bool,
+               self_isolation: Option<&SelfIsolation>, rng: &mut RandomWrapper, disease: &Disease) -> Point {
         let current_hour = simulation_hour % constants::NUMBER_OF_HOURS;
         match current_hour {
             constants::ROUTINE_START_TIME => {
                 self.update_infection_day();
-                new_cell = self.hospitalize(cell, &grid.hospital_area, map, disease);
-            }
-            constants::SLEEP_START_TIME..=constants::SLEEP_END_TIME => {
-                if !self.is_hospital_staff() {
-                    self.current_area = self.home_location.clone();
-                }
+                self.hospitalize(cell, simulation_hour, grid, map, hospitals, rng, disease)
             }
             constants::ROUTINE_END_TIME => {
-                new_cell = self.deceased(map, cell, rng, disease)
+                self.deceased(map, cell, simulation_hour, rng, disease)
+            }
+            _ if self.state_machine.is_outcome_due(simulation_hour) => {
+                self.deceased(map, cell, simulation_hour, rng, disease)
             }
             _ => {
-                new_cell = self.perform_movements(cell, current_hour, simulation_hour, grid, map, rng, disease);
+                if let Some(self_isolation) = self_isolation {
+                    self.decide_isolation(simulation_hour, self_isolation, rng);
+                }
+                if self.is_isolating_at(simulation_hour) {
+                    return self.isolate(cell, simulation_hour, grid, map, rng, disease);
+                }
+                let schedule = schedules.of(self.segment(schedules.elderly_age), day_off);
+                self.perform_movements(cell, current_hour, simulation_hour, grid, map, schedule, rng, disease)
             }
         }
+    }
+
+    fn decide_isolation(&mut self, simulation_hr: Hour, self_isolation: &SelfIsolation, rng: &mut RandomWrapper) {
+        let decided = matches!(self.isolation, Some(isolation) if isolation.is_decided());
+        if self.state_machine.is_symptomatic() && !decided {
+            self.isolation = Some(self_isolation.decide(simulation_hr, rng));
+        }
+    }
+
+    pub fn is_isolating_at(&self, simulation_hr: Hour) -> bool {
+        !self.is_hospitalized() && matches!(self.isolation, Some(isolation) if isolation.is_isolating_at(simulation_hr))
+    }
+
+    /// Whether the citizen started isolating on their own this hour
+    pub fn started_self_isolating_at(&self, simulation_hr: Hour) -> bool {
+        matches!(self.isolation, Some(Isolation::SelfIsolating { from, .. }) if from == simulation_hr)
+    }
+
+    pub fn quarantine_until(&mut self, until: Hour) {
+        if !matches!(self.isolation, Some(Isolation::SelfIsolating { .. })) {
+            self.isolation = Some(Isolation::HouseholdQuarantine { until });
+        }
+    }
+
+    /// Goes home, if not there yet, and stays there
+    fn isolate(&mut self, cell: Point, simulation_hr: Hour, grid: &Grid, map: &AgentLocationMap, rng: &mut RandomWrapper,
+               disease: &Disease) -> Point {
+        let mut new_cell = cell;
+        if !self.home_location.contains(&cell) {
+            let home_cell = self.home_location.get_random_point(rng);
+            if map.is_cell_vacant(&home_cell) {
+                new_cell = map.move_agent(cell, home_cell);
+            }
+        }
+        if self.home_location.contains(&new_cell) {
+            self.current_area = self.home_location.clone();
+        }
+        self.update_infection_dynamics(new_cell, map, grid, simulation_hr, rng, disease);
         new_cell
     }
 
@@ -253,95 +345,128 @@ impl Citizen {
         matches!(self.work_status, WorkStatus::Essential {})
     }
 
+    pub fn get_age(&self) -> Option<u32> {
+        self.age
+    }
+
+    pub fn get_sex(&self) -> Option<Sex> {
+        self.sex
+    }
+
+    #[cfg(test)]
+    pub fn set_age_and_sex(&mut self, age: Option<u32>, sex: Option<Sex>) {
+        self.age = age;
+        self.sex = sex;
+    }
+
+    pub fn get_work_status(&self) -> WorkStatus {
+        self.work_status
+    }
+
+    pub fn get_comorbidities(&self) -> &[String] {
+        &self.attributes.comorbidities
+    }
+
+    #[cfg(test)]
+    pub fn set_comorbidities(&mut self, comorbidities: Vec<String>) {
+        self.attributes.comorbidities = comorbidities;
+    }
+
+    pub fn segment(&self, elderly_age: u32) -> Segment {
+        match self.work_status {
+            WorkStatus::Normal => Segment::Worker,
+            WorkStatus::Essential => Segment::EssentialWorker,
+            WorkStatus::HospitalStaff { .. } => Segment::HospitalStaff,
+            WorkStatus::Student { .. } => Segment::Student,
+            WorkStatus::NA => {
+                if matches!(self.age, Some(age) if age >= elderly_age) { Segment::Elderly } else { Segment::NonWorker }
+            }
+        }
+    }
+
     fn perform_movements(&mut self, cell: Point, hour_of_day: Hour, simulation_hr: Hour, grid: &Grid,
-                         map: &AgentLocationMap, rng: &mut RandomWrapper, disease: &Disease) -> Point {
+                         map: &AgentLocationMap, schedule: &Schedule, rng: &mut RandomWrapper, disease: &Disease) -> Point {
+        let activity = schedule.activity_at(hour_of_day);
+        if let Some((Activity::Sleep, _)) = activity {
+            if !self.is_hospital_staff() {
+                self.current_area = self.home_location.clone();
+            }
+            return cell;
+        }
+
         let mut new_cell = cell;
-        match self.work_status {
-            WorkStatus::Normal {} | WorkStatus::Essential {} => {
-                match hour_of_day {
-                    constants::ROUTINE_TRAVEL_START_TIME | constants::ROUTINE_TRAVEL_END_TIME => {
-                        if self.uses_public_transport {
-                            new_cell = self.goto_area(grid.transport_area.clone(), map, cell, rng);
-                            self.current_area = grid.transport_area.clone();
-                        } else {
-                            new_cell = self.move_agent_from(map, cell, rng);
-                        }
-                    }
-                    constants::ROUTINE_WORK_TIME => {
-                        new_cell = self.goto_area(self.work_location.clone(), map, cell, rng);
-                        self.current_area = self.work_location.clone();
-                    }
-                    constants::ROUTINE_WORK_END_TIME => {
-                        new_cell = self.goto_area(self.home_location.clone(), map, cell, rng);
-                        self.current_area = self.home_location.clone();
-                    }
-                    _ => {
-                        new_cell = self.move_agent_from(map, cell, rng);
-                    }
-                }
-                self.update_infection_dynamics(new_cell, map, simulation_hr, rng, disease);
+        if let WorkStatus::HospitalStaff { work_start_at } = self.work_status {
+            // why we are substracting work start hour
+            if simulation_hr.saturating_sub(work_start_at) == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) {
+                self.work_quarantined = true;
+                return new_cell;
             }
 
-            WorkStatus::HospitalStaff { work_start_at } => {
-                // info!("simulation_hr : {}, works_starts_at: {}", simulation_hr, work_start_at);
-                // why we are substracting work start hour
-                if simulation_hr.saturating_sub(work_start_at) == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) {
-                    self.work_quarantined = true;
-                    return new_cell;
-                }
+            if simulation_hr.saturating_sub(work_start_at)  == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS * 2) {
+                new_cell = self.goto_area(self.home_location.clone(), map, cell, rng);
+                self.current_area = self.home_location.clone();
+                self.work_status = WorkStatus::HospitalStaff { work_start_at: (simulation_hr + constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) };
+                return new_cell;
+            }
+        }
 
-                if simulation_hr.saturating_sub(work_start_at)  == (constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS * 2) {
-                    new_cell = self.goto_area(self.home_location.clone(), map, cell, rng);
-                    self.current_area = self.home_location.clone();
-                    self.work_status = WorkStatus::HospitalStaff { work_start_at: (simulation_hr + constants::HOURS_IN_A_DAY * constants::QUARANTINE_DAYS) };
-                    return new_cell;
+        match activity {
+            Some((activity, true)) => {
+                new_cell = self.start_activity(activity, cell, simulation_hr, grid, map, rng);
+            }
+            _ => {
+                if !self.work_quarantined {
+                    new_cell = self.move_agent_from(map, cell, rng);
                 }
+            }
+        }
+        self.update_infection_dynamics(new_cell, map, grid, simulation_hr, rng, disease);
+        new_cell
+    }
 
-                match hour_of_day {
-                    constants::ROUTINE_WORK_TIME => {
-                        if self.current_area != grid.hospital_area && work_start_at <= simulation_hr {
-                            new_cell = self.goto_area(grid.hospital_area.clone(), map, cell, rng);
-                            self.current_area = grid.hospital_area.clone();
-                            self.work_status = WorkStatus::HospitalStaff { work_start_at: simulation_hr };
-                        }
-                        self.work_quarantined = false;
-                    }
-                    constants::ROUTINE_WORK_END_TIME => {
-                        self.work_quarantined = true;
-                    }
-                    _ => {
-                        if !self.work_quarantined && self.can_move() {
-                            new_cell = self.move_agent_from(map, cell, rng);
+    fn start_activity(&mut self, activity: Activity, cell: Point, simulation_hr: Hour, grid: &Grid,
+                      map: &AgentLocationMap, rng: &mut RandomWrapper) -> Point {
+        if activity == Activity::OffShift {
+            self.work_quarantined = true;
+            return cell;
+        }
+        self.work_quarantined = false;
+
+        let target_area = match activity {
+            Activity::Home => Some(self.home_location.clone()),
+            Activity::Transport if self.uses_public_transport => Some(grid.transport_area.clone()),
+            Activity::Work if self.is_working() => Some(self.work_location.clone()),
+            Activity::School if matches!(self.work_status, WorkStatus::Student { school_closed: false }) => {
+                Some(self.work_location.clone())
+            }
+            Activity::Neighbourhood => Some(grid.housing_area.clone()),
+            Activity::Hospital => {
+                match self.work_status {
+                    WorkStatus::HospitalStaff { work_start_at } => {
+                        if self.current_area == grid.hospital_area || work_start_at > simulation_hr {
+                            return cell;
                         }
+                        self.work_status = WorkStatus::HospitalStaff { work_start_at: simulation_hr };
+                        Some(grid.hospital_area.clone())
                     }
+                    _ => None,
                 }
-                self.update_infection_dynamics(new_cell, map, simulation_hr, rng, disease);
             }
-
-            WorkStatus::NA {} => {
-                match hour_of_day {
-                    constants::ROUTINE_WORK_TIME => {
-                        new_cell = self.goto_area(grid.housing_area.clone(), map, cell, rng);
-                        self.current_area = grid.housing_area.clone();
-                    }
-                    constants::NON_WORKING_TRAVEL_END_TIME => {
-                        new_cell = self.goto_area(self.home_location.clone(), map, cell, rng);
-                        self.current_area = self.home_location.clone();
-                    }
-
-                    _ => {
-                        new_cell = self.move_agent_from(map, cell, rng);
-                    }
-                }
-                self.update_infection_dynamics(new_cell, map, simulation_hr, rng, disease);
+            _ => None,
+        };
+        match target_area {
+            Some(area) => {
+                let new_cell = self.goto_area(area.clone(), map, cell, rng);
+                self.current_area = area;
+                new_cell
             }
+            None => self.move_agent_from(map, cell, rng),
         }
-        new_cell
     }
 
-    fn update_infection_dynamics(&mut self, cell: Point, map: &AgentLocationMap,
+    fn update_infection_dynamics(&mut self, cell: Point, map: &AgentLocationMap, grid: &Grid,
                                  sim_hr: Hour, rng: &mut RandomWrapper, disease: &Disease) {
-        self.update_exposure(cell, map, sim_hr, rng, disease);
+        self.update_exposure(cell, map, grid, sim_hr, rng, disease);
         self.update_infection(sim_hr, rng, disease);
         self.update_infection_severity(sim_hr, rng, disease);
     }
@@ -352,16 +477,32 @@ impl Citizen {
         }
     }
 
-    fn hospitalize(&mut self, cell: Point, hospital: &Area, map: &AgentLocationMap,
-                   disease: &Disease) -> Point {
+    fn hospitalize(&mut self, cell: Point, sim_hr: Hour, grid: &Grid, map: &AgentLocationMap, hospitals: &mut HospitalOccupancy,
+                   rng: &mut RandomWrapper, disease: &Disease) -> Point {
         let mut new_cell = cell;
-        if self.state_machine.is_infected() && !self.hospitalized {
-            let to_be_hospitalized = self.state_machine.hospitalize(disease, self.immunity);
+        if self.state_machine.is_infected() && !self.is_hospitalized() {
+            let to_be_hospitalized = self.state_machine.hospitalize(sim_hr, rng, disease, self.immunity);
             if to_be_hospitalized {
-                let (is_hospitalized, new_location) = AgentLocationMap::goto_hospital(map, hospital, cell, self);
-                new_cell = new_location;
-                if is_hospitalized {
-                    self.hospitalized = true;
+                let needs_icu = rng.get().gen_bool(grid.icu_percentage);
+                match hospitals.admit(&grid.hospitals, &grid.admission, &cell, needs_icu) {
+                    Some(bed) => {
+                        let hospital = &grid.hospitals[bed.hospital].area;
+                        let (is_hospitalized, new_location) = AgentLocationMap::goto_hospital(map, hospital, cell, self);
+                        new_cell = new_location;
+                        if is_hospitalized {
+                            self.hospital_bed = Some(bed);
+                            self.untreated = needs_icu && !bed.icu;
+                        } else {
+                            hospitals.release(bed);
+                            hospitals.turn_away();
+                            self.untreated = true;
+                        }
+                    }
+                    None => {
+                        hospitals.turn_away();
+                        self.untreated = true;
+                        new_cell = map.move_agent(cell, self.home_location.get_random_point(rng));
+                    }
                 }
             }
         }
@@ -370,7 +511,7 @@ impl Citizen {
 
     fn update_infection_severity(&mut self, sim_hr: Hour, rng: &mut RandomWrapper, disease: &Disease) {
         if self.state_machine.is_pre_symptomatic() {
-            self.state_machine.change_infection_severity(sim_hr, rng, disease);
+            self.state_machine.change_infection_severity(sim_hr, rng, disease, &self.attributes.comorbidities);
         }
     }
 
@@ -380,30 +521,76 @@ impl Citizen {
         }
     }
 
-    fn update_exposure(&mut self, cell: Point, map: &AgentLocationMap, sim_hr: Hour, rng: &mut RandomWrapper,
+    fn update_exposure(&mut self, cell: Point, map: &AgentLocationMap, grid: &Grid, sim_hr: Hour, rng: &mut RandomWrapper,
                        disease: &Disease) {
         if self.state_machine.is_susceptible() && !self.work_quarantined && !self.vaccinated {
-            let neighbours = self.current_area.get_neighbors_of(cell);
-
-            let neighbor_that_spreads_infection = neighbours
+            let kernel = disease.get_transmission();
+            let contacts: Vec<(CoOrdinate, Percentage)> = self.current_area.get_neighbors_within(cell, kernel.get_radius())
                 .filter(|p| map.is_point_in_grid(p))
-                .filter_map(|cell| { map.get_agent_for(&cell) })
-                .filter(|agent| agent.state_machine.is_infected() && !agent.hospitalized)
-                .find(|neighbor| rng.get().gen_bool(neighbor.get_infection_transmission_rate(disease)));
-
-            if neighbor_that_spreads_infection.is_some() {
+                .filter_map(|p| map.get_agent_for(&p).map(|agent| (p, agent)))
+                .filter(|(_, agent)| agent.state_machine.is_infected() && !agent.is_hospitalized())
+                .map(|(p, agent)| {
+                    let rate = agent.get_infection_transmission_rate(sim_hr, disease);
+                    let source_efficacy = agent.mask_efficacy(grid, rng, |mask| mask.get_source_efficacy());
+                    (cell.chebyshev_distance(&p), rate * (1.0 - source_efficacy))
+                })
+                .collect();
+
+            if contacts.is_empty() {
+                self.contact_hours = 0;
+                return;
+            }
+            self.contact_hours += 1;
+            let wearer_efficacy = self.mask_efficacy(grid, rng, |mask| mask.get_wearer_efficacy());
+            let contacts: Vec<(CoOrdinate, Percentage)> = contacts.into_iter()
+                .map(|(distance, rate)| (distance, rate * (1.0 - wearer_efficacy)))
+                .collect();
+            let probability = kernel.infection_probability(&contacts, self.setting(grid), self.contact_hours);
+            if rng.get().gen_bool(probability) {
                 self.state_machine.expose(sim_hr);
             }
         }
     }
 
+    /// The efficacy of the mask the citizen wears in the current setting, or none if not wearing one
+    fn mask_efficacy(&self, grid: &Grid, rng: &mut RandomWrapper, efficacy: fn(&MaskUse) -> Percentage) -> Percentage {
+        match self.mask_use {
+            Some(mask) if mask.wears_mask(self.setting(grid), rng) => efficacy(&mask),
+            _ => 0.0
+        }
+    }
+
+    pub fn set_mask_use(&mut self, mask_use: Option<MaskUse>) {
+        self.mask_use = mask_use;
+    }
+
+    #[cfg(test)]
+    pub fn get_mask_use(&self) -> Option<MaskUse> {
+        self.mask_use
+    }
+
+    /// The kind of place the citizen is at
+    fn setting(&self, grid: &Grid) -> Setting {
+        if self.is_hospitalized() || self.current_area == grid.hospital_area {
+            Setting::Hospital
+        } else if self.current_area == self.home_location {
+            Setting::Home
+        } else if self.current_area == grid.transport_area {
+            Setting::Transport
+        } else if self.current_area == self.work_location {
+            if self.is_student() { Setting::School } else { Setting::Work }
+        } else {
+            Setting::Community
+        }
+    }
+
     fn goto_area(&mut self, target_area: Area, map: &AgentLocationMap, cell: Point, rng: &mut RandomWrapper) -> Point {
         //TODO: Refactor - Jayanta
         // If agent is working and current_area is work, target area is home and symptomatic then allow movement
         let mut override_movement = false;
 
         match self.work_status{
-            WorkStatus::Normal{} | WorkStatus::Essential{} => {
+            WorkStatus::Normal{} | WorkStatus::Essential{} | WorkStatus::Student{..} => {
                 if self.work_location.contains(&cell) && target_area == self.home_location && (self.state_machine.is_mild_symptomatic() || self.state_machine.is_infected_severe()) {
                     override_movement = true;
                 }
@@ -413,7 +600,7 @@ impl Citizen {
         if !self.can_move() && !override_movement {
             return cell;
         }
-        if self.is_working() {
+        if self.is_working() || self.is_student() {
             let mut new_cell: Point = target_area.get_random_point(rng);
             if !map.is_cell_vacant(&new_cell) {
                 new_cell = cell;
@@ -424,16 +611,16 @@ impl Citizen {
         self.move_agent_from(map, cell, rng)
     }
 
-    fn deceased(&mut self, map: &AgentLocationMap, cell: Point, rng: &mut RandomWrapper,
+    fn deceased(&mut self, map: &AgentLocationMap, cell: Point, sim_hr: Hour, rng: &mut RandomWrapper,
                 disease: &Disease) -> Point {
         let mut new_cell = cell;
         if self.state_machine.is_infected() {
-            let result = self.state_machine.decease(rng, disease);
+            let result = self.state_machine.decease(sim_hr, rng, disease, self.is_hospitalized(), self.untreated, &self.attributes.comorbidities);
             if result.1 == 1 {
                 new_cell = map.move_agent(cell, self.home_location.get_random_point(rng));
             }
-            if result != (0, 0) && self.hospitalized{
-                self.hospitalized = false;
+            if result != (0, 0) && self.is_hospitalized() {
+                self.hospital_bed = None;
             }
         }
         new_cell
@@ -475,7 +662,16 @@ impl Citizen {
     }
 
     pub fn is_hospitalized(&self) -> bool {
-        self.hospitalized
+        self.hospital_bed.is_some()
+    }
+
+    pub fn get_hospital_bed(&self) -> Option<&Bed> {
+        self.hospital_bed.as_ref()
+    }
+
+    #[cfg(test)]
+    pub fn set_hospital_bed(&mut self, bed: Option<Bed>) {
+        self.hospital_bed = bed;
     }
 
     #[cfg(test)]
@@ -499,15 +695,35 @@ impl Citizen {
     }
 }
 
-pub fn citizen_factory(number_of_agents: Count, home_locations: &[Area], work_locations: &[Area], public_transport_locations: &[Point],
-                       percentage_public_transport: Percentage, working_percentage: Percentage, rng: &mut RandomWrapper,
-                       starting_infections: &StartingInfections, travel_plan_config: Option<TravelPlanConfig>, region: String) -> Vec<Citizen> {
+/// Citizens get an age and sex drawn from the age distribution, and whether they work and use public transport
+/// depends on their age group. Those of school age become students at the schools in turn
+pub fn citizen_factory(number_of_agents: Count, home_locations: &[Area], work_locations: &[Area], schools: &[Area],
+                       public_transport_locations: &[Point], percentage_public_transport: Percentage,
+                       working_percentage: Percentage, age_distribution: &[AgeGroup], school_age: &SchoolAge,
+                       rng: &mut RandomWrapper, starting_infections: &StartingInfections,
+                       travel_plan_config: Option<TravelPlanConfig>, region: String) -> Vec<Citizen> {
     let mut agent_list = Vec::with_capacity(home_locations.len());
     let commute_plan: Option<CommutePlan> = if travel_plan_config.is_some() && travel_plan_config.as_ref().unwrap().commute.enabled {
         Some(travel_plan_config.unwrap().commute_plan())
     } else { None };
+    if !age_distribution.is_empty() && schools.is_empty() {
+        warn!("There are no schools, so no citizen will be a student");
+    }
+    let mut number_of_students = 0;
     for i in 0..number_of_agents as usize {
-        let is_a_working_citizen = rng.get().gen_bool(working_percentage);
+        let age_group = if age_distribution.is_empty() { None } else {
+            Some(age_distribution.choose_weighted(rng.get(), |group| group.share)
+                .expect("Could not draw an age from the age distribution"))
+        };
+        let age = age_group.and_then(|group| draw_age(&group.age, rng));
+        let sex = age_group.and_then(|group| group.male_percentage)
+            .map(|male_percentage| if rng.get().gen_bool(male_percentage) { Sex::Male } else { Sex::Female });
+        let working_percentage = age_group.and_then(|group| group.working_percentage).unwrap_or(working_percentage);
+        let percentage_public_transport = age_group.and_then(|group| group.public_transport_percentage)
+            .unwrap_or(percentage_public_transport);
+
+        let is_a_student = !schools.is_empty() && matches!(age, Some(age) if school_age.includes(age));
+        let is_a_working_citizen = !is_a_student && rng.get().gen_bool(working_percentage);
 
         let total_home_locations = home_locations.len();
         let total_work_locations = work_locations.len();
@@ -523,13 +739,21 @@ pub fn citizen_factory(number_of_agents: Count, home_locations: &[Area], work_lo
             home_location.get_random_point(rng)
         };
 
-        let work_location = if is_a_working_citizen { work_location } else {
-            home_location.clone()
+        let (work_status, work_location) = if is_a_student {
+            let school = schools[number_of_students % schools.len()].clone();
+            number_of_students += 1;
+            (WorkStatus::Student { school_closed: false }, school)
+        } else {
+            let work_location = if is_a_working_citizen { work_location } else {
+                home_location.clone()
+            };
+            (Citizen::derive_work_status(is_a_working_citizen, rng), work_location)
         };
-        let work_status = Citizen::derive_work_status(is_a_working_citizen, rng);
 
-        let agent = Citizen::new(home_location.clone(), work_location.clone(), public_transport_location,
-                                 uses_public_transport, work_status, rng);
+        let mut agent = Citizen::new(home_location.clone(), work_location.clone(), public_transport_location,
+                                     uses_public_transport, work_status, rng);
+        agent.age = age;
+        agent.sex = sex;
 
         agent_list.push(agent);
     }
@@ -542,6 +766,22 @@ pub fn citizen_factory(number_of_agents: Count, home_locations: &[Area], work_lo
     agent_list
 }
 
+/// An age within an age group, such as "5-9", drawn uniformly
+pub fn draw_age(age_group: &str, rng: &mut RandomWrapper) -> Option<u32> {
+    age_bounds(age_group).map(|(youngest, oldest)| rng.get().gen_range(youngest..=oldest))
+}
+
+/// Gives citizens the risk factors of the disease by their prevalence in the age group of each citizen
+pub fn assign_risk_factors(agent_list: &mut [Citizen], risk_factors: &[RiskFactor], rng: &mut RandomWrapper) {
+    for citizen in agent_list.iter_mut() {
+        for factor in risk_factors {
+            if rng.get().gen_bool(factor.prevalence_at(citizen.age)) {
+                citizen.attributes.comorbidities.push(factor.get_name().to_string());
+            }
+        }
+    }
+}
+
 pub fn update_commuters(agent_list: &mut [Citizen], commute_plan: CommutePlan, region_name: String) {
     let total_commuters_by_region : Vec<(String, u32)> = commute_plan.get_total_commuters_by_region(region_name.clone());
     for (region, commuters) in total_commuters_by_region {
@@ -581,6 +821,11 @@ pub fn set_starting_infections(agent_list: &mut [Citizen], start_infections: &St
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::schedule::ScheduleBlock;
+    use crate::interventions::masks::MaskAdoption;
+    use crate::infectiousness::InfectiousnessProfile;
+    use crate::durations::{DurationDistribution, StageDurations};
+    use crate::disease_state_machine::{InfectionSeverity, State};
 
     fn before_each() -> Vec<Citizen> {
         let mut rng = RandomWrapper::new();
@@ -591,8 +836,8 @@ mod tests {
 
         let public_transport_location = vec![Point::new(5, 0), Point::new(5, 1), Point::new(5, 2), Point::new(5, 3)];
         let start_infections = StartingInfections::new(0, 0, 0, 1);
-        citizen_factory(4, &home_locations, &work_locations, &public_transport_location, 0.5, 0.5,
-                        &mut rng, &start_infections, None, "engine1".to_string())
+        citizen_factory(4, &home_locations, &work_locations, &[], &public_transport_location, 0.5, 0.5, &[],
+                        &SchoolAge::default(), &mut rng, &start_infections, None, "engine1".to_string())
     }
 
     #[test]
@@ -651,4 +896,314 @@ mod tests {
         assert_eq!(working_citizen.is_working(), true);
         assert_eq!(non_working_citizen.is_working(), false);
     }
+
+    #[test]
+    fn should_make_citizens_of_school_age_students() {
+        let mut rng = RandomWrapper::new();
+        let engine_id = "engine1".to_string();
+        let home_locations = vec![Area::new(engine_id.clone(), Point::new(0, 0), Point::new(4, 4))];
+        let work_locations = vec![Area::new(engine_id.clone(), Point::new(5, 0), Point::new(6, 2))];
+        let schools = vec![Area::new(engine_id.clone(), Point::new(7, 0), Point::new(8, 2)),
+                           Area::new(engine_id, Point::new(9, 0), Point::new(10, 2))];
+        let age_distribution = vec![AgeGroup::new("5-9", 0.5), AgeGroup::new("10-14", 0.5), AgeGroup::new("30-34", 0.0)];
+
+        let citizens = citizen_factory(10, &home_locations, &work_locations, &schools, &[], 0.5, 1.0,
+                                       &age_distribution, &SchoolAge::default(), &mut rng,
+                                       &StartingInfections::new(0, 0, 0, 1), None, "engine1".to_string());
+
+        assert!(citizens.iter().all(|citizen| citizen.is_student() && !citizen.is_working()));
+        assert_eq!(citizens.iter().filter(|citizen| citizen.work_location == schools[0]).count(), 5);
+        assert_eq!(citizens.iter().filter(|citizen| citizen.work_location == schools[1]).count(), 5);
+    }
+
+    #[test]
+    fn should_not_make_citizens_students_without_schools() {
+        let mut rng = RandomWrapper::new();
+        let engine_id = "engine1".to_string();
+        let home_locations = vec![Area::new(engine_id.clone(), Point::new(0, 0), Point::new(4, 4))];
+        let work_locations = vec![Area::new(engine_id, Point::new(5, 0), Point::new(6, 2))];
+
+        let citizens = citizen_factory(10, &home_locations, &work_locations, &[], &[], 0.5, 1.0,
+                                       &[AgeGroup::new("5-9", 1.0)], &SchoolAge::default(), &mut rng,
+                                       &StartingInfections::new(0, 0, 0, 1), None, "engine1".to_string());
+
+        assert!(citizens.iter().all(|citizen| !citizen.is_student() && citizen.is_working()));
+    }
+
+    #[test]
+    fn should_make_non_working_citizens_of_elderly_age_elderly() {
+        let mut rng = RandomWrapper::new();
+        let engine_id = "engine1".to_string();
+        let home_locations = vec![Area::new(engine_id.clone(), Point::new(0, 0), Point::new(4, 4))];
+        let work_locations = vec![Area::new(engine_id, Point::new(5, 0), Point::new(6, 2))];
+        let start_infections = StartingInfections::new(0, 0, 0, 1);
+
+        let elderly = citizen_factory(5, &home_locations, &work_locations, &[], &[], 0.5, 0.0, &[AgeGroup::new("80+", 1.0)],
+                                      &SchoolAge::default(), &mut rng, &start_infections, None, "engine1".to_string());
+        let adults = citizen_factory(5, &home_locations, &work_locations, &[], &[], 0.5, 0.0, &[AgeGroup::new("30-34", 1.0)],
+                                     &SchoolAge::default(), &mut rng, &start_infections, None, "engine1".to_string());
+
+        assert!(elderly.iter().all(|citizen| citizen.segment(60) == Segment::Elderly));
+        assert!(adults.iter().all(|citizen| citizen.segment(60) == Segment::NonWorker));
+    }
+
+    #[test]
+    fn should_draw_age_and_sex_from_age_group() {
+        let mut rng = RandomWrapper::new();
+        let engine_id = "engine1".to_string();
+        let home_locations = vec![Area::new(engine_id.clone(), Point::new(0, 0), Point::new(4, 4))];
+        let work_locations = vec![Area::new(engine_id, Point::new(5, 0), Point::new(6, 2))];
+        let age_group = AgeGroup { male_percentage: Some(1.0), ..AgeGroup::new("30-34", 1.0) };
+
+        let citizens = citizen_factory(10, &home_locations, &work_locations, &[], &[], 0.5, 0.5, &[age_group],
+                                       &SchoolAge::default(), &mut rng, &StartingInfections::new(0, 0, 0, 1), None,
+                                       "engine1".to_string());
+
+        assert!(citizens.iter().all(|citizen| matches!(citizen.get_age(), Some(age) if (30..=34).contains(&age))));
+        assert!(citizens.iter().all(|citizen| citizen.get_sex() == Some(Sex::Male)));
+        assert!(before_each().iter().all(|citizen| citizen.get_age().is_none() && citizen.get_sex().is_none()));
+    }
+
+    #[test]
+    fn should_use_working_percentage_of_age_group() {
+        let mut rng = RandomWrapper::new();
+        let engine_id = "engine1".to_string();
+        let home_locations = vec![Area::new(engine_id.clone(), Point::new(0, 0), Point::new(4, 4))];
+        let work_locations = vec![Area::new(engine_id, Point::new(5, 0), Point::new(6, 2))];
+        let age_distribution = vec![
+            AgeGroup { working_percentage: Some(0.0), ..AgeGroup::new("70-74", 0.5) },
+            AgeGroup::new("30-34", 0.5),
+        ];
+
+        let citizens = citizen_factory(40, &home_locations, &work_locations, &[], &[], 0.5, 1.0, &age_distribution,
+                                       &SchoolAge::default(), &mut rng, &StartingInfections::new(0, 0, 0, 1), None,
+                                       "engine1".to_string());
+
+        assert!(citizens.iter().all(|citizen| citizen.is_working() == (citizen.get_age().unwrap() < 70)));
+    }
+
+    #[test]
+    fn should_assign_risk_factors_by_prevalence() {
+        let mut citizens = before_each();
+        let risk_factors = vec![RiskFactor::new("diabetes", 1.0, 1.0, 1.0, 1.0), RiskFactor::new("asthma", 0.0, 1.0, 1.0, 1.0)];
+
+        assign_risk_factors(&mut citizens, &risk_factors, &mut RandomWrapper::new());
+
+        assert!(citizens.iter().all(|citizen| citizen.get_comorbidities() == ["diabetes".to_string()]));
+    }
+
+    fn perform_operation_at(citizen: &mut Citizen, cell: Point, hour: Hour, grid: &Grid, schedules: &Schedules) -> Point {
+        let map = AgentLocationMap::new(100, &[citizen.clone()], &[cell]);
+        let mut hospitals = HospitalOccupancy::new(&grid.hospitals, std::iter::empty());
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        citizen.perform_operation(cell, hour, grid, &map, &mut hospitals, schedules, false, None, &mut RandomWrapper::new(), &disease)
+    }
+
+    #[test]
+    fn should_follow_configured_schedule() {
+        let grid = crate::geography::define_geography(&crate::config::GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let home = grid.houses[0].clone();
+        let office = grid.offices[0].clone();
+        let mut citizen = Citizen::new(home.clone(), office.clone(), Point::new(0, 0), false, WorkStatus::Normal {},
+                                       &mut RandomWrapper::new());
+        let mut schedules = Schedules::default();
+        schedules.worker = Schedule::new(vec![ScheduleBlock::new(1, 9, Activity::Sleep),
+                                              ScheduleBlock::new(10, 12, Activity::Work),
+                                              ScheduleBlock::new(13, 22, Activity::Home)]);
+
+        let at_home = perform_operation_at(&mut citizen, Point::new(0, 0), 8, &grid, &schedules);
+        assert_eq!(at_home, Point::new(0, 0));
+
+        let at_work = perform_operation_at(&mut citizen, at_home, 10, &grid, &schedules);
+        assert!(office.contains(&at_work));
+
+        let back_home = perform_operation_at(&mut citizen, at_work, 13, &grid, &schedules);
+        assert!(home.contains(&back_home));
+    }
+
+    fn grid_with_schools() -> Grid {
+        let geography_parameters = crate::config::GeographyParameters {
+            schools: Some(crate::config::SchoolsConfig::default()),
+            ..crate::config::GeographyParameters::new(100, 0.003)
+        };
+        crate::geography::define_geography(&geography_parameters, "engine1".to_string())
+    }
+
+    #[test]
+    fn should_not_go_to_closed_school() {
+        let grid = grid_with_schools();
+        let home = grid.houses[0].clone();
+        let school = grid.schools[0].clone();
+        let mut student = Citizen::new(home.clone(), school.clone(), Point::new(0, 0), false,
+                                       WorkStatus::Student { school_closed: false }, &mut RandomWrapper::new());
+        let schedules = Schedules::default();
+
+        let at_school = perform_operation_at(&mut student, Point::new(0, 0), constants::SCHOOL_START_TIME, &grid, &schedules);
+        assert!(school.contains(&at_school));
+
+        student.current_area = home.clone();
+        student.set_school_closed(true);
+        let at_home = perform_operation_at(&mut student, Point::new(0, 0), constants::SCHOOL_START_TIME, &grid, &schedules);
+        assert!(home.contains(&at_home));
+    }
+
+    #[test]
+    fn should_find_setting_from_current_area() {
+        let grid = grid_with_schools();
+        let home = grid.houses[0].clone();
+        let mut rng = RandomWrapper::new();
+        let mut worker = Citizen::new(home.clone(), grid.offices[0].clone(), Point::new(0, 0), false, WorkStatus::Normal {}, &mut rng);
+        let mut student = Citizen::new(home, grid.schools[0].clone(), Point::new(0, 0), false,
+                                       WorkStatus::Student { school_closed: false }, &mut rng);
+
+        assert_eq!(worker.setting(&grid), Setting::Home);
+        worker.current_area = grid.offices[0].clone();
+        assert_eq!(worker.setting(&grid), Setting::Work);
+        worker.current_area = grid.transport_area.clone();
+        assert_eq!(worker.setting(&grid), Setting::Transport);
+        worker.current_area = grid.hospital_area.clone();
+        assert_eq!(worker.setting(&grid), Setting::Hospital);
+        worker.current_area = grid.offices[1].clone();
+        assert_eq!(worker.setting(&grid), Setting::Community);
+        student.current_area = grid.schools[0].clone();
+        assert_eq!(student.setting(&grid), Setting::School);
+    }
+
+    #[test]
+    fn should_protect_by_mask_efficacy_only_when_wearing_one() {
+        let grid = crate::geography::define_geography(&crate::config::GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let mut rng = RandomWrapper::new();
+        let mut citizen = Citizen::new(grid.houses[0].clone(), grid.offices[0].clone(), Point::new(0, 0), false,
+                                       WorkStatus::Normal {}, &mut rng);
+        assert_eq!(citizen.mask_efficacy(&grid, &mut rng, |mask| mask.get_wearer_efficacy()), 0.0);
+
+        let adoption = MaskAdoption { work: 1.0, ..MaskAdoption::default() };
+        citizen.set_mask_use(Some(MaskUse::new(adoption, 1.0, 0.5, 0.3)));
+        assert_eq!(citizen.mask_efficacy(&grid, &mut rng, |mask| mask.get_wearer_efficacy()), 0.0);
+
+        citizen.current_area = grid.offices[0].clone();
+        assert_eq!(citizen.mask_efficacy(&grid, &mut rng, |mask| mask.get_wearer_efficacy()), 0.3);
+        assert_eq!(citizen.mask_efficacy(&grid, &mut rng, |mask| mask.get_source_efficacy()), 0.5);
+    }
+
+    #[test]
+    fn should_go_home_to_self_isolate_when_symptomatic() {
+        let grid = crate::geography::define_geography(&crate::config::GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let home = grid.houses[0].clone();
+        let office = grid.offices[0].clone();
+        let at_work = office.start_offset;
+        let mut citizen = Citizen::new(home.clone(), office.clone(), Point::new(0, 0), false, WorkStatus::Normal {},
+                                       &mut RandomWrapper::new());
+        citizen.current_area = office;
+        citizen.state_machine.set_mild_symptomatic();
+        let mut frozen = citizen.clone();
+        let mut declined = citizen.clone();
+
+        let map = AgentLocationMap::new(100, &[citizen.clone()], &[at_work]);
+        let mut hospitals = HospitalOccupancy::new(&grid.hospitals, std::iter::empty());
+        let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        let self_isolation = SelfIsolation::default();
+        let mut rng = RandomWrapper::new();
+
+        let isolated_at = citizen.perform_operation(at_work, 10, &grid, &map, &mut hospitals, &Schedules::default(), false,
+                                                    Some(&self_isolation), &mut rng, &disease);
+        assert!(home.contains(&isolated_at));
+        assert!(citizen.is_isolating_at(10));
+        assert!(citizen.started_self_isolating_at(10));
+
+        let frozen_at = frozen.perform_operation(at_work, 10, &grid, &map, &mut hospitals, &Schedules::default(), false,
+                                                 None, &mut rng, &disease);
+        assert_eq!(frozen_at, at_work);
+
+        let declining = SelfIsolation { probability: 0.0, ..SelfIsolation::default() };
+        let declined_at = declined.perform_operation(at_work, 10, &grid, &map, &mut hospitals, &Schedules::default(), false,
+                                                     Some(&declining), &mut rng, &disease);
+        assert_eq!(declined_at, at_work);
+        assert_eq!(declined.isolation, Some(Isolation::Declined));
+    }
+
+    #[test]
+    fn should_not_override_self_isolation_with_household_quarantine() {
+        let engine_id = "engine1".to_string();
+        let home_location = Area::new(engine_id.clone(), Point::new(0, 0), Point::new(10, 10));
+        let work_location = Area::new(engine_id, Point::new(11, 0), Point::new(20, 20));
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(2, 2), false, WorkStatus::Normal {},
+                                       &mut RandomWrapper::new());
+
+        citizen.quarantine_until(50);
+        assert!(citizen.is_isolating_at(20));
+
+        citizen.isolation = Some(Isolation::SelfIsolating { from: 20, until: 40 });
+        citizen.quarantine_until(80);
+        assert!(!citizen.is_isolating_at(40));
+    }
+
+    #[test]
+    fn should_transmit_by_infectiousness_profile_and_relative_infectiousness() {
+        let engine_id = "engine1".to_string();
+        let home_location = Area::new(engine_id.clone(), Point::new(0, 0), Point::new(10, 10));
+        let work_location = Area::new(engine_id, Point::new(11, 0), Point::new(20, 20));
+        let mut citizen = Citizen::new(home_location, work_location, Point::new(2, 2), false, WorkStatus::Normal {},
+                                       &mut RandomWrapper::new());
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
+        disease.set_infectiousness(InfectiousnessProfile::Daily(vec![0.1, 0.2]), 0.5, 0.25);
+
+        citizen.state_machine.set_mild_symptomatic();
+        assert_eq!(citizen.get_infection_transmission_rate(30, &disease), 0.2);
+        assert_eq!(citizen.get_infection_transmission_rate(48, &disease), 0.0);
+
+        citizen.state_machine.set_mild_asymptomatic();
+        assert_eq!(citizen.get_infection_transmission_rate(30, &disease), 0.1);
+
+        citizen.state_machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 0 } };
+        assert_eq!(citizen.get_infection_transmission_rate(30, &disease), 0.05);
+
+        disease.set_infectiousness(InfectiousnessProfile::Daily(vec![0.5, 0.8]), 2.0, 0.25);
+        citizen.state_machine.set_mild_asymptomatic();
+        assert_eq!(citizen.get_infection_transmission_rate(30, &disease), 1.0);
+    }
+
+    #[test]
+    fn should_decease_at_any_hour_after_sampled_time_to_death() {
+        let grid = crate::geography::define_geography(&crate::config::GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let mut rng = RandomWrapper::new();
+        let mut citizen = Citizen::new(grid.houses[0].clone(), grid.offices[0].clone(), Point::new(0, 0), false,
+                                       WorkStatus::Normal {}, &mut rng);
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 1.0, 0, 0);
+        disease.set_non_hospitalized_death_rate(1.0);
+        disease.set_durations(StageDurations {
+            incubation: Some(DurationDistribution::Fixed { hours: 0.0 }),
+            pre_symptomatic: Some(DurationDistribution::Fixed { hours: 0.0 }),
+            time_to_death: Some(DurationDistribution::Fixed { hours: 5.0 }),
+            ..StageDurations::default()
+        });
+        citizen.state_machine.expose(0);
+        citizen.state_machine.infect(&mut rng, 1, &disease);
+        citizen.state_machine.change_infection_severity(2, &mut rng, &disease, &[]);
+        let cell = grid.houses[0].start_offset;
+        let map = AgentLocationMap::new(100, &[citizen.clone()], &[cell]);
+        let mut hospitals = HospitalOccupancy::new(&grid.hospitals, std::iter::empty());
+
+        citizen.perform_operation(cell, 6, &grid, &map, &mut hospitals, &Schedules::default(), false, None, &mut rng, &disease);
+        assert!(!citizen.state_machine.is_deceased());
+        citizen.perform_operation(cell, 7, &grid, &map, &mut hospitals, &Schedules::default(), false, None, &mut rng, &disease);
+        assert!(citizen.state_machine.is_deceased());
+    }
+
+    #[test]
+    fn should_close_school_only_for_students() {
+        let engine_id = "engine1".to_string();
+        let home_location = Area::new(engine_id.clone(), Point::new(0, 0), Point::new(10, 10));
+        let school = Area::new(engine_id, Point::new(11, 0), Point::new(20, 20));
+        let mut rng = RandomWrapper::new();
+        let mut student = Citizen::new(home_location.clone(), school.clone(), Point::new(2, 2), false,
+                                       WorkStatus::Student { school_closed: false }, &mut rng);
+        let mut worker = Citizen::new(home_location, school, Point::new(2, 2), false, WorkStatus::Normal {}, &mut rng);
+
+        student.set_school_closed(true);
+        worker.set_school_closed(true);
+
+        assert!(matches!(student.work_status, WorkStatus::Student { school_closed: true }));
+        assert!(matches!(worker.work_status, WorkStatus::Normal));
+    }
 }
@@ -22,13 +22,13 @@ use std::collections::hash_map::{Iter, IterMut};
 
 use fnv::FnvHashMap;
 
-use crate::constants;
 use crate::agent::Citizen;
 use crate::commute::Commuter;
-use crate::custom_types::{CoOrdinate, Count, Hour, Size};
+use crate::custom_types::{CoOrdinate, Count, Size};
 use crate::disease_state_machine::State;
 use crate::geography::{Area, Grid};
 use crate::geography::Point;
+use crate::interventions::masks::MaskUse;
 use crate::listeners::events::counts::Counts;
 use crate::random_wrapper::RandomWrapper;
 use crate::travel_plan::Migrator;
@@ -133,8 +133,9 @@ impl AgentLocationMap {
         }
     }
 
+    /// Migrators wear masks as the citizens of this region do, given by `mask_use`
     pub fn assimilate_migrators(&mut self, incoming: &mut Vec<Migrator>, grid: &mut Grid, counts: &mut Counts,
-                                rng: &mut RandomWrapper) {
+                                rng: &mut RandomWrapper, mask_use: Option<MaskUse>) {
         if incoming.is_empty() {
             return;
         }
@@ -148,7 +149,8 @@ impl AgentLocationMap {
                 house.clone()
             };
             let transport_location = self.random_starting_point(&grid.housing_area, rng);
-            let citizen = Citizen::from_migrator(migrator, house.clone(), office.clone(), transport_location, grid.housing_area.clone());
+            let mut citizen = Citizen::from_migrator(migrator, house.clone(), office.clone(), transport_location, grid.housing_area.clone());
+            citizen.set_mask_use(mask_use);
             new_citizens.push(citizen.clone());
             grid.add_house_occupant(&house.clone());
             if migrator.working {
@@ -171,15 +173,17 @@ impl AgentLocationMap {
         }
     }
 
+    /// Commuters wear masks as the citizens of this region do, given by `mask_use`. Those `arriving_for_work`
+    /// are given an office
     pub fn assimilate_commuters(&mut self, incoming: &mut Vec<Commuter>, grid: &mut Grid, counts: &mut Counts,
-                                rng: &mut RandomWrapper, simulation_hour: Hour) {
+                                rng: &mut RandomWrapper, arriving_for_work: bool, mask_use: Option<MaskUse>) {
         if incoming.is_empty() { return; }
         debug!("Assimilating {} incoming commuters", incoming.len());
         let mut new_citizens: Vec<Citizen> = Vec::with_capacity(incoming.len());
         for commuter in incoming {
             let transport_location = self.random_starting_point(&grid.transport_area, rng);
 
-            let work_area: Option<Area> = if simulation_hour == constants::ROUTINE_TRAVEL_START_TIME {
+            let work_area: Option<Area> = if arriving_for_work {
                 debug!("inside if of simulation hour");
                 let office = grid.choose_office_with_free_space(rng);
                 debug!("got the office space - {:?}", office.clone());
@@ -188,7 +192,8 @@ impl AgentLocationMap {
                 Some(office.clone())
             } else { None };
 
-            let citizen = Citizen::from_commuter(commuter, transport_location, grid.housing_area.clone(), work_area);
+            let mut citizen = Citizen::from_commuter(commuter, transport_location, grid.housing_area.clone(), work_area);
+            citizen.set_mask_use(mask_use);
             new_citizens.push(citizen.clone()); //use current area as transport area
 
             AgentLocationMap::increment_counts(&citizen.state_machine.state, counts);
@@ -344,4 +349,23 @@ mod tests {
             assert!(!map.is_point_in_grid(&point))
         }
     }
+
+    #[test]
+    fn should_give_travellers_the_mask_use_of_the_region() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = crate::geography::define_geography(&crate::config::GeographyParameters::new(100, 0.003), "engine1".to_string());
+        grid.houses_occupancy.insert(grid.houses[0].clone(), 0);
+        grid.offices_occupancy.insert(grid.offices[0].clone(), 0);
+        let traveller = Citizen::new(grid.houses[0].clone(), grid.offices[0].clone(), Point::new(0, 0), false, WorkStatus::Normal {}, &mut rng);
+        let mut map = AgentLocationMap::new(100, &[], &[]);
+        let mut counts = Counts::new(0, 0, 0);
+        let mask_use = MaskUse::new(crate::interventions::masks::MaskAdoption { work: 0.5, ..Default::default() }, 1.0, 0.5, 0.3);
+
+        map.assimilate_commuters(&mut vec![Commuter::from(&traveller)], &mut grid, &mut counts, &mut rng,
+                                 true, Some(mask_use));
+        map.assimilate_migrators(&mut vec![Migrator::from(&traveller)], &mut grid, &mut counts, &mut rng, Some(mask_use));
+
+        assert_eq!(map.current_population(), 2);
+        assert!(map.iter().all(|(_, citizen)| citizen.get_mask_use() == Some(mask_use)));
+    }
 }
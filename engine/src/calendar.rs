@@ -0,0 +1,94 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashSet;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::config::CalendarConfig;
+use crate::constants;
+use crate::custom_types::Hour;
+
+/// Dates of the simulation hours, and whether they are days off (weekends and holidays). Without a start date
+/// there are no dates, and every day is a working day
+#[derive(Debug, PartialEq, Clone)]
+pub struct Calendar {
+    start_date: Option<NaiveDate>,
+    weekend: Vec<Weekday>,
+    holidays: HashSet<NaiveDate>,
+}
+
+impl Calendar {
+    pub fn new(start_date: Option<NaiveDate>, config: &CalendarConfig) -> Calendar {
+        Calendar {
+            start_date,
+            weekend: config.weekend.clone(),
+            holidays: config.holidays.iter().copied().collect(),
+        }
+    }
+
+    pub fn date_at(&self, hour: Hour) -> Option<NaiveDate> {
+        self.start_date.map(|start| start + Duration::days((hour / constants::HOURS_IN_A_DAY) as i64))
+    }
+
+    pub fn is_day_off(&self, hour: Hour) -> bool {
+        match self.date_at(hour) {
+            Some(date) => self.weekend.contains(&date.weekday()) || self.holidays.contains(&date),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar() -> Calendar {
+        let config = CalendarConfig { weekend: vec![Weekday::Sat, Weekday::Sun], holidays: vec![NaiveDate::from_ymd(2020, 3, 10)] };
+        Calendar::new(Some(NaiveDate::from_ymd(2020, 3, 6)), &config)
+    }
+
+    #[test]
+    fn should_date_simulation_hours_from_start_date() {
+        let calendar = calendar();
+
+        assert_eq!(calendar.date_at(0), Some(NaiveDate::from_ymd(2020, 3, 6)));
+        assert_eq!(calendar.date_at(23), Some(NaiveDate::from_ymd(2020, 3, 6)));
+        assert_eq!(calendar.date_at(24), Some(NaiveDate::from_ymd(2020, 3, 7)));
+    }
+
+    #[test]
+    fn should_take_weekends_and_holidays_off() {
+        let calendar = calendar();
+
+        assert!(!calendar.is_day_off(10));
+        assert!(calendar.is_day_off(24 + 10));
+        assert!(calendar.is_day_off(2 * 24 + 10));
+        assert!(!calendar.is_day_off(3 * 24 + 10));
+        assert!(calendar.is_day_off(4 * 24 + 10));
+    }
+
+    #[test]
+    fn should_not_have_dates_or_days_off_without_start_date() {
+        let calendar = Calendar::new(None, &CalendarConfig::default());
+
+        assert_eq!(calendar.date_at(24), None);
+        assert!(!calendar.is_day_off(24));
+    }
+}
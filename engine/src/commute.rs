@@ -18,10 +18,15 @@
  */
 
 use uuid::Uuid;
-use crate::constants;
+use crate::agent::Sex;
+use crate::calendar::Calendar;
 use crate::custom_types::Hour;
 use crate::disease_state_machine::DiseaseStateMachine;
 use crate::geography::{Area, Point};
+use crate::interventions::travel_restriction::TravelRestrictions;
+use crate::schedule::CommuteHours;
+use crate::travel_plan;
+use crate::travel_plan::ScheduledMatrix;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Commuter {
@@ -33,6 +38,12 @@ pub struct Commuter {
     pub uses_public_transport: bool,
     pub working: bool,
     pub state_machine: DiseaseStateMachine,
+    #[serde(default)]
+    pub age: Option<u32>,
+    #[serde(default)]
+    pub sex: Option<Sex>,
+    #[serde(default)]
+    pub comorbidities: Vec<String>,
 }
 
 
@@ -62,27 +73,23 @@ impl CommutersByRegion {
 #[derive(Clone, Debug, Deserialize)]
 pub struct Commute {
     pub enabled: bool,
-    pub matrix: Option<Vec<Vec<u32>>>
+    pub matrix: Option<Vec<Vec<u32>>>,
+    #[serde(default)]
+    pub schedule: Vec<ScheduledMatrix>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct CommutePlan {
     pub regions: Vec<String>,
-    pub matrix: Vec<Vec<u32>>
+    pub matrix: Vec<Vec<u32>>,
+    pub schedule: Vec<ScheduledMatrix>,
 }
 
 impl CommutePlan {
-    pub fn incoming_regions_count(&self, engine_id: &String) -> u32 {
-        let index = self.get_position(engine_id);
-        self.column(index).filter(|val| *val > 0).count() as u32
-    }
-
-    pub fn get_outgoing(&self, from_region: &String, to_region: &String) -> u32 {
-        let from_index = self.get_position(from_region);
-        let to_index = self.get_position(to_region);
-
-        let row = self.matrix.get(from_index).unwrap();
-        *row.get(to_index).unwrap()
+    /// Every engine sends a (possibly empty) message to every region at the commute hours, since restricted
+    /// or returning flows don't follow the commute matrix
+    pub fn incoming_regions_count(&self) -> u32 {
+        self.regions.len() as u32
     }
 
     fn get_position(&self, engine_id: &String) -> usize {
@@ -90,28 +97,122 @@ impl CommutePlan {
             .expect("Could not find region with specified name")
     }
 
-    fn column(&self, index: usize) -> impl Iterator<Item=u32> + '_ {
-        self.matrix.iter().map(move |row| *row.get(index).unwrap())
+    /// Commuters are assigned for the largest flow in the schedule, so that the flow at any hour is a share of them
+    pub fn get_total_commuters_by_region(&self, from_region: String) -> Vec<(String, u32)> {
+        let max_matrix = travel_plan::max_matrix(&self.matrix, &self.schedule);
+        let from_index = self.get_position(&from_region);
+        self.regions.iter().enumerate()
+            .map(|(to_index, region)| (region.to_string(), max_matrix[from_index][to_index]))
+            .collect()
     }
 
-    pub fn get_total_commuters_by_region(&self, from_region: String) -> Vec<(String, u32)> {
-        let mut commuters_by_region: Vec<(String, u32)>  = vec![];
-        for region in &self.regions {
-            commuters_by_region.push((region.to_string(), self.get_outgoing(&from_region,region )))
+    fn scheduled_count(&self, from_region: &String, to_region: &String, count: usize, simulation_hour: Hour,
+                       calendar: &Calendar) -> usize {
+        if self.schedule.is_empty() {
+            return count;
+        }
+        let from_index = self.get_position(from_region);
+        let to_index = self.get_position(to_region);
+        let assigned = travel_plan::max_matrix(&self.matrix, &self.schedule)[from_index][to_index];
+        let scheduled = travel_plan::matrix_at(&self.matrix, &self.schedule, simulation_hour, calendar)[from_index][to_index];
+        if assigned == 0 {
+            return count;
         }
-        commuters_by_region
+        (count as f64 * scheduled as f64 / assigned as f64).round() as usize
     }
 
-    pub fn get_commuters_by_region(&self, commuters: &Vec<(Point, Commuter)>, simulation_hour: Hour) -> Vec<CommutersByRegion> {
+    /// Restrictions only apply to the trips to work, so that commuters can always return home.
+    /// Returns the commuters by region, along with the ones actually leaving
+    pub fn get_commuters_by_region(&self, commuters: &[(Point, Commuter)], simulation_hour: Hour, commute_hours: CommuteHours,
+                                   calendar: &Calendar, restrictions: &TravelRestrictions) -> (Vec<CommutersByRegion>, Vec<(Point, Commuter)>) {
+        let hour_of_day = simulation_hour % 24;
         let mut commuters_by_region : Vec<CommutersByRegion> = Vec::new();
+        let mut actual_outgoing: Vec<(Point, Commuter)> = Vec::new();
         for region in &self.regions {
-            let mut commuters_for_region : Vec<Commuter> = Vec::new();
-            for (_point, commuter) in commuters {
-                if simulation_hour % 24 == constants::ROUTINE_TRAVEL_START_TIME && commuter.work_location.location_id == *region {commuters_for_region.push(commuter.clone())}
-                if simulation_hour % 24 == constants::ROUTINE_TRAVEL_END_TIME && commuter.home_location.location_id == *region {commuters_for_region.push(commuter.clone())}
+            let mut commuters_for_region : Vec<(Point, Commuter)> = Vec::new();
+            for (point, commuter) in commuters {
+                if hour_of_day == commute_hours.to_work && commuter.work_location.location_id == *region &&
+                    restrictions.allows(&commuter.state_machine) {
+                    commuters_for_region.push((*point, commuter.clone()))
+                }
+                if hour_of_day == commute_hours.back_home && commuter.home_location.location_id == *region {
+                    commuters_for_region.push((*point, commuter.clone()))
+                }
+            }
+            if hour_of_day == commute_hours.to_work {
+                if let Some((_, commuter)) = commuters_for_region.first() {
+                    let from_region = commuter.home_location.location_id.clone();
+                    let scheduled = self.scheduled_count(&from_region, region, commuters_for_region.len(), simulation_hour, calendar);
+                    let allowed = restrictions.allowed_count(&from_region, region, scheduled);
+                    commuters_for_region.truncate(allowed);
+                }
             }
-            commuters_by_region.push(CommutersByRegion{to_engine_id: region.clone(), commuters: commuters_for_region })
+            actual_outgoing.extend(commuters_for_region.iter().cloned());
+            commuters_by_region.push(CommutersByRegion {
+                to_engine_id: region.clone(),
+                commuters: commuters_for_region.into_iter().map(|(_, commuter)| commuter).collect(),
+            })
+        }
+        (commuters_by_region, actual_outgoing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CalendarConfig;
+
+    fn commuter(home: &str, work: &str) -> (Point, Commuter) {
+        (Point::new(1, 1), Commuter {
+            id: Uuid::new_v4(),
+            immunity: 0,
+            home_location: Area::new(home.to_string(), Point::new(0, 0), Point::new(5, 5)),
+            work_location: Area::new(work.to_string(), Point::new(10, 10), Point::new(15, 15)),
+            vaccinated: false,
+            uses_public_transport: false,
+            working: true,
+            state_machine: DiseaseStateMachine::new(),
+            age: None,
+            sex: None,
+            comorbidities: Vec::new(),
+        })
+    }
+
+    fn commute_plan() -> CommutePlan {
+        CommutePlan {
+            regions: vec!["engine1".to_string(), "engine2".to_string()],
+            matrix: vec![vec![0, 10], vec![10, 0]],
+            schedule: vec![
+                ScheduledMatrix { days_of_cycle: vec![5, 6], matrix: vec![vec![0, 5], vec![5, 0]], ..ScheduledMatrix::default() },
+                ScheduledMatrix { from_hour: Some(240), to_hour: Some(264), matrix: vec![vec![0, 20], vec![10, 0]], ..ScheduledMatrix::default() },
+            ],
         }
-        commuters_by_region
+    }
+
+    #[test]
+    fn should_assign_commuters_for_largest_scheduled_flow() {
+        let commuters_by_region = commute_plan().get_total_commuters_by_region("engine1".to_string());
+
+        assert_eq!(vec![("engine1".to_string(), 0), ("engine2".to_string(), 20)], commuters_by_region);
+    }
+
+    #[test]
+    fn should_send_share_of_commuters_for_scheduled_flow() {
+        let commute_plan = commute_plan();
+        let outgoing: Vec<(Point, Commuter)> = (0..20).map(|_| commuter("engine1", "engine2")).collect();
+        let no_restrictions = TravelRestrictions::default();
+
+        let commute_hours = CommuteHours::default();
+        let calendar = Calendar::new(None, &CalendarConfig::default());
+
+        let (_, weekday) = commute_plan.get_commuters_by_region(&outgoing, 7, commute_hours, &calendar, &no_restrictions);
+        let (_, weekend) = commute_plan.get_commuters_by_region(&outgoing, 5 * 24 + 7, commute_hours, &calendar, &no_restrictions);
+        let (by_region, surge) = commute_plan.get_commuters_by_region(&outgoing, 240 + 7, commute_hours, &calendar, &no_restrictions);
+
+        assert_eq!(10, weekday.len());
+        assert_eq!(5, weekend.len());
+        assert_eq!(20, surge.len());
+        assert_eq!(2, by_region.len());
+        assert_eq!(20, by_region[1].commuters.len());
     }
 }
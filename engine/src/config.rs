@@ -17,33 +17,55 @@
  *
  */
 
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
 
+use crate::constants;
 use crate::disease::{Disease, DiseaseOverride};
 use crate::interventions::{InterventionConfig};
+use crate::schedule::Schedules;
+use crate::calendar::Calendar;
+use crate::isolation::SelfIsolation;
+use crate::listeners::stratified_counts::StratifiedCountsConfig;
+use chrono::{NaiveDate, Weekday};
 use crate::custom_types::{Count, Hour, Size, Percentage, validate_percentage};
-use validator::Validate;
+use validator::{Validate, ValidationError, ValidationErrors};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Validate)]
 pub struct Config {
+    #[validate]
     population: Population,
+    #[validate]
     disease: Disease,
     #[serde(default)]
     disease_overrides: Vec<DiseaseOverride>,
+    #[validate]
     geography_parameters: GeographyParameters,
     hours: Hour,
+    #[validate]
     interventions: Vec<InterventionConfig>,
     output_file: Option<String>,
     #[serde(default)]
     enable_citizen_state_messages: bool,
     #[serde(default)]
     starting_infections: StartingInfections,
+    #[serde(default)]
+    schedules: Schedules,
+    #[serde(default)]
+    start_date: Option<NaiveDate>,
+    #[serde(default)]
+    calendar: CalendarConfig,
+    #[serde(default)]
+    #[validate]
+    self_isolation: Option<SelfIsolation>,
+    #[serde(default)]
+    stratified_counts: Option<StratifiedCountsConfig>,
 }
 
 impl Config {
     pub fn get_disease(&self) -> Disease {
-        self.disease
+        self.disease.clone()
     }
 
     pub fn get_starting_infections(&self) -> StartingInfections {
@@ -82,6 +104,22 @@ impl Config {
         self.geography_parameters.clone()
     }
 
+    pub fn get_schedules(&self) -> Schedules {
+        self.schedules.clone()
+    }
+
+    pub fn get_calendar(&self) -> Calendar {
+        Calendar::new(self.start_date, &self.calendar)
+    }
+
+    pub fn get_self_isolation(&self) -> Option<SelfIsolation> {
+        self.self_isolation
+    }
+
+    pub fn get_stratified_counts(&self) -> Option<StratifiedCountsConfig> {
+        self.stratified_counts.clone()
+    }
+
     #[cfg(test)]
     pub fn new(population: Population, disease: Disease, geography_parameters: GeographyParameters, disease_overrides: Vec<DiseaseOverride>,
                hours: Hour, interventions: Vec<InterventionConfig>, output_file: Option<String>)
@@ -96,6 +134,11 @@ impl Config {
             output_file,
             enable_citizen_state_messages: true,
             starting_infections: StartingInfections::default(),
+            schedules: Schedules::default(),
+            start_date: None,
+            calendar: CalendarConfig::default(),
+            self_isolation: None,
+            stratified_counts: None,
         }
     }
 }
@@ -105,41 +148,374 @@ pub struct GeographyParameters {
     pub grid_size: Size,
     #[validate(custom = "validate_percentage")]
     pub hospital_beds_percentage: Percentage,
+    #[serde(default)]
+    pub geojson: Option<GeoJsonGeography>,
+    #[serde(default)]
+    pub layout: GridLayout,
+    #[serde(default = "default_household_sizes")]
+    #[validate(custom = "validate_building_sizes")]
+    pub household_sizes: Vec<BuildingSize>,
+    #[serde(default = "default_office_sizes")]
+    #[validate(custom = "validate_building_sizes")]
+    pub office_sizes: Vec<BuildingSize>,
+    #[serde(default)]
+    #[validate]
+    pub hospitals: HospitalsConfig,
+    #[serde(default)]
+    #[validate]
+    pub schools: Option<SchoolsConfig>,
 }
 
 impl GeographyParameters{
     #[cfg(test)]
     pub fn new(grid_size: Size, hospital_beds_percentage: f64) -> GeographyParameters {
         GeographyParameters{
-            grid_size, hospital_beds_percentage
+            grid_size, hospital_beds_percentage, geojson: None, layout: GridLayout::default(),
+            household_sizes: default_household_sizes(), office_sizes: default_office_sizes(),
+            hospitals: HospitalsConfig::default(),
+            schools: None,
         }
     }
+
+    /// Without `schools` nobody is a student
+    pub fn school_age(&self) -> SchoolAge {
+        self.schools.as_ref().map(|schools| schools.age).unwrap_or_default()
+    }
+}
+
+/// Hospitals of the region. Without `facilities` there is one hospital, sized by `hospital_beds_percentage`
+/// and without ICU beds. `icu_percentage` is the share of admitted patients that need an ICU bed
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
+pub struct HospitalsConfig {
+    #[serde(default)]
+    pub facilities: Vec<HospitalConfig>,
+    #[serde(default = "default_admission_policy")]
+    pub admission: AdmissionPolicy,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    pub icu_percentage: Percentage,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct HospitalConfig {
+    pub beds: Count,
+    #[serde(default)]
+    pub icu_beds: Count,
+}
+
+impl HospitalConfig {
+    #[cfg(test)]
+    pub fn new(beds: Count, icu_beds: Count) -> HospitalConfig {
+        HospitalConfig { beds, icu_beds }
+    }
+}
+
+/// How a hospital is chosen among those with a free bed
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum AdmissionPolicy {
+    Nearest,
+    LeastLoaded,
+}
+
+impl Default for HospitalsConfig {
+    fn default() -> Self {
+        HospitalsConfig { facilities: Vec::new(), admission: default_admission_policy(), icu_percentage: 0.0 }
+    }
+}
+
+fn default_admission_policy() -> AdmissionPolicy {
+    AdmissionPolicy::Nearest
+}
+
+/// Schools of the region, and the ages of the citizens that go to them
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
+pub struct SchoolsConfig {
+    #[serde(default = "default_school_sizes")]
+    #[validate(custom = "validate_building_sizes")]
+    pub sizes: Vec<BuildingSize>,
+    #[serde(default)]
+    pub age: SchoolAge,
+}
+
+impl Default for SchoolsConfig {
+    fn default() -> Self {
+        SchoolsConfig { sizes: default_school_sizes(), age: SchoolAge::default() }
+    }
+}
+
+fn default_school_sizes() -> Vec<BuildingSize> {
+    vec![BuildingSize::new(constants::SCHOOL_SIZE * constants::SCHOOL_SIZE, 1.0)]
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct SchoolAge {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl SchoolAge {
+    pub fn includes(&self, age: u32) -> bool {
+        self.min <= age && age <= self.max
+    }
+}
+
+impl Default for SchoolAge {
+    fn default() -> Self {
+        SchoolAge { min: constants::SCHOOL_AGE_MIN, max: constants::SCHOOL_AGE_MAX }
+    }
+}
+
+/// Days off, on top of the weekend
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CalendarConfig {
+    #[serde(default = "default_weekend")]
+    pub weekend: Vec<Weekday>,
+    #[serde(default)]
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        CalendarConfig { weekend: default_weekend(), holidays: Vec::new() }
+    }
+}
+
+fn default_weekend() -> Vec<Weekday> {
+    vec![Weekday::Sat, Weekday::Sun]
+}
+
+/// Width of each vertical strip of the default layout, relative to the grid size. The school strip is only
+/// carved out when `schools` are configured, and is `SCHOOL_AREA_RELATIVE_SIZE` wide unless given
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct GridLayout {
+    pub housing: Percentage,
+    pub transport: Percentage,
+    pub work: Percentage,
+    pub hospital: Percentage,
+    #[serde(default)]
+    pub school: Percentage,
+}
+
+impl Default for GridLayout {
+    fn default() -> Self {
+        GridLayout {
+            housing: constants::HOUSE_AREA_RELATIVE_SIZE,
+            transport: constants::TRANSPORT_AREA_RELATIVE_SIZE,
+            work: constants::WORK_AREA_RELATIVE_SIZE,
+            hospital: constants::INITIAL_HOSPITAL_RELATIVE_SIZE,
+            school: 0.0,
+        }
+    }
+}
+
+/// A share of the houses (or offices) built to hold `capacity` people, one per cell
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BuildingSize {
+    pub capacity: Count,
+    pub share: Percentage,
+}
+
+impl BuildingSize {
+    pub fn new(capacity: Count, share: Percentage) -> BuildingSize {
+        BuildingSize { capacity, share }
+    }
+
+    /// Width and height of the smallest near-square area with room for `capacity` cells
+    pub fn dimensions(&self) -> (Size, Size) {
+        let width = (self.capacity.max(1) as f64).sqrt().ceil() as Size;
+        let height = (self.capacity.max(1) as f64 / width as f64).ceil() as Size;
+        (width, height)
+    }
+}
+
+fn validate_building_sizes(sizes: &[BuildingSize]) -> Result<(), ValidationError> {
+    if sizes.is_empty() {
+        return Err(ValidationError::new("at least one building size is needed"));
+    }
+    if sizes.iter().any(|size| size.capacity == 0) {
+        return Err(ValidationError::new("building capacity needs to be positive"));
+    }
+    if sizes.iter().any(|size| size.share <= 0.0) {
+        return Err(ValidationError::new("building share needs to be positive"));
+    }
+    Ok(())
+}
+
+fn default_household_sizes() -> Vec<BuildingSize> {
+    vec![BuildingSize::new(constants::HOME_SIZE * constants::HOME_SIZE, 1.0)]
+}
+
+fn default_office_sizes() -> Vec<BuildingSize> {
+    vec![BuildingSize::new(constants::OFFICE_SIZE * constants::OFFICE_SIZE, 1.0)]
+}
+
+/// Building footprints to lay the grid out from, instead of the default vertical strips.
+/// `building_types` maps each kind of place (house, office, hospital, school, transport)
+/// to the values of the `building` property that should be classified as it
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct GeoJsonGeography {
+    pub file: String,
+    #[serde(default = "default_building_types")]
+    pub building_types: HashMap<String, Vec<String>>,
+}
+
+impl GeoJsonGeography {
+    #[cfg(test)]
+    pub fn new(file: String) -> GeoJsonGeography {
+        GeoJsonGeography { file, building_types: default_building_types() }
+    }
+}
+
+fn default_building_types() -> HashMap<String, Vec<String>> {
+    let types = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<String>>();
+    let mut building_types = HashMap::new();
+    building_types.insert("house".to_string(), types(&["house", "residential", "apartments", "detached", "yes"]));
+    building_types.insert("office".to_string(), types(&["office", "commercial", "industrial", "retail"]));
+    building_types.insert("hospital".to_string(), types(&["hospital", "clinic"]));
+    building_types.insert("school".to_string(), types(&["school", "college", "university", "kindergarten"]));
+    building_types.insert("transport".to_string(), types(&["train_station", "transportation"]));
+    building_types
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum Population {
     Csv(CsvPopulation),
     Auto(AutoPopulation),
+    Synthetic(SyntheticPopulation),
+}
+
+impl Validate for Population {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Population::Auto(population) => population.validate(),
+            Population::Synthetic(population) => population.validate(),
+            Population::Csv(_) => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct CsvPopulation {
     pub file: String,
-    pub cols: Vec<String>,
+    pub cols: Columns,
+    #[serde(default)]
+    pub skip_invalid_rows: bool,
+}
+
+/// The columns of a population file that citizens are read from
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Columns {
+    /// Fields read from the columns of the same name, e.g. ["age", "working"]
+    Names(Vec<String>),
+    /// Fields read from the named columns, e.g. {"age": "AGE_YEARS", "working": "employed"}
+    Mapping(HashMap<String, String>),
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone, Validate)]
+impl Columns {
+    /// The column of each field
+    pub fn mapping(&self) -> HashMap<String, String> {
+        match self {
+            Columns::Names(names) => names.iter().map(|name| (name.clone(), name.clone())).collect(),
+            Columns::Mapping(mapping) => mapping.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
 pub struct AutoPopulation {
     pub number_of_agents: Count,
     #[validate(custom = "validate_percentage")]
     pub public_transport_percentage: Percentage,
     #[validate(custom = "validate_percentage")]
     pub working_percentage: Percentage,
+    #[serde(default)]
+    #[validate]
+    #[validate(custom = "validate_age_distribution")]
+    pub age_distribution: Vec<AgeGroup>,
+}
+
+/// A population fitted to census margins of age, sex, working and public transport use
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
+pub struct SyntheticPopulation {
+    pub number_of_agents: Count,
+    #[validate]
+    #[validate(custom = "validate_age_distribution")]
+    pub age_distribution: Vec<AgeGroup>,
+    #[validate(custom = "validate_percentage")]
+    pub male_percentage: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub working_percentage: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub public_transport_percentage: Percentage,
+    #[serde(default = "default_ipf_tolerance")]
+    pub tolerance: f64,
+    #[serde(default = "default_ipf_max_iterations")]
+    pub max_iterations: u32,
+}
+
+fn default_ipf_tolerance() -> f64 {
+    constants::IPF_TOLERANCE
+}
+
+fn default_ipf_max_iterations() -> u32 {
+    constants::IPF_MAX_ITERATIONS
+}
+
+/// Share of the population in an age group, e.g. "5-9", as in a census table. The percentages of the group that
+/// are male, work and use public transport default to those of the whole population (and sex to unknown)
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
+pub struct AgeGroup {
+    pub age: String,
+    pub share: Percentage,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    pub male_percentage: Option<Percentage>,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    pub working_percentage: Option<Percentage>,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    pub public_transport_percentage: Option<Percentage>,
+}
+
+fn validate_age_distribution(age_groups: &[AgeGroup]) -> Result<(), ValidationError> {
+    if age_groups.iter().any(|group| group.share < 0.0) {
+        return Err(ValidationError::new("age group share needs to be at least 0"));
+    }
+    if !age_groups.is_empty() && age_groups.iter().all(|group| group.share == 0.0) {
+        return Err(ValidationError::new("at least one age group needs a positive share"));
+    }
+    Ok(())
+}
+
+impl AgeGroup {
+    #[cfg(test)]
+    pub fn new(age: &str, share: Percentage) -> AgeGroup {
+        AgeGroup { age: age.to_string(), share, male_percentage: None, working_percentage: None, public_transport_percentage: None }
+    }
+}
+
+/// The youngest and oldest ages of an age group such as "5-9" or "80+", or of a single age
+pub fn age_bounds(age: &str) -> Option<(u32, u32)> {
+    let age = age.trim();
+    if let Some(lower) = age.strip_suffix('+') {
+        let lower = lower.trim().parse::<u32>().ok()?;
+        return Some((lower, lower.max(constants::OLDEST_AGE)));
+    }
+    match age.split_once('-') {
+        Some((lower, upper)) => {
+            let (lower, upper) = (lower.trim().parse::<u32>().ok()?, upper.trim().parse::<u32>().ok()?);
+            if lower > upper { None } else { Some((lower, upper)) }
+        }
+        None => age.parse::<u32>().ok().map(|age| (age, age)),
+    }
 }
 
 pub fn read(filename: String) -> Result<Config, Box<dyn Error>> {
     let reader = File::open(filename)?;
     let v: Config = serde_json::from_reader(reader)?;
+    v.validate()?;
     Ok(v)
 }
 
@@ -215,8 +591,9 @@ mod tests {
 
         let population = Population::Csv(CsvPopulation {
             file: "config/pune_population.csv".to_string(),
-            cols: vec![String::from("age"), String::from("sex"), String::from("working"),
-                       String::from("pub_transport")],
+            cols: Columns::Names(vec![String::from("age"), String::from("sex"), String::from("working"),
+                                      String::from("pub_transport")]),
+            skip_invalid_rows: false,
         });
 
         let expected_config = Config {
@@ -229,6 +606,11 @@ mod tests {
             output_file: None,
             enable_citizen_state_messages: false,
             starting_infections: StartingInfections::default(),
+            schedules: Schedules::default(),
+            start_date: None,
+            calendar: CalendarConfig::default(),
+            self_isolation: None,
+            stratified_counts: None,
         };
 
         assert_eq!(expected_config, read_config);
@@ -244,6 +626,7 @@ mod tests {
             number_of_agents: 10000,
             public_transport_percentage: 0.2,
             working_percentage: 0.7,
+            age_distribution: Vec::new(),
         });
 
         let expected_config = Config {
@@ -256,8 +639,183 @@ mod tests {
             output_file: Some("simulation_default_config".to_string()),
             enable_citizen_state_messages: false,
             starting_infections: StartingInfections::new(2, 3, 4, 5),
+            schedules: Schedules::default(),
+            start_date: None,
+            calendar: CalendarConfig::default(),
+            self_isolation: None,
+            stratified_counts: None,
         };
 
         assert_eq!(expected_config, read_config);
     }
+
+    #[test]
+    fn should_default_building_types_of_geojson_geography() {
+        let json = r#"{"grid_size": 100, "hospital_beds_percentage": 0.003, "geojson": {"file": "pune.geojson"}}"#;
+
+        let geography_parameters: GeographyParameters = serde_json::from_str(json).unwrap();
+
+        let geojson = geography_parameters.geojson.unwrap();
+        assert_eq!(geojson.file, "pune.geojson");
+        assert_eq!(geojson.building_types, default_building_types());
+        assert!(geojson.building_types["house"].contains(&"apartments".to_string()));
+    }
+
+    #[test]
+    fn should_read_hospitals_of_geography() {
+        let json = r#"{"grid_size": 100, "hospital_beds_percentage": 0.003,
+            "hospitals": {"facilities": [{"beds": 20, "icu_beds": 4}, {"beds": 10}], "admission": "LeastLoaded", "icu_percentage": 0.25}}"#;
+
+        let geography_parameters: GeographyParameters = serde_json::from_str(json).unwrap();
+
+        let expected = HospitalsConfig {
+            facilities: vec![HospitalConfig::new(20, 4), HospitalConfig::new(10, 0)],
+            admission: AdmissionPolicy::LeastLoaded,
+            icu_percentage: 0.25,
+        };
+        assert_eq!(geography_parameters.hospitals, expected);
+    }
+
+    #[test]
+    fn should_read_schools_of_geography() {
+        let json = r#"{"grid_size": 100, "hospital_beds_percentage": 0.003,
+            "schools": {"sizes": [{"capacity": 50, "share": 1.0}], "age": {"min": 6, "max": 15}}}"#;
+
+        let geography_parameters: GeographyParameters = serde_json::from_str(json).unwrap();
+
+        let schools = geography_parameters.schools.as_ref().unwrap();
+        assert_eq!(schools.sizes, vec![BuildingSize::new(50, 1.0)]);
+        assert_eq!(schools.age, SchoolAge { min: 6, max: 15 });
+        assert_eq!(geography_parameters.school_age(), SchoolAge { min: 6, max: 15 });
+        assert_eq!(geography_parameters.layout.school, 0.0);
+    }
+
+    #[test]
+    fn should_read_calendar() {
+        let mut json: serde_json::Value = serde_json::from_reader(File::open("config/test/auto_pop.json").unwrap()).unwrap();
+        json["start_date"] = serde_json::json!("2020-03-06");
+        json["calendar"] = serde_json::json!({"weekend": ["Fri"], "holidays": ["2020-03-10"]});
+
+        let config: Config = serde_json::from_value(json).unwrap();
+
+        assert_eq!(config.start_date, Some(NaiveDate::from_ymd(2020, 3, 6)));
+        assert_eq!(config.calendar, CalendarConfig { weekend: vec![Weekday::Fri], holidays: vec![NaiveDate::from_ymd(2020, 3, 10)] });
+        assert!(config.get_calendar().is_day_off(10));
+    }
+
+    #[test]
+    fn should_check_school_age() {
+        let school_age = SchoolAge::default();
+
+        assert!(school_age.includes(5));
+        assert!(school_age.includes(17));
+        assert!(!school_age.includes(4));
+        assert!(!school_age.includes(18));
+    }
+
+    #[test]
+    fn should_find_bounds_of_age_groups() {
+        assert_eq!(age_bounds("5-9"), Some((5, 9)));
+        assert_eq!(age_bounds(" 17 "), Some((17, 17)));
+        assert_eq!(age_bounds("80+"), Some((80, constants::OLDEST_AGE)));
+        assert_eq!(age_bounds("9-5"), None);
+        assert_eq!(age_bounds("unknown"), None);
+    }
+
+    #[test]
+    fn should_not_accept_building_sizes_without_room_or_share() {
+        assert!(GeographyParameters::new(100, 0.003).validate().is_ok());
+        let invalid = [vec![], vec![BuildingSize::new(0, 1.0)], vec![BuildingSize::new(4, 0.0)], vec![BuildingSize::new(4, 1.0), BuildingSize::new(9, -0.5)]];
+        for sizes in invalid.iter() {
+            let household_sizes = GeographyParameters { household_sizes: sizes.clone(), ..GeographyParameters::new(100, 0.003) };
+            assert!(household_sizes.validate().is_err(), "{:?}", sizes);
+            let office_sizes = GeographyParameters { office_sizes: sizes.clone(), ..GeographyParameters::new(100, 0.003) };
+            assert!(office_sizes.validate().is_err(), "{:?}", sizes);
+        }
+        let schools = SchoolsConfig { sizes: vec![], ..SchoolsConfig::default() };
+        assert!(GeographyParameters { schools: Some(schools), ..GeographyParameters::new(100, 0.003) }.validate().is_err());
+    }
+
+    #[test]
+    fn should_not_accept_icu_percentage_out_of_range() {
+        for icu_percentage in [-0.1, 1.5].iter() {
+            let hospitals = HospitalsConfig { icu_percentage: *icu_percentage, ..HospitalsConfig::default() };
+            assert!(GeographyParameters { hospitals, ..GeographyParameters::new(100, 0.003) }.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn should_read_age_distribution_of_auto_population() {
+        let json = r#"{"number_of_agents": 100, "public_transport_percentage": 0.2, "working_percentage": 0.7,
+            "age_distribution": [{"age": "0-4", "share": 0.2},
+                                 {"age": "20-24", "share": 0.8, "male_percentage": 0.52, "working_percentage": 0.6, "public_transport_percentage": 0.4}]}"#;
+
+        let auto_pop: AutoPopulation = serde_json::from_str(json).unwrap();
+
+        let adults = AgeGroup { age: "20-24".to_string(), share: 0.8, male_percentage: Some(0.52), working_percentage: Some(0.6),
+            public_transport_percentage: Some(0.4) };
+        assert_eq!(auto_pop.age_distribution, vec![AgeGroup::new("0-4", 0.2), adults]);
+        assert!(Population::Auto(auto_pop).validate().is_ok());
+    }
+
+    #[test]
+    fn should_not_accept_age_groups_with_invalid_shares_or_percentages() {
+        let auto_pop = |age_distribution: Vec<AgeGroup>| Population::Auto(AutoPopulation {
+            number_of_agents: 100, public_transport_percentage: 0.2, working_percentage: 0.7, age_distribution,
+        });
+        assert!(auto_pop(vec![]).validate().is_ok());
+
+        let male = AgeGroup { male_percentage: Some(1.2), ..AgeGroup::new("20-24", 0.8) };
+        let working = AgeGroup { working_percentage: Some(-0.1), ..AgeGroup::new("20-24", 0.8) };
+        let public_transport = AgeGroup { public_transport_percentage: Some(1.5), ..AgeGroup::new("20-24", 0.8) };
+        let invalid = [vec![male], vec![working], vec![public_transport], vec![AgeGroup::new("0-4", 0.0), AgeGroup::new("5-9", 0.0)],
+            vec![AgeGroup::new("0-4", 1.0), AgeGroup::new("5-9", -0.2)]];
+        for age_distribution in invalid.iter() {
+            assert!(auto_pop(age_distribution.clone()).validate().is_err(), "{:?}", age_distribution);
+        }
+    }
+
+    #[test]
+    fn should_map_fields_to_columns() {
+        let names: CsvPopulation = serde_json::from_str(r#"{"file": "pop.csv", "cols": ["age", "working"]}"#).unwrap();
+        let mapping: CsvPopulation = serde_json::from_str(r#"{"file": "pop.csv", "cols": {"age": "AGE_YEARS"}}"#).unwrap();
+
+        assert_eq!(names.cols.mapping().get("working"), Some(&"working".to_string()));
+        assert_eq!(names.cols.mapping().len(), 2);
+        assert_eq!(mapping.cols.mapping().get("age"), Some(&"AGE_YEARS".to_string()));
+        assert!(!mapping.skip_invalid_rows);
+    }
+
+    #[test]
+    fn should_read_synthetic_population() {
+        let json = r#"{"Synthetic": {"number_of_agents": 100, "age_distribution": [{"age": "0-4", "share": 1.0}],
+            "male_percentage": 0.5, "working_percentage": 0.7, "public_transport_percentage": 0.2}}"#;
+
+        let population: Population = serde_json::from_str(json).unwrap();
+
+        let expected = SyntheticPopulation {
+            number_of_agents: 100,
+            age_distribution: vec![AgeGroup::new("0-4", 1.0)],
+            male_percentage: 0.5,
+            working_percentage: 0.7,
+            public_transport_percentage: 0.2,
+            tolerance: constants::IPF_TOLERANCE,
+            max_iterations: constants::IPF_MAX_ITERATIONS,
+        };
+        assert_eq!(population, Population::Synthetic(expected));
+    }
+
+    #[test]
+    fn should_not_read_synthetic_population_with_invalid_margin() {
+        let mut json: serde_json::Value = serde_json::from_reader(File::open("config/test/auto_pop.json").unwrap()).unwrap();
+        json["population"] = serde_json::json!({"Synthetic": {"number_of_agents": 100, "age_distribution": [{"age": "0-4", "share": 1.0}],
+            "male_percentage": 0.5, "working_percentage": 0.7, "public_transport_percentage": 0.2}});
+        let path = std::env::temp_dir().join("synthetic_population_config.json");
+        serde_json::to_writer(File::create(&path).unwrap(), &json).unwrap();
+        assert!(read(path.to_str().unwrap().to_string()).is_ok());
+
+        json["population"]["Synthetic"]["male_percentage"] = serde_json::json!(1.5);
+        serde_json::to_writer(File::create(&path).unwrap(), &json).unwrap();
+        assert!(read(path.to_str().unwrap().to_string()).is_err());
+    }
 }
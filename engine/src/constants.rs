@@ -23,6 +23,7 @@ pub const HOUSE_AREA_RELATIVE_SIZE: Percentage = 0.4;
 pub const TRANSPORT_AREA_RELATIVE_SIZE: Percentage = 0.1;
 pub const WORK_AREA_RELATIVE_SIZE: Percentage = 0.2;
 pub const INITIAL_HOSPITAL_RELATIVE_SIZE: Percentage = 0.1;
+pub const SCHOOL_AREA_RELATIVE_SIZE: Percentage = 0.1;
 
 pub const NUMBER_OF_HOURS: Hour = 24;
 pub const ROUTINE_START_TIME: Hour = 0;
@@ -34,6 +35,8 @@ pub const ROUTINE_TRAVEL_END_TIME: Hour = 17;
 pub const ROUTINE_WORK_END_TIME: Hour = 16;
 pub const ROUTINE_END_TIME: Hour = 23;
 pub const NON_WORKING_TRAVEL_END_TIME: Hour = 12;
+pub const SCHOOL_START_TIME: Hour = 8;
+pub const SCHOOL_END_TIME: Hour = 14;
 
 pub const HOURS_IN_A_DAY: Hour = 24;
 pub const QUARANTINE_DAYS: Day = 14;
@@ -44,6 +47,15 @@ pub const HOSPITAL_STAFF_PERCENTAGE: Percentage = 0.002;
 
 pub const HOME_SIZE: Size = 2;
 pub const OFFICE_SIZE: Size = 10;
+pub const SCHOOL_SIZE: Size = 10;
+pub const SCHOOL_AGE_MIN: u32 = 5;
+pub const SCHOOL_AGE_MAX: u32 = 17;
+pub const ELDERLY_AGE: u32 = 60;
+pub const OLDEST_AGE: u32 = 100;
+
+pub const IPF_TOLERANCE: f64 = 1e-6;
+pub const IPF_MAX_ITERATIONS: u32 = 1000;
+pub const MAX_REPORTED_ROW_ERRORS: usize = 10;
 
 //TODO: Quick fix, move these field to input parameters
 pub const ASYMPTOMATIC_LAST_DAY: Day = 9;
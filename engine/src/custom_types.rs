@@ -28,9 +28,16 @@ pub type Percentage = f64;
 
 
 pub fn validate_percentage(value: &f64) -> Result<(), ValidationError> {
-    if value < &0.0 && value > &1.0 {
+    if !(0.0..=1.0).contains(value) {
         return Err(ValidationError::new("percentage value needs to be between 0 to 1"));
     }
     Ok(())
 }
 
+pub fn validate_non_negative(value: f64) -> Result<(), ValidationError> {
+    if value < 0.0 {
+        return Err(ValidationError::new("value needs to be at least 0"));
+    }
+    Ok(())
+}
+
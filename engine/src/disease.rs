@@ -17,14 +17,22 @@
  *
  */
 
+use std::collections::HashMap;
+
 use rand::Rng;
 use serde::Deserialize;
-use crate::custom_types::{Day, Hour, Percentage, validate_percentage};
-use validator::Validate;
+use crate::config::age_bounds;
+use crate::custom_types::{Day, Hour, Percentage, validate_percentage, validate_non_negative};
+use validator::{Validate, ValidationError};
 
 use crate::random_wrapper::RandomWrapper;
+use crate::transmission::TransmissionKernel;
+use crate::durations::{StageDurations, Timeline};
+use crate::infectiousness::InfectiousnessProfile;
+use crate::constants;
+use rand::seq::SliceRandom;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Validate)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Validate)]
 pub struct Disease {
     regular_transmission_start_day: Day,
     high_transmission_start_day: Day,
@@ -37,12 +45,103 @@ pub struct Disease {
     high_transmission_rate: Percentage,
     #[validate(custom = "validate_percentage")]
     death_rate: Percentage,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    non_hospitalized_death_rate: Option<Percentage>,
     #[validate(custom = "validate_percentage")]
     percentage_asymptomatic_population: Percentage,
     #[validate(custom = "validate_percentage")]
     percentage_severe_infected_population: Percentage,
     exposed_duration: Hour,
     pre_symptomatic_duration: Hour,
+    #[serde(default = "default_untreated_mortality_multiplier")]
+    untreated_mortality_multiplier: f64,
+    #[serde(default = "default_hospitalization_rate")]
+    #[validate(custom = "validate_percentage")]
+    hospitalization_rate: Percentage,
+    #[serde(default)]
+    #[validate]
+    risk_factors: Vec<RiskFactor>,
+    #[serde(default)]
+    #[validate]
+    transmission: TransmissionKernel,
+    #[serde(default)]
+    #[validate]
+    durations: StageDurations,
+    #[serde(default)]
+    #[validate]
+    infectiousness: Option<InfectiousnessProfile>,
+    #[serde(default = "default_multiplier")]
+    #[validate(custom = "validate_non_negative")]
+    asymptomatic_infectiousness: f64,
+    #[serde(default = "default_multiplier")]
+    #[validate(custom = "validate_non_negative")]
+    pre_symptomatic_infectiousness: f64,
+}
+
+fn default_untreated_mortality_multiplier() -> f64 {
+    1.0
+}
+
+fn default_hospitalization_rate() -> Percentage {
+    1.0
+}
+
+/// A comorbidity or other risk factor, multiplying the chances of a severe case, of a severe case needing a hospital,
+/// and of dying. Citizens have it as given by the population file, or else by its prevalence, which may differ by age group
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Validate)]
+pub struct RiskFactor {
+    name: String,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    prevalence: Percentage,
+    #[serde(default)]
+    #[validate(custom = "validate_prevalence_by_age")]
+    prevalence_by_age: HashMap<String, Percentage>,
+    #[serde(default = "default_multiplier")]
+    #[validate(custom = "validate_non_negative")]
+    severity_multiplier: f64,
+    #[serde(default = "default_multiplier")]
+    #[validate(custom = "validate_non_negative")]
+    hospitalization_multiplier: f64,
+    #[serde(default = "default_multiplier")]
+    #[validate(custom = "validate_non_negative")]
+    death_multiplier: f64,
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+fn validate_prevalence_by_age(prevalence_by_age: &HashMap<String, Percentage>) -> Result<(), ValidationError> {
+    prevalence_by_age.values().try_for_each(validate_percentage)
+}
+
+impl RiskFactor {
+    #[cfg(test)]
+    pub fn new(name: &str, prevalence: Percentage, severity_multiplier: f64, hospitalization_multiplier: f64,
+               death_multiplier: f64) -> RiskFactor {
+        RiskFactor {
+            name: name.to_string(),
+            prevalence,
+            prevalence_by_age: HashMap::new(),
+            severity_multiplier,
+            hospitalization_multiplier,
+            death_multiplier,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The prevalence in the age group of the age, if given, or else in the whole population
+    pub fn prevalence_at(&self, age: Option<u32>) -> Percentage {
+        age.and_then(|age| self.prevalence_by_age.iter()
+            .find(|(group, _)| matches!(age_bounds(group), Some((youngest, oldest)) if youngest <= age && age <= oldest))
+            .map(|(_, prevalence)| *prevalence))
+            .unwrap_or(self.prevalence)
+    }
 }
 
 impl Disease {
@@ -54,7 +153,7 @@ impl Disease {
         let reader = File::open(config_file_path).expect("Failed to open disease config file");
         let yaml: HashMap<String, Disease> = serde_yaml::from_reader(reader).expect("Failed to parse disease config file");
         let disease = yaml.get(disease_name).expect("Failed to find disease");
-        *disease
+        disease.clone()
     }
 
     #[cfg(test)]
@@ -69,10 +168,19 @@ impl Disease {
             regular_transmission_rate,
             high_transmission_rate,
             death_rate,
+            non_hospitalized_death_rate: None,
             percentage_asymptomatic_population,
             percentage_severe_infected_population,
             exposed_duration,
-            pre_symptomatic_duration
+            pre_symptomatic_duration,
+            untreated_mortality_multiplier: default_untreated_mortality_multiplier(),
+            hospitalization_rate: default_hospitalization_rate(),
+            risk_factors: Vec::new(),
+            transmission: TransmissionKernel::default(),
+            durations: StageDurations::default(),
+            infectiousness: None,
+            asymptomatic_infectiousness: default_multiplier(),
+            pre_symptomatic_infectiousness: default_multiplier(),
         }
     }
 
@@ -85,6 +193,29 @@ impl Disease {
         0.0
     }
 
+    /// The transmission rate by the infectiousness profile, if there is one, or else by the day of infection
+    pub fn get_infectiousness(&self, infection_day: Day, hours_infected: Hour) -> Percentage {
+        match &self.infectiousness {
+            Some(profile) => profile.rate_at(hours_infected),
+            None => self.get_current_transmission_rate(infection_day),
+        }
+    }
+
+    pub fn get_asymptomatic_infectiousness(&self) -> f64 {
+        self.asymptomatic_infectiousness
+    }
+
+    pub fn get_pre_symptomatic_infectiousness(&self) -> f64 {
+        self.pre_symptomatic_infectiousness
+    }
+
+    #[cfg(test)]
+    pub fn set_infectiousness(&mut self, infectiousness: InfectiousnessProfile, asymptomatic: f64, pre_symptomatic: f64) {
+        self.infectiousness = Some(infectiousness);
+        self.asymptomatic_infectiousness = asymptomatic;
+        self.pre_symptomatic_infectiousness = pre_symptomatic;
+    }
+
     pub fn to_be_hospitalized(&self, infection_day: Day) -> bool {
         let transmission_rate = self.get_current_transmission_rate(infection_day);
         if transmission_rate >= self.high_transmission_rate {
@@ -97,27 +228,94 @@ impl Disease {
         self.last_day
     }
 
-    pub fn to_be_deceased(&self, rng: &mut RandomWrapper) -> bool {
-        if rng.get().gen_bool(self.death_rate) {
-            return true;
+    /// Whether a severe case dies, and whether it would have survived if treated. Cases that were denied a
+    /// hospital bed, or an ICU bed they needed, die `untreated_mortality_multiplier` times as often. Cases that
+    /// never needed a hospital die at `non_hospitalized_death_rate`, if given
+    pub fn to_be_deceased(&self, rng: &mut RandomWrapper, hospitalized: bool, untreated: bool, comorbidities: &[String]) -> (bool, bool) {
+        let risk_multiplier = self.risk_multiplier(comorbidities, |factor| factor.death_multiplier);
+        let treated_death_rate = (self.death_rate * risk_multiplier).min(1.0);
+        let death_rate = if untreated {
+            (treated_death_rate * self.untreated_mortality_multiplier).min(1.0)
+        } else if hospitalized {
+            treated_death_rate
+        } else {
+            (self.non_hospitalized_death_rate.unwrap_or(self.death_rate) * risk_multiplier).min(1.0)
+        };
+        let draw: f64 = rng.get().gen();
+        let deceased = draw < death_rate;
+        (deceased, untreated && deceased && draw >= treated_death_rate)
+    }
+
+    /// The chance of a symptomatic case with the comorbidities becoming severe
+    pub fn get_severe_percentage(&self, comorbidities: &[String]) -> Percentage {
+        (self.percentage_severe_infected_population * self.risk_multiplier(comorbidities, |factor| factor.severity_multiplier)).min(1.0)
+    }
+
+    /// The chance of a severe case with the comorbidities needing a hospital
+    pub fn get_hospitalization_percentage(&self, comorbidities: &[String]) -> Percentage {
+        (self.hospitalization_rate * self.risk_multiplier(comorbidities, |factor| factor.hospitalization_multiplier)).min(1.0)
+    }
+
+    fn risk_multiplier(&self, comorbidities: &[String], multiplier: fn(&RiskFactor) -> f64) -> f64 {
+        self.risk_factors.iter()
+            .filter(|factor| comorbidities.contains(&factor.name))
+            .map(multiplier)
+            .product()
+    }
+
+    /// The hours a newly exposed citizen will spend in each stage. Incubation falls back to `exposed_duration`
+    /// give or take an hour, and the pre-symptomatic period to `pre_symptomatic_duration`
+    pub fn sample_timeline(&self, rng: &mut RandomWrapper) -> Timeline {
+        let incubation = match &self.durations.incubation {
+            Some(distribution) => distribution.sample(rng),
+            None => {
+                let random_factor = *constants::RANGE_FOR_EXPOSED.choose(rng.get()).unwrap();
+                (self.exposed_duration as i32 + random_factor).max(0) as Hour
+            }
+        };
+        let pre_symptomatic = match &self.durations.pre_symptomatic {
+            Some(distribution) => distribution.sample(rng),
+            None => self.pre_symptomatic_duration,
+        };
+        Timeline {
+            incubation,
+            pre_symptomatic,
+            infectious: self.durations.infectious.as_ref().map(|d| d.sample(rng)),
+            to_hospitalization: self.durations.time_to_hospitalization.as_ref().map(|d| d.sample(rng)),
+            to_death: self.durations.time_to_death.as_ref().map(|d| d.sample(rng)),
         }
-        false
     }
 
-    pub fn get_percentage_asymptomatic_population(&self) -> Percentage {
-        self.percentage_asymptomatic_population
+    #[cfg(test)]
+    pub fn set_durations(&mut self, durations: StageDurations) {
+        self.durations = durations;
+    }
+
+    pub fn get_transmission(&self) -> &TransmissionKernel {
+        &self.transmission
     }
 
-    pub fn get_percentage_severe_infected_population(&self) -> Percentage {
-        self.percentage_severe_infected_population
+    pub fn get_risk_factors(&self) -> &[RiskFactor] {
+        &self.risk_factors
     }
 
-    pub fn get_exposed_duration(&self) -> Hour {
-        self.exposed_duration
+    #[cfg(test)]
+    pub fn set_risk_factors(&mut self, risk_factors: Vec<RiskFactor>) {
+        self.risk_factors = risk_factors;
     }
 
-    pub fn get_pre_symptomatic_duration(&self) -> Hour {
-        self.pre_symptomatic_duration
+    #[cfg(test)]
+    pub fn set_untreated_mortality_multiplier(&mut self, multiplier: f64) {
+        self.untreated_mortality_multiplier = multiplier;
+    }
+
+    #[cfg(test)]
+    pub fn set_non_hospitalized_death_rate(&mut self, death_rate: Percentage) {
+        self.non_hospitalized_death_rate = Some(death_rate);
+    }
+
+    pub fn get_percentage_asymptomatic_population(&self) -> Percentage {
+        self.percentage_asymptomatic_population
     }
 }
 
@@ -141,6 +339,7 @@ impl DiseaseOverride {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::durations::DurationDistribution;
 
     #[test]
     fn get_current_transmission_rate() {
@@ -174,11 +373,144 @@ mod tests {
             regular_transmission_rate: 0.05,
             high_transmission_rate: 0.5,
             death_rate: 0.2,
+            non_hospitalized_death_rate: None,
             percentage_asymptomatic_population: 0.0,
             percentage_severe_infected_population: 1.0,
             exposed_duration: 0,
-            pre_symptomatic_duration: 0
+            pre_symptomatic_duration: 0,
+            untreated_mortality_multiplier: 1.0,
+            hospitalization_rate: 1.0,
+            risk_factors: Vec::new(),
+            transmission: TransmissionKernel::default(),
+            durations: StageDurations::default(),
+            infectiousness: None,
+            asymptomatic_infectiousness: 1.0,
+            pre_symptomatic_infectiousness: 1.0,
         };
         assert_eq!(expected, disease)
     }
+
+    #[test]
+    fn should_decease_untreated_cases_more_often() {
+        let mut disease = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        disease.set_untreated_mortality_multiplier(5.0);
+        let mut rng = RandomWrapper::new();
+
+        let outcomes: Vec<(bool, bool)> = (0..1000).map(|_| disease.to_be_deceased(&mut rng, false, true, &[])).collect();
+
+        assert!(outcomes.iter().all(|(deceased, _)| *deceased));
+        let excess_deaths = outcomes.iter().filter(|(_, excess)| *excess).count();
+        assert!(excess_deaths > 700 && excess_deaths < 900);
+    }
+
+    #[test]
+    fn should_not_count_excess_deaths_for_treated_cases() {
+        let mut disease = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        disease.set_untreated_mortality_multiplier(5.0);
+        let mut rng = RandomWrapper::new();
+
+        let outcomes: Vec<(bool, bool)> = (0..1000).map(|_| disease.to_be_deceased(&mut rng, true, false, &[])).collect();
+
+        assert!(outcomes.iter().all(|(_, excess)| !*excess));
+        let deaths = outcomes.iter().filter(|(deceased, _)| *deceased).count();
+        assert!(deaths > 100 && deaths < 300);
+    }
+
+    #[test]
+    fn should_decease_non_hospitalized_cases_at_their_own_rate() {
+        let mut disease = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.0, 0.3, 0.3, 48, 48);
+        disease.set_non_hospitalized_death_rate(1.0);
+        let mut rng = RandomWrapper::new();
+
+        assert!((0..100).all(|_| disease.to_be_deceased(&mut rng, false, false, &[]) == (true, false)));
+        assert!((0..100).all(|_| disease.to_be_deceased(&mut rng, true, false, &[]) == (false, false)));
+    }
+
+    #[test]
+    fn should_multiply_chances_by_risk_factors() {
+        let mut disease = Disease::new(5, 20, 40, 9, 12, 0.025, 0.25, 0.2, 0.3, 0.3, 48, 48);
+        disease.set_risk_factors(vec![RiskFactor::new("diabetes", 0.1, 2.0, 0.5, 3.0),
+                                      RiskFactor::new("hypertension", 0.2, 2.0, 1.0, 10.0)]);
+        let diabetes = vec!["diabetes".to_string()];
+        let both = vec!["diabetes".to_string(), "hypertension".to_string()];
+
+        assert_eq!(disease.get_severe_percentage(&[]), 0.3);
+        assert_eq!(disease.get_severe_percentage(&diabetes), 0.6);
+        assert_eq!(disease.get_severe_percentage(&both), 1.0);
+        assert_eq!(disease.get_hospitalization_percentage(&diabetes), 0.5);
+        assert_eq!(disease.get_hospitalization_percentage(&["asthma".to_string()]), 1.0);
+
+        let mut rng = RandomWrapper::new();
+        assert!((0..100).all(|_| disease.to_be_deceased(&mut rng, true, false, &both) == (true, false)));
+    }
+
+    #[test]
+    fn should_find_prevalence_by_age_group() {
+        let json = r#"{"name": "diabetes", "prevalence": 0.05, "prevalence_by_age": {"60+": 0.2, "40-59": 0.1}}"#;
+        let risk_factor: RiskFactor = serde_json::from_str(json).unwrap();
+
+        assert_eq!(risk_factor.prevalence_at(Some(70)), 0.2);
+        assert_eq!(risk_factor.prevalence_at(Some(45)), 0.1);
+        assert_eq!(risk_factor.prevalence_at(Some(20)), 0.05);
+        assert_eq!(risk_factor.prevalence_at(None), 0.05);
+        assert_eq!(risk_factor.death_multiplier, 1.0);
+    }
+
+    #[test]
+    fn should_not_validate_risk_factors_with_invalid_prevalence_or_negative_multipliers() {
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 24, 24);
+        disease.set_risk_factors(vec![RiskFactor::new("diabetes", 0.1, 2.0, 0.5, 3.0)]);
+        assert!(disease.validate().is_ok());
+
+        let invalid = [RiskFactor::new("diabetes", 1.1, 2.0, 0.5, 3.0), RiskFactor::new("diabetes", 0.1, -2.0, 0.5, 3.0),
+            RiskFactor::new("diabetes", 0.1, 2.0, -0.5, 3.0), RiskFactor::new("diabetes", 0.1, 2.0, 0.5, -3.0)];
+        for risk_factor in invalid.iter() {
+            disease.set_risk_factors(vec![risk_factor.clone()]);
+            assert!(disease.validate().is_err(), "{:?}", risk_factor);
+        }
+
+        let json = r#"{"name": "diabetes", "prevalence": 0.05, "prevalence_by_age": {"60+": 1.2}}"#;
+        let risk_factor: RiskFactor = serde_json::from_str(json).unwrap();
+        assert!(risk_factor.validate().is_err());
+    }
+
+    #[test]
+    fn should_not_validate_disease_with_invalid_durations() {
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 24, 24);
+        assert!(disease.validate().is_ok());
+
+        disease.set_durations(StageDurations {
+            incubation: Some(DurationDistribution::Empirical { edges: vec![0.0, 24.0], weights: vec![1.0, 1.0] }),
+            ..StageDurations::default()
+        });
+        assert!(disease.validate().is_err());
+    }
+
+    #[test]
+    fn should_not_validate_disease_with_invalid_infectiousness() {
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 24, 24);
+        disease.set_infectiousness(InfectiousnessProfile::Gamma { shape: 0.5, scale: 24.0, peak_rate: 0.1 }, 1.0, 1.0);
+
+        assert!(disease.validate().is_err());
+    }
+
+    #[test]
+    fn should_not_validate_non_hospitalized_death_rate_out_of_range() {
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 24, 24);
+        disease.set_non_hospitalized_death_rate(0.5);
+        assert!(disease.validate().is_ok());
+
+        disease.set_non_hospitalized_death_rate(1.5);
+        assert!(disease.validate().is_err());
+    }
+
+    #[test]
+    fn should_not_validate_disease_with_negative_infectiousness_multiplier() {
+        let mut disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 24, 24);
+        disease.set_infectiousness(InfectiousnessProfile::Daily(vec![0.1]), -0.5, 1.0);
+        assert!(disease.validate().is_err());
+
+        disease.set_infectiousness(InfectiousnessProfile::Daily(vec![0.1]), 1.0, -0.5);
+        assert!(disease.validate().is_err());
+    }
 }
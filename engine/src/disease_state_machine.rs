@@ -21,8 +21,8 @@ use crate::disease::Disease;
 use crate::random_wrapper::RandomWrapper;
 use rand::Rng;
 use crate::constants;
-use rand::seq::SliceRandom;
 use crate::custom_types::{Day, Hour};
+use crate::durations::Timeline;
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum State {
@@ -44,6 +44,20 @@ pub enum InfectionSeverity {
 pub struct DiseaseStateMachine {
     pub state: State,
     infection_day: Day,
+    #[serde(default)]
+    excess_death: bool,
+    #[serde(default = "default_needs_hospital")]
+    needs_hospital: bool,
+    #[serde(default)]
+    timeline: Option<Timeline>,
+    #[serde(default)]
+    infected_at: Hour,
+    #[serde(default)]
+    symptoms_at: Hour,
+}
+
+fn default_needs_hospital() -> bool {
+    true
 }
 
 impl DiseaseStateMachine {
@@ -51,9 +65,19 @@ impl DiseaseStateMachine {
         DiseaseStateMachine {
             state: State::Susceptible {},
             infection_day: 0,
+            excess_death: false,
+            needs_hospital: true,
+            timeline: None,
+            infected_at: 0,
+            symptoms_at: 0,
         }
     }
 
+    /// The durations of the stages, sampled when first needed
+    fn timeline(&mut self, rng: &mut RandomWrapper, disease: &Disease) -> Timeline {
+        *self.timeline.get_or_insert_with(|| disease.sample_timeline(rng))
+    }
+
     pub fn get_infection_day(self) -> Day {
         match self.state {
             State::Infected { .. } => {
@@ -63,6 +87,19 @@ impl DiseaseStateMachine {
         }
     }
 
+    pub fn hours_infected(&self, sim_hr: Hour) -> Hour {
+        sim_hr.saturating_sub(self.infected_at)
+    }
+
+    /// How infectious the citizen is relative to a symptomatic case
+    pub fn relative_infectiousness(&self, disease: &Disease) -> f64 {
+        match self.state {
+            State::Infected { symptoms: false, .. } => disease.get_asymptomatic_infectiousness(),
+            State::Infected { severity: InfectionSeverity::Pre { .. }, .. } => disease.get_pre_symptomatic_infectiousness(),
+            _ => 1.0
+        }
+    }
+
     pub fn expose(&mut self, current_hour: Hour) {
         match self.state {
             State::Susceptible {} => {
@@ -77,16 +114,14 @@ impl DiseaseStateMachine {
     pub fn infect(&mut self, rng: &mut RandomWrapper, sim_hr: Hour, disease: &Disease) -> bool {
         match self.state {
             State::Exposed { at_hour } => {
-                let option = constants::RANGE_FOR_EXPOSED.choose(rng.get());
-                // info!("option: {:?}", option);
-                let random_factor = *option.unwrap();
-                if sim_hr - at_hour >= (disease.get_exposed_duration() as i32 + random_factor) as Hour {
+                if sim_hr.saturating_sub(at_hour) >= self.timeline(rng, disease).incubation {
                     let symptoms = rng.get().gen_bool(1.0 - disease.get_percentage_asymptomatic_population());
                     let mut severity = InfectionSeverity::Pre { at_hour: sim_hr };
                     if !symptoms {
                         severity = InfectionSeverity::Mild {};
                     }
                     self.state = State::Infected { symptoms, severity };
+                    self.infected_at = sim_hr;
                     return true;
                 }
                 false
@@ -97,18 +132,20 @@ impl DiseaseStateMachine {
         }
     }
 
-    pub fn change_infection_severity(&mut self, current_hour: Hour, rng: &mut RandomWrapper, disease: &Disease) {
+    pub fn change_infection_severity(&mut self, current_hour: Hour, rng: &mut RandomWrapper, disease: &Disease, comorbidities: &[String]) {
         match self.state {
             State::Infected { symptoms: true, severity } => {
                 match severity {
                     InfectionSeverity::Pre { at_hour } => {
-                        if current_hour - at_hour >= disease.get_pre_symptomatic_duration() {
+                        if current_hour.saturating_sub(at_hour) >= self.timeline(rng, disease).pre_symptomatic {
                             let mut severity = InfectionSeverity::Mild {};
-                            let severe = rng.get().gen_bool(disease.get_percentage_severe_infected_population());
+                            let severe = rng.get().gen_bool(disease.get_severe_percentage(comorbidities));
                             if severe {
                                 severity = InfectionSeverity::Severe {};
+                                self.needs_hospital = rng.get().gen_bool(disease.get_hospitalization_percentage(comorbidities));
                             }
                             self.state = State::Infected { symptoms: true, severity };
+                            self.symptoms_at = current_hour;
                         }
                     }
                     _ => {}
@@ -120,11 +157,16 @@ impl DiseaseStateMachine {
         }
     }
 
-    pub fn hospitalize(&mut self, disease: &Disease, immunity: i32) -> bool {
+    pub fn hospitalize(&mut self, sim_hr: Hour, rng: &mut RandomWrapper, disease: &Disease, immunity: i32) -> bool {
         match self.state {
-            State::Infected { symptoms: true, severity: InfectionSeverity::Severe} =>
-            // why we are adding immunity in infection day
-                disease.to_be_hospitalized((self.infection_day as i32 + immunity) as Day),
+            State::Infected { symptoms: true, severity: InfectionSeverity::Severe} => {
+                let due = match self.timeline(rng, disease).to_hospitalization {
+                    Some(hours) => sim_hr.saturating_sub(self.symptoms_at) >= hours,
+                    // why we are adding immunity in infection day
+                    None => disease.to_be_hospitalized((self.infection_day as i32 + immunity) as Day),
+                };
+                self.needs_hospital && due
+            }
             State::Infected { .. } => { false }
             _ => {
                 panic!("Invalid state transition!")
@@ -132,12 +174,25 @@ impl DiseaseStateMachine {
         }
     }
 
-    pub fn decease(&mut self, rng: &mut RandomWrapper, disease: &Disease) -> (i32, i32) {
+    pub fn decease(&mut self, sim_hr: Hour, rng: &mut RandomWrapper, disease: &Disease, hospitalized: bool, untreated: bool,
+                   comorbidities: &[String]) -> (i32, i32) {
+        let timeline = self.timeline(rng, disease);
+        let (infected_at, infection_day) = (self.infected_at, self.infection_day);
+        let recovered = |last_day: Day| match timeline.infectious {
+            Some(hours) => sim_hr.saturating_sub(infected_at) >= hours,
+            None => infection_day == last_day,
+        };
         match self.state {
             State::Infected { symptoms: true, severity: InfectionSeverity::Severe {} } => {
-                if self.infection_day == disease.get_disease_last_day() {
-                    if disease.to_be_deceased(rng) {
+                let outcome = match timeline.to_death {
+                    Some(hours) => sim_hr.saturating_sub(self.symptoms_at) >= hours,
+                    None => self.infection_day == disease.get_disease_last_day(),
+                };
+                if outcome {
+                    let (deceased, excess_death) = disease.to_be_deceased(rng, hospitalized, untreated, comorbidities);
+                    if deceased {
                         self.state = State::Deceased {};
+                        self.excess_death = excess_death;
                         return (1, 0);
                     }
                     self.state = State::Recovered {};
@@ -145,13 +200,13 @@ impl DiseaseStateMachine {
                 }
             }
             State::Infected { symptoms:true, severity: InfectionSeverity::Mild{} } => {
-                if self.infection_day == constants::MILD_INFECTED_LAST_DAY {
+                if recovered(constants::MILD_INFECTED_LAST_DAY) {
                     self.state = State::Recovered {};
                     return (0, 1);
                 }
             }
             State::Infected { .. } => {
-                if self.infection_day == constants::ASYMPTOMATIC_LAST_DAY {
+                if recovered(constants::ASYMPTOMATIC_LAST_DAY) {
                     self.state = State::Recovered {};
                     return (0, 1);
                 }
@@ -216,6 +271,25 @@ impl DiseaseStateMachine {
         }
     }
 
+    /// Whether a severe case reached the time to death sampled for it, which is checked every hour
+    pub fn is_outcome_due(&self, sim_hr: Hour) -> bool {
+        self.is_severe() && matches!(self.timeline, Some(Timeline { to_death: Some(hours), .. })
+            if sim_hr.saturating_sub(self.symptoms_at) >= hours)
+    }
+
+    pub fn is_severe(&self) -> bool {
+        matches!(self.state, State::Infected { symptoms: true, severity: InfectionSeverity::Severe })
+    }
+
+    /// Deceased for lack of hospital capacity, and would have survived if treated
+    pub fn is_excess_death(&self) -> bool {
+        self.excess_death
+    }
+
+    pub fn is_recovered(&self) -> bool {
+        matches!(self.state, State::Recovered {})
+    }
+
     pub fn is_deceased(&self) -> bool {
         match self.state {
             State::Deceased {} => {
@@ -273,6 +347,8 @@ impl DiseaseStateMachine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::disease::RiskFactor;
+    use crate::durations::{DurationDistribution, StageDurations};
 
     #[test]
     fn should_initialize() {
@@ -323,7 +399,7 @@ mod tests {
     fn should_panic() {
         let disease = Disease::init("config/diseases.yaml", &String::from("small_pox"));
         let mut machine = DiseaseStateMachine::new();
-        machine.hospitalize(&disease, 2);
+        machine.hospitalize(0, &mut RandomWrapper::new(), &disease, 2);
     }
 
     #[test]
@@ -334,7 +410,7 @@ mod tests {
 
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
 
-        machine.change_infection_severity(140, &mut rng, &disease);
+        machine.change_infection_severity(140, &mut rng, &disease, &[]);
 
         let result = match machine.state {
             State::Infected { symptoms: true, severity } => {
@@ -359,7 +435,7 @@ mod tests {
 
         machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 100 } };
 
-        machine.change_infection_severity(120, &mut rng, &disease);
+        machine.change_infection_severity(120, &mut rng, &disease, &[]);
 
         let result = match machine.state {
             State::Infected { symptoms: true, severity } => {
@@ -403,6 +479,77 @@ mod tests {
         assert_eq!(machine.infection_day, 1);
     }
 
+    fn disease_with_durations(percentage_asymptomatic_population: f64, durations: StageDurations) -> Disease {
+        let mut disease = Disease::new(10, 20, 40, 9, 12, 0.025, 0.25, 1.0, percentage_asymptomatic_population, 1.0, 24, 24);
+        disease.set_durations(durations);
+        disease
+    }
+
+    #[test]
+    fn should_infect_after_sampled_incubation() {
+        let disease = disease_with_durations(0.0, StageDurations {
+            incubation: Some(DurationDistribution::Fixed { hours: 30.0 }),
+            ..StageDurations::default()
+        });
+        let mut rng = RandomWrapper::new();
+        let mut machine = DiseaseStateMachine::new();
+        machine.expose(10);
+
+        assert!(!machine.infect(&mut rng, 39, &disease));
+        assert!(machine.infect(&mut rng, 40, &disease));
+    }
+
+    #[test]
+    fn should_recover_after_sampled_infectious_period() {
+        let disease = disease_with_durations(1.0, StageDurations {
+            incubation: Some(DurationDistribution::Fixed { hours: 0.0 }),
+            infectious: Some(DurationDistribution::Fixed { hours: 48.0 }),
+            ..StageDurations::default()
+        });
+        let mut rng = RandomWrapper::new();
+        let mut machine = DiseaseStateMachine::new();
+        machine.expose(0);
+        machine.infect(&mut rng, 2, &disease);
+
+        assert_eq!(machine.decease(49, &mut rng, &disease, false, false, &[]), (0, 0));
+        assert_eq!(machine.decease(50, &mut rng, &disease, false, false, &[]), (0, 1));
+    }
+
+    #[test]
+    fn should_hospitalize_and_decease_severe_cases_after_sampled_times_from_symptoms() {
+        let disease = disease_with_durations(0.0, StageDurations {
+            incubation: Some(DurationDistribution::Fixed { hours: 0.0 }),
+            pre_symptomatic: Some(DurationDistribution::Fixed { hours: 0.0 }),
+            time_to_hospitalization: Some(DurationDistribution::Fixed { hours: 24.0 }),
+            time_to_death: Some(DurationDistribution::Fixed { hours: 100.0 }),
+            ..StageDurations::default()
+        });
+        let mut rng = RandomWrapper::new();
+        let mut machine = DiseaseStateMachine::new();
+        machine.expose(0);
+        machine.infect(&mut rng, 0, &disease);
+        machine.change_infection_severity(10, &mut rng, &disease, &[]);
+        assert!(machine.is_severe());
+
+        assert!(!machine.hospitalize(33, &mut rng, &disease, 0));
+        assert!(machine.hospitalize(34, &mut rng, &disease, 0));
+        assert_eq!(machine.decease(109, &mut rng, &disease, false, false, &[]), (0, 0));
+        assert_eq!(machine.decease(110, &mut rng, &disease, false, false, &[]), (1, 0));
+    }
+
+    #[test]
+    fn should_not_hospitalize_severe_cases_not_needing_hospital() {
+        let mut disease = Disease::new(0, 0, 40, 9, 12, 0.025, 0.25, 0.2, 0.0, 1.0, 0, 0);
+        disease.set_risk_factors(vec![RiskFactor::new("frail", 0.0, 1.0, 0.0, 1.0)]);
+        let mut rng = RandomWrapper::new();
+        let mut machine = DiseaseStateMachine::new();
+        machine.state = State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 0 } };
+        machine.change_infection_severity(2, &mut rng, &disease, &["frail".to_string()]);
+
+        assert!(machine.is_severe());
+        assert!(!machine.hospitalize(2, &mut rng, &disease, 2));
+    }
+
     #[test]
     fn should_set_severe_infected() {
         let mut machine = DiseaseStateMachine::new();
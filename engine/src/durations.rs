@@ -0,0 +1,239 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::custom_types::Hour;
+use crate::random_wrapper::RandomWrapper;
+
+/// A distribution of durations in hours
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum DurationDistribution {
+    Fixed { hours: f64 },
+    Uniform { min: f64, max: f64 },
+    Gamma { shape: f64, scale: f64 },
+    /// With the mean and standard deviation of the logarithm of the duration
+    LogNormal { mu: f64, sigma: f64 },
+    /// Picks a bin by its weight, and a duration within it uniformly. `edges` bound the bins, one more than the weights
+    Empirical { edges: Vec<f64>, weights: Vec<f64> },
+}
+
+impl DurationDistribution {
+    pub fn sample(&self, rng: &mut RandomWrapper) -> Hour {
+        let hours = match self {
+            DurationDistribution::Fixed { hours } => *hours,
+            DurationDistribution::Uniform { min, max } => {
+                if min < max { rng.get().gen_range(*min..*max) } else { *min }
+            }
+            DurationDistribution::Gamma { shape, scale } => sample_gamma(*shape, rng) * scale,
+            DurationDistribution::LogNormal { mu, sigma } => (mu + sigma * sample_standard_normal(rng)).exp(),
+            DurationDistribution::Empirical { edges, weights } => {
+                if edges.len() != weights.len() + 1 {
+                    panic!("An empirical distribution needs one edge more than weights, got {} and {}", edges.len(), weights.len());
+                }
+                let bin = rng.get().sample(WeightedIndex::new(weights).expect("Invalid empirical distribution weights"));
+                let (low, high) = (edges[bin], edges[bin + 1]);
+                if low < high { rng.get().gen_range(low..high) } else { low }
+            }
+        };
+        hours.max(0.0).round() as Hour
+    }
+
+    fn invalid_reason(&self) -> Option<&'static str> {
+        match self {
+            DurationDistribution::Fixed { hours } if *hours < 0.0 => Some("a fixed duration cannot be negative"),
+            DurationDistribution::Uniform { min, max } if min > max => Some("the min of a uniform duration cannot be above its max"),
+            DurationDistribution::Gamma { shape, scale } if *shape <= 0.0 || *scale <= 0.0 => {
+                Some("the shape and scale of a gamma duration must be positive")
+            }
+            DurationDistribution::LogNormal { sigma, .. } if *sigma < 0.0 => Some("the sigma of a lognormal duration cannot be negative"),
+            DurationDistribution::Empirical { edges, weights } => {
+                if edges.len() != weights.len() + 1 {
+                    Some("an empirical duration needs one edge more than weights")
+                } else if edges.windows(2).any(|bin| bin[0] > bin[1]) {
+                    Some("the edges of an empirical duration must be in increasing order")
+                } else if weights.iter().any(|weight| *weight < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+                    Some("the weights of an empirical duration cannot be negative, and at least one must be positive")
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Validate for DurationDistribution {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.invalid_reason() {
+            None => Ok(()),
+            Some(reason) => {
+                let mut errors = ValidationErrors::new();
+                errors.add("distribution", ValidationError::new(reason));
+                Err(errors)
+            }
+        }
+    }
+}
+
+fn sample_standard_normal(rng: &mut RandomWrapper) -> f64 {
+    let u1: f64 = 1.0 - rng.get().gen::<f64>();
+    let u2: f64 = rng.get().gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Marsaglia and Tsang's method, boosted for shapes below 1
+fn sample_gamma(shape: f64, rng: &mut RandomWrapper) -> f64 {
+    if shape <= 0.0 {
+        panic!("The shape of a gamma distribution must be positive, got {}", shape);
+    }
+    if shape < 1.0 {
+        let u: f64 = 1.0 - rng.get().gen::<f64>();
+        return sample_gamma(shape + 1.0, rng) * u.powf(1.0 / shape);
+    }
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = sample_standard_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = 1.0 - rng.get().gen::<f64>();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+/// Distributions of the time spent in each stage, sampled for every citizen. A stage left out keeps the fixed
+/// durations of the disease. `infectious` runs from infection to recovery of mild and asymptomatic cases, and
+/// `time_to_hospitalization` and `time_to_death` from the onset of symptoms of severe cases, the latter to their
+/// recovery too
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default, Validate)]
+#[serde(default)]
+pub struct StageDurations {
+    #[validate]
+    pub incubation: Option<DurationDistribution>,
+    #[validate]
+    pub pre_symptomatic: Option<DurationDistribution>,
+    #[validate]
+    pub infectious: Option<DurationDistribution>,
+    #[validate]
+    pub time_to_hospitalization: Option<DurationDistribution>,
+    #[validate]
+    pub time_to_death: Option<DurationDistribution>,
+}
+
+/// The hours a citizen spends in each stage, as sampled for them. None where the stage has no distribution
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Timeline {
+    pub incubation: Hour,
+    pub pre_symptomatic: Hour,
+    pub infectious: Option<Hour>,
+    pub to_hospitalization: Option<Hour>,
+    pub to_death: Option<Hour>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mean_of(distribution: &DurationDistribution) -> f64 {
+        let mut rng = RandomWrapper::new();
+        let samples = 20000;
+        (0..samples).map(|_| distribution.sample(&mut rng) as f64).sum::<f64>() / samples as f64
+    }
+
+    #[test]
+    fn should_sample_fixed_and_uniform_durations() {
+        let mut rng = RandomWrapper::new();
+        assert_eq!(DurationDistribution::Fixed { hours: 36.4 }.sample(&mut rng), 36);
+
+        let uniform = DurationDistribution::Uniform { min: 24.0, max: 48.0 };
+        assert!((0..100).map(|_| uniform.sample(&mut rng)).all(|hours| (24..=48).contains(&hours)));
+    }
+
+    #[test]
+    fn should_sample_gamma_durations_around_their_mean() {
+        let mean = mean_of(&DurationDistribution::Gamma { shape: 4.0, scale: 30.0 });
+        assert!((mean - 120.0).abs() < 5.0, "mean was {}", mean);
+
+        let mean = mean_of(&DurationDistribution::Gamma { shape: 0.5, scale: 100.0 });
+        assert!((mean - 50.0).abs() < 5.0, "mean was {}", mean);
+    }
+
+    #[test]
+    fn should_sample_lognormal_durations_around_their_median() {
+        let mut rng = RandomWrapper::new();
+        let distribution = DurationDistribution::LogNormal { mu: 100.0_f64.ln(), sigma: 0.5 };
+        let below_median = (0..10000).filter(|_| distribution.sample(&mut rng) < 100).count();
+        assert!((below_median as f64 / 10000.0 - 0.5).abs() < 0.03, "{} below the median", below_median);
+    }
+
+    #[test]
+    fn should_sample_empirical_durations_within_weighted_bins() {
+        let mut rng = RandomWrapper::new();
+        let distribution = DurationDistribution::Empirical { edges: vec![0.0, 24.0, 48.0, 72.0], weights: vec![0.0, 1.0, 0.0] };
+        assert!((0..100).map(|_| distribution.sample(&mut rng)).all(|hours| (24..=48).contains(&hours)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_on_mismatched_empirical_bins() {
+        let distribution = DurationDistribution::Empirical { edges: vec![0.0, 24.0], weights: vec![1.0, 1.0] };
+        distribution.sample(&mut RandomWrapper::new());
+    }
+
+    #[test]
+    fn should_read_stage_durations() {
+        let durations: StageDurations = serde_json::from_str(
+            r#"{"incubation": {"Gamma": {"shape": 5.8, "scale": 22.0}}, "time_to_death": {"Fixed": {"hours": 400}}}"#).unwrap();
+
+        assert_eq!(durations.incubation, Some(DurationDistribution::Gamma { shape: 5.8, scale: 22.0 }));
+        assert_eq!(durations.time_to_death, Some(DurationDistribution::Fixed { hours: 400.0 }));
+        assert_eq!(durations.infectious, None);
+    }
+
+    #[test]
+    fn should_reject_invalid_distributions_when_validated() {
+        let invalid = vec![
+            DurationDistribution::Fixed { hours: -1.0 },
+            DurationDistribution::Uniform { min: 48.0, max: 24.0 },
+            DurationDistribution::Gamma { shape: 0.0, scale: 24.0 },
+            DurationDistribution::Gamma { shape: 2.0, scale: -1.0 },
+            DurationDistribution::LogNormal { mu: 4.0, sigma: -0.5 },
+            DurationDistribution::Empirical { edges: vec![0.0, 24.0], weights: vec![1.0, 1.0] },
+            DurationDistribution::Empirical { edges: vec![48.0, 24.0], weights: vec![1.0] },
+            DurationDistribution::Empirical { edges: vec![0.0, 24.0], weights: vec![0.0] },
+        ];
+        for distribution in invalid {
+            assert!(distribution.validate().is_err(), "{:?}", distribution);
+            let durations = StageDurations { time_to_death: Some(distribution), ..StageDurations::default() };
+            assert!(durations.validate().is_err());
+        }
+
+        let valid = DurationDistribution::Empirical { edges: vec![0.0, 24.0, 72.0], weights: vec![1.0, 3.0] };
+        assert!(valid.validate().is_ok());
+        assert!(StageDurations { incubation: Some(valid), ..StageDurations::default() }.validate().is_ok());
+    }
+}
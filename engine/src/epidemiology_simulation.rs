@@ -19,6 +19,7 @@
 
 use core::borrow::Borrow;
 use core::borrow::BorrowMut;
+use std::collections::HashSet;
 use std::time::{Duration, Instant, SystemTime};
 
 use chrono::{DateTime, Local};
@@ -27,7 +28,8 @@ use futures::StreamExt;
 use rand::Rng;
 use rdkafka::consumer::{DefaultConsumerContext, MessageStream};
 
-use crate::{constants, RunMode, ticks_consumer, travel_consumer};
+use crate::{RunMode, ticks_consumer, travel_consumer};
+use crate::agent;
 use crate::agent::Citizen;
 use crate::allocation_map::AgentLocationMap;
 use crate::commute::{CommutePlan, Commuter, CommutersByRegion};
@@ -37,10 +39,13 @@ use crate::custom_types::{Count, Hour};
 use crate::disease::Disease;
 use crate::disease_state_machine::State;
 use crate::geography;
-use crate::geography::{Grid, Point};
+use crate::geography::{Area, Grid, HospitalOccupancy, Point};
 use crate::interventions::hospital::BuildNewHospital;
-use crate::interventions::Interventions;
+use crate::interventions::{InterventionCommand, Interventions};
 use crate::interventions::lockdown::LockdownIntervention;
+use crate::interventions::school_closure::SchoolClosureIntervention;
+use crate::interventions::masks::MaskIntervention;
+use crate::interventions::travel_restriction::TravelRestrictions;
 use crate::interventions::vaccination::VaccinateIntervention;
 use crate::kafka_consumer::TravelPlanConfig;
 use crate::kafka_producer::{COMMUTE_TOPIC, KafkaProducer, MIGRATION_TOPIC, TickAck};
@@ -49,9 +54,14 @@ use crate::listeners::disease_tracker::Hotspot;
 use crate::listeners::events::counts::Counts;
 use crate::listeners::events_kafka_producer::EventsKafkaProducer;
 use crate::listeners::intervention_reporter::InterventionReporter;
+use crate::listeners::risk_groups::RiskGroupsReporter;
+use crate::listeners::stratified_counts::StratifiedCountsReporter;
 use crate::listeners::listener::{Listener, Listeners};
 use crate::listeners::travel_counter::TravelCounter;
 use crate::random_wrapper::RandomWrapper;
+use crate::schedule::{CommuteHours, Schedules};
+use crate::calendar::Calendar;
+use crate::isolation::SelfIsolation;
 use crate::ticks_consumer::Tick;
 use crate::travel_plan::{EngineMigrationPlan, MigrationPlan, Migrator, MigratorsByRegion};
 
@@ -60,6 +70,9 @@ pub struct Epidemiology {
     pub write_agent_location_map: AgentLocationMap,
     pub grid: Grid,
     pub disease: Disease,
+    pub schedules: Schedules,
+    pub calendar: Calendar,
+    pub self_isolation: Option<SelfIsolation>,
     pub sim_id: String,
 }
 
@@ -68,19 +81,35 @@ impl Epidemiology {
         let start = Instant::now();
         let disease = config.get_disease();
         let start_infections = config.get_starting_infections();
-        let mut grid = geography::define_geography(config.get_grid_size(), sim_id.clone());
+        let geography_parameters = config.get_geography_parameters();
+        let mut grid = match &geography_parameters.geojson {
+            Some(geojson) => geography::define_geography_from_geojson(&geography_parameters, geojson, sim_id.clone()),
+            None => geography::define_geography(&geography_parameters, sim_id.clone()),
+        };
+        let schedules = config.get_schedules();
         let mut rng = RandomWrapper::new();
-        let (start_locations, agent_list) = match config.get_population() {
-            Population::Csv(csv_pop) => grid.read_population(&csv_pop, &start_infections, &mut rng, &sim_id),
-            Population::Auto(auto_pop) => grid.generate_population(&auto_pop, &start_infections, &mut rng, travel_plan_config, sim_id.clone()),
+        let population = config.get_population();
+        let (start_locations, mut agent_list) = match &population {
+            Population::Csv(csv_pop) => grid.read_population(csv_pop, &start_infections, &mut rng, &sim_id),
+            Population::Auto(auto_pop) => grid.generate_population(auto_pop, &start_infections, &mut rng, travel_plan_config, sim_id.clone()),
+            Population::Synthetic(synthetic_pop) => grid.generate_synthetic_population(synthetic_pop, &start_infections, &mut rng, &sim_id),
         };
-        grid.resize_hospital(agent_list.len() as i32, HOSPITAL_STAFF_PERCENTAGE, config.get_geography_parameters().hospital_beds_percentage, sim_id.clone());
+        let comorbidities_given = matches!(&population, Population::Csv(csv_pop) if csv_pop.cols.mapping().contains_key("comorbidities"));
+        if !comorbidities_given {
+            agent::assign_risk_factors(&mut agent_list, disease.get_risk_factors(), &mut rng);
+        }
+        if geography_parameters.hospitals.facilities.is_empty() {
+            grid.resize_hospital(agent_list.len() as i32, HOSPITAL_STAFF_PERCENTAGE, geography_parameters.hospital_beds_percentage, sim_id.clone());
+        }
+        grid.set_hospitals(&geography_parameters.hospitals, sim_id.clone());
 
         let agent_location_map = AgentLocationMap::new(config.get_grid_size(), &agent_list, &start_locations);
         let write_agent_location_map = agent_location_map.clone();
 
         info!("Initialization completed in {} seconds", start.elapsed().as_secs_f32());
-        Epidemiology { agent_location_map, write_agent_location_map, grid, disease, sim_id }
+        let calendar = config.get_calendar();
+        let self_isolation = config.get_self_isolation();
+        Epidemiology { agent_location_map, write_agent_location_map, grid, disease, schedules, calendar, self_isolation, sim_id }
     }
 
     fn stop_simulation(lock_down_details: &mut LockdownIntervention, run_mode: &RunMode, row: Counts) -> bool {
@@ -118,6 +147,17 @@ impl Epidemiology {
                                                              Box::new(hotspot_tracker),
                                                              Box::new(intervention_reporter)];
 
+        let risk_factors = self.disease.get_risk_factors();
+        if !risk_factors.is_empty() {
+            let names = risk_factors.iter().map(|factor| factor.get_name().to_string()).collect();
+            listeners_vec.push(Box::new(RiskGroupsReporter::new(format!("{}_risk_groups.csv", output_file_format), names)));
+        }
+
+        if let Some(stratified_counts) = config.get_stratified_counts() {
+            listeners_vec.push(Box::new(StratifiedCountsReporter::new(format!("{}_stratified_counts.csv", output_file_format),
+                                                                      stratified_counts)));
+        }
+
         match run_mode {
             RunMode::Standalone => {}
             RunMode::SingleDaemon => {
@@ -160,6 +200,9 @@ impl Epidemiology {
             vaccinate: vaccinations,
             lockdown: lock_down_details,
             build_new_hospital: hospital_intervention,
+            travel_restrictions: TravelRestrictions::default(),
+            school_closure: SchoolClosureIntervention::init(config),
+            masks: MaskIntervention::init(config),
         }
     }
 
@@ -185,6 +228,25 @@ impl Epidemiology {
             listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.lockdown)
         }
 
+        if interventions.school_closure.should_apply(counts_at_hr) {
+            interventions.school_closure.apply();
+            Epidemiology::set_schools_closed(counts_at_hr.get_hour(), write_buffer, true);
+            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.school_closure)
+        }
+        if interventions.school_closure.should_reopen(counts_at_hr) {
+            interventions.school_closure.unapply();
+            Epidemiology::set_schools_closed(counts_at_hr.get_hour(), write_buffer, false);
+            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.school_closure)
+        }
+
+        if interventions.masks.should_apply(counts_at_hr) {
+            interventions.masks.apply(counts_at_hr.get_hour());
+            Epidemiology::set_mask_use(counts_at_hr.get_hour(), write_buffer, &interventions.masks);
+            listeners.intervention_applied(counts_at_hr.get_hour(), &interventions.masks)
+        } else if interventions.masks.should_update(counts_at_hr) {
+            Epidemiology::set_mask_use(counts_at_hr.get_hour(), write_buffer, &interventions.masks);
+        }
+
         interventions.build_new_hospital.counts_updated(counts_at_hr);
         if interventions.build_new_hospital.should_apply(counts_at_hr) {
             info!("Increasing the hospital size");
@@ -196,10 +258,47 @@ impl Epidemiology {
         }
     }
 
+    fn apply_global_interventions(commands: &[InterventionCommand], interventions: &mut Interventions, hour: Hour,
+                                  listeners: &mut Listeners, rng: &mut RandomWrapper, write_buffer: &mut AgentLocationMap) {
+        for command in commands {
+            match command {
+                InterventionCommand::Lockdown { essential_workers_population } => {
+                    let essential_workers_population = *essential_workers_population;
+                    let was_locked_down = interventions.lockdown.is_locked_down();
+                    if interventions.lockdown.apply_global(essential_workers_population) {
+                        for (_, agent) in write_buffer.iter_mut() {
+                            agent.assign_essential_worker(essential_workers_population, rng);
+                        }
+                    }
+                    if !was_locked_down {
+                        Epidemiology::lock_city(hour, write_buffer);
+                        listeners.intervention_applied(hour, &interventions.lockdown);
+                    }
+                }
+                InterventionCommand::LiftLockdown {} => {
+                    if interventions.lockdown.is_locked_down() {
+                        Epidemiology::unlock_city(hour, write_buffer);
+                        interventions.lockdown.unapply();
+                        listeners.intervention_applied(hour, &interventions.lockdown);
+                    }
+                }
+                InterventionCommand::RestrictTravel(restrictions) => {
+                    info!("Restricting travel. Hour: {}", hour);
+                    interventions.travel_restrictions = restrictions.clone();
+                }
+                InterventionCommand::LiftTravelRestrictions {} => {
+                    info!("Lifting travel restrictions. Hour: {}", hour);
+                    interventions.travel_restrictions = TravelRestrictions::default();
+                }
+            }
+        }
+    }
+
     pub async fn run(&mut self, config: &Config, travel_plan_config: Option<TravelPlanConfig>, run_mode: &RunMode) {
         let mut listeners = self.create_listeners(config, run_mode);
         let population = self.agent_location_map.current_population();
         let mut counts_at_hr = Epidemiology::counts_at_start(population, &config.get_starting_infections());
+        counts_at_hr.set_date(self.calendar.date_at(0));
         let mut rng = RandomWrapper::new();
 
         self.write_agent_location_map.init_with_capacity(population as usize);
@@ -243,10 +342,12 @@ impl Epidemiology {
             if population_before_travel == 0 { panic!("No citizens!"); }
 
             Epidemiology::simulate(counts_at_hr, simulation_hour, read_buffer_reference, write_buffer_reference,
-                                   &self.grid, listeners, rng, &self.disease, percent_outgoing,
+                                   &self.grid, listeners, rng, &self.disease, &self.schedules, &self.calendar,
+                                   self.self_isolation.as_ref(), percent_outgoing,
                                    &mut outgoing, &mut outgoing_commuters, config.enable_citizen_state_messages(), None, &sim_id);
 
             listeners.counts_updated(*counts_at_hr);
+            listeners.population_updated(simulation_hour, write_buffer_reference);
             Epidemiology::process_interventions(interventions, counts_at_hr, listeners,
                                                 rng, write_buffer_reference, config, &mut self.grid, sim_id.to_string());
 
@@ -292,7 +393,8 @@ impl Epidemiology {
         let migrators_consumer = travel_consumer::start(engine_id, &[&*format!("{}{}", MIGRATION_TOPIC, engine_id)]);
         let mut migration_stream = migrators_consumer.start_with(Duration::from_millis(1), false);
 
-        let commute_plan = if is_commute_enabled { travel_plan_config.commute_plan() } else { CommutePlan { regions: Vec::new(), matrix: Vec::new() } };
+        let commute_hours = self.schedules.commute_hours();
+        let commute_plan = if is_commute_enabled { travel_plan_config.commute_plan() } else { CommutePlan { regions: Vec::new(), matrix: Vec::new(), schedule: Vec::new() } };
         let commute_consumer = travel_consumer::start(engine_id, &[&*format!("{}{}", COMMUTE_TOPIC, engine_id)]);
         let mut commute_stream = commute_consumer.start_with(Duration::from_millis(1), false);
 
@@ -309,8 +411,9 @@ impl Epidemiology {
 
         for simulation_hour in 1..config.get_hours() {
             let start_time = Instant::now();
-            let tick = Epidemiology::receive_tick(run_mode, &mut ticks_stream, simulation_hour, is_commute_enabled, is_migration_enabled).await;
-            if let Some(t) = tick {
+            let tick = Epidemiology::receive_tick(run_mode, &mut ticks_stream, simulation_hour, commute_hours, is_commute_enabled,
+                                                  is_migration_enabled).await;
+            if let Some(t) = &tick {
                 // total_tick_sync_time += start_time.elapsed().as_millis();
                 // info!("total tick sync time as hour {} - is {}", simulation_hour, total_tick_sync_time);
                 if t.terminate() {
@@ -340,29 +443,36 @@ impl Epidemiology {
 
             let grid = &self.grid;
             let disease = &self.disease;
+            let schedules = &self.schedules;
+            let calendar = &self.calendar;
+            let self_isolation = self.self_isolation.as_ref();
 
             let mut percent_outgoing = 0.0;
             let mut outgoing: Vec<(Point, Migrator)> = Vec::new();
 
             if simulation_hour % 24 == 0 && is_migration_enabled {
+                engine_migration_plan.set_migration_plan(MigrationPlan::new(travel_plan_config.get_regions(),
+                                                                            travel_plan_config.get_migration_matrix_at(simulation_hour, calendar).unwrap()));
                 percent_outgoing = engine_migration_plan.percent_outgoing();
             }
             let mut actual_outgoing: Vec<(Point, Migrator)> = Vec::new();
 
+            let travel_restrictions = interventions.travel_restrictions.clone();
             let received_migrators =
                 if is_migration_enabled {
-                    Some(Epidemiology::receive_migrators(tick, &mut migration_stream, &engine_migration_plan))
+                    Some(Epidemiology::receive_migrators(tick.as_ref(), &mut migration_stream, &engine_migration_plan,
+                                                        &travel_restrictions))
                 } else { None };
 
             let mut outgoing_commuters: Vec<(Point, Commuter)> = Vec::new();
             let sim = async {
                 Epidemiology::simulate(counts_at_hr, simulation_hour, read_buffer_reference, write_buffer_reference,
-                                       grid, listeners, rng, disease, percent_outgoing,
+                                       grid, listeners, rng, disease, schedules, calendar, self_isolation, percent_outgoing,
                                        &mut outgoing, &mut outgoing_commuters, config.enable_citizen_state_messages(), Some(&travel_plan_config), engine_id);
 
 
                 let (outgoing_migrators_by_region, actual_total_outgoing) = if is_migration_enabled {
-                    engine_migration_plan.alloc_outgoing_to_regions(&outgoing)
+                    engine_migration_plan.alloc_outgoing_to_regions(&outgoing, &travel_restrictions)
                 } else { (Vec::new(), Vec::new()) };
 
                 actual_outgoing = actual_total_outgoing;
@@ -371,15 +481,17 @@ impl Epidemiology {
                     listeners.outgoing_migrators_added(simulation_hour, &outgoing_migrators_by_region);
                 }
 
-                let outgoing_commuters_by_region = if is_commute_enabled {
-                    commute_plan.get_commuters_by_region(&outgoing_commuters, simulation_hour)
-                } else { Vec::new() };
+                let (outgoing_commuters_by_region, actual_outgoing_commuters) = if is_commute_enabled {
+                    commute_plan.get_commuters_by_region(&outgoing_commuters, simulation_hour, commute_hours, calendar, &travel_restrictions)
+                } else { (Vec::new(), Vec::new()) };
+
+                outgoing_commuters = actual_outgoing_commuters;
 
                 if is_migration_enabled {
-                    Epidemiology::send_migrators(tick, &mut producer, outgoing_migrators_by_region);
+                    Epidemiology::send_migrators(tick.as_ref(), &mut producer, outgoing_migrators_by_region);
                 }
                 if is_commute_enabled {
-                    Epidemiology::send_commuters(tick, &mut producer, outgoing_commuters_by_region).await;
+                    Epidemiology::send_commuters(tick.as_ref(), &mut producer, outgoing_commuters_by_region, commute_hours).await;
                 }
             };
 
@@ -387,14 +499,16 @@ impl Epidemiology {
 
             if is_commute_enabled {
                 let commute_start_time = Instant::now();
-                let received_commuters = Epidemiology::receive_commuters(tick, &mut commute_stream, &commute_plan, engine_id);
+                let received_commuters = Epidemiology::receive_commuters(tick.as_ref(), &mut commute_stream, &commute_plan, commute_hours, engine_id);
                 let (mut incoming_commuters, ) = join!(received_commuters);
                 total_commute_sync_time += commute_start_time.elapsed().as_millis();
                 // info!("total commute sync time as hour {} - is {}", simulation_hour, total_commute_sync_time);
                 n_incoming += incoming_commuters.len();
                 n_outgoing += outgoing_commuters.len();
                 write_buffer_reference.remove_commuters(&outgoing_commuters, counts_at_hr);
-                write_buffer_reference.assimilate_commuters(&mut incoming_commuters, &mut self.grid, counts_at_hr, rng, simulation_hour);
+                let arriving_for_work = simulation_hour % 24 == commute_hours.to_work;
+                write_buffer_reference.assimilate_commuters(&mut incoming_commuters, &mut self.grid, counts_at_hr, rng, arriving_for_work,
+                                                            interventions.masks.mask_use(simulation_hour));
                 debug!("assimilated the commuters");
             }
 
@@ -403,18 +517,24 @@ impl Epidemiology {
                 n_incoming += incoming.len();
                 n_outgoing += outgoing.len();
                 write_buffer_reference.remove_migrators(&actual_outgoing, counts_at_hr, &mut self.grid);
-                write_buffer_reference.assimilate_migrators(&mut incoming, &mut self.grid, counts_at_hr, rng);
+                write_buffer_reference.assimilate_migrators(&mut incoming, &mut self.grid, counts_at_hr, rng,
+                                                            interventions.masks.mask_use(simulation_hour));
             }
 
             listeners.counts_updated(*counts_at_hr);
+            listeners.population_updated(simulation_hour, write_buffer_reference);
             Epidemiology::process_interventions(interventions, counts_at_hr, listeners,
                                                 rng, write_buffer_reference, config, &mut self.grid, engine_id.to_string());
+            if let Some(t) = &tick {
+                Epidemiology::apply_global_interventions(t.interventions(), interventions, counts_at_hr.get_hour(),
+                                                         listeners, rng, write_buffer_reference);
+            }
 
             if Epidemiology::stop_simulation(&mut interventions.lockdown, run_mode, *counts_at_hr) {
                 break;
             }
 
-            Epidemiology::send_ack(run_mode, &mut producer, *counts_at_hr, simulation_hour, &interventions.lockdown, is_commute_enabled, is_migration_enabled).await;
+            Epidemiology::send_ack(run_mode, &mut producer, *counts_at_hr, simulation_hour, &interventions.lockdown, tick.is_some()).await;
 
             if simulation_hour % 100 == 0 {
                 info!("Throughput: {} iterations/sec; simulation hour {} of {}",
@@ -457,9 +577,10 @@ impl Epidemiology {
 
 
     async fn receive_tick(run_mode: &RunMode, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
-                          simulation_hour: Hour, is_commute_enabled: bool, is_migration_enabled: bool) -> Option<Tick> {
+                          simulation_hour: Hour, commute_hours: CommuteHours, is_commute_enabled: bool,
+                          is_migration_enabled: bool) -> Option<Tick> {
         let day_hour = simulation_hour % 24;
-        let is_commute_hour = day_hour == constants::ROUTINE_TRAVEL_END_TIME || day_hour == constants::ROUTINE_TRAVEL_START_TIME;
+        let is_commute_hour = commute_hours.includes(day_hour);
         let is_migration_hour = day_hour == 0;
         let receive_tick_for_commute: bool = is_commute_enabled && is_commute_hour;
         let receive_tick_for_migration: bool = is_migration_enabled && is_migration_hour;
@@ -475,15 +596,10 @@ impl Epidemiology {
         None
     }
 
+    /// Every tick received is acknowledged, as is the first hour
     async fn send_ack(run_mode: &RunMode, producer: &mut KafkaProducer, counts: Counts, simulation_hour: Hour,
-                      lockdown: &LockdownIntervention, is_commute_enabled: bool, is_migration_enabled: bool) {
-        let day_hour = simulation_hour % 24;
-        let is_commute_hour = day_hour == constants::ROUTINE_TRAVEL_END_TIME || day_hour == constants::ROUTINE_TRAVEL_START_TIME;
-        let is_migration_hour = day_hour == 0;
-        let received_tick_for_commute: bool = is_commute_enabled && is_commute_hour;
-        let received_tick_for_migration: bool = is_migration_enabled && is_migration_hour;
-
-        if simulation_hour == 1 || received_tick_for_commute || received_tick_for_migration {
+                      lockdown: &LockdownIntervention, received_tick: bool) {
+        if simulation_hour == 1 || received_tick {
             if let RunMode::MultiEngine { engine_id } = run_mode {
                 let ack = TickAck {
                     engine_id: engine_id.to_string(),
@@ -499,25 +615,26 @@ impl Epidemiology {
         }
     }
 
-    fn send_migrators(tick: Option<Tick>, producer: &mut KafkaProducer, outgoing: Vec<MigratorsByRegion>) {
+    fn send_migrators(tick: Option<&Tick>, producer: &mut KafkaProducer, outgoing: Vec<MigratorsByRegion>) {
         if tick.is_some() && tick.unwrap().hour() % 24 == 0 {
             producer.send_migrators(outgoing);
         }
     }
 
-    async fn send_commuters(tick: Option<Tick>, producer: &mut KafkaProducer, outgoing: Vec<CommutersByRegion>) {
+    async fn send_commuters(tick: Option<&Tick>, producer: &mut KafkaProducer, outgoing: Vec<CommutersByRegion>,
+                            commute_hours: CommuteHours) {
         if tick.is_some() {
             let hour = tick.unwrap().hour() % 24;
-            if hour == constants::ROUTINE_TRAVEL_START_TIME || hour == constants::ROUTINE_TRAVEL_END_TIME {
+            if commute_hours.includes(hour) {
                 producer.send_commuters(outgoing).await;
             }
         }
     }
 
-    async fn receive_migrators(tick: Option<Tick>, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
-                               engine_migration_plan: &EngineMigrationPlan) -> Vec<Migrator> {
+    async fn receive_migrators(tick: Option<&Tick>, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
+                               engine_migration_plan: &EngineMigrationPlan, restrictions: &TravelRestrictions) -> Vec<Migrator> {
         if tick.is_some() && tick.unwrap().hour() % 24 == 0 {
-            let expected_incoming_regions = engine_migration_plan.incoming_regions_count();
+            let expected_incoming_regions = engine_migration_plan.incoming_regions_count(restrictions);
             let mut received_incoming_regions = 0;
             debug!("Receiving migrators from {} regions", expected_incoming_regions);
             let mut incoming: Vec<Migrator> = Vec::new();
@@ -538,13 +655,13 @@ impl Epidemiology {
     }
 
 
-    async fn receive_commuters(tick: Option<Tick>, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
-                               commute_plan: &CommutePlan, engine_id: &String) -> Vec<Commuter> {
+    async fn receive_commuters(tick: Option<&Tick>, message_stream: &mut MessageStream<'_, DefaultConsumerContext>,
+                               commute_plan: &CommutePlan, commute_hours: CommuteHours, engine_id: &String) -> Vec<Commuter> {
         if tick.is_some() {
             let mut incoming: Vec<Commuter> = Vec::new();
             let hour = tick.unwrap().hour() % 24;
-            if hour == constants::ROUTINE_TRAVEL_START_TIME || hour == constants::ROUTINE_TRAVEL_END_TIME {
-                let expected_incoming_regions = commute_plan.incoming_regions_count(engine_id);
+            if commute_hours.includes(hour) {
+                let expected_incoming_regions = commute_plan.incoming_regions_count();
                 let mut received_incoming_regions = 0;
                 debug!("Receiving commuters from {} regions", expected_incoming_regions);
                 while expected_incoming_regions != received_incoming_regions {
@@ -565,7 +682,7 @@ impl Epidemiology {
                                            engine_id: &String) -> Option<CommutersByRegion> {
         let msg = message_stream.next().await;
         let mut maybe_commuters = travel_consumer::read_commuters(msg);
-        while maybe_commuters.is_none() || (maybe_commuters.as_ref().unwrap().commuters.is_empty() && maybe_commuters.as_ref().unwrap().to_engine_id() == engine_id) {
+        while maybe_commuters.is_none() {
             let next_msg = message_stream.next().await;
             maybe_commuters = travel_consumer::read_commuters(next_msg);
         }
@@ -600,17 +717,24 @@ impl Epidemiology {
 
     fn simulate(csv_record: &mut Counts, simulation_hour: Hour, read_buffer: &AgentLocationMap,
                 write_buffer: &mut AgentLocationMap, grid: &Grid, listeners: &mut Listeners,
-                rng: &mut RandomWrapper, disease: &Disease, percent_outgoing: f64,
+                rng: &mut RandomWrapper, disease: &Disease, schedules: &Schedules, calendar: &Calendar,
+                self_isolation: Option<&SelfIsolation>, percent_outgoing: f64,
                 outgoing: &mut Vec<(Point, Migrator)>, outgoing_commuters: &mut Vec<(Point, Commuter)>,
                 publish_citizen_state: bool, travel_plan_config: Option<&TravelPlanConfig>,
                 region_name: &String) {
         write_buffer.clear();
         csv_record.clear();
+        csv_record.set_date(calendar.date_at(simulation_hour));
+        let day_off = calendar.is_day_off(simulation_hour);
+        let commute_hours = schedules.commute_hours();
+        let mut hospitals = HospitalOccupancy::new(&grid.hospitals, read_buffer.iter().filter_map(|(_, agent)| agent.get_hospital_bed()));
         for (cell, agent) in read_buffer.iter() {
             let mut current_agent = agent.clone();
             let infection_status = current_agent.state_machine.is_infected();
-            let point = current_agent.perform_operation(*cell, simulation_hour, grid, read_buffer, rng, disease);
+            let point = current_agent.perform_operation(*cell, simulation_hour, grid, read_buffer, &mut hospitals, schedules,
+                                                        day_off, self_isolation, rng, disease);
             Epidemiology::update_counts(csv_record, &current_agent);
+            Epidemiology::update_new_counts(csv_record, agent, &current_agent);
 
             if !infection_status && current_agent.state_machine.is_infected() {
                 listeners.citizen_got_infected(cell);
@@ -637,14 +761,7 @@ impl Epidemiology {
                     outgoing.push((*new_location, migrator));
                 }
 
-                if is_commute_enabled && simulation_hour % 24 == constants::ROUTINE_TRAVEL_START_TIME &&
-                    current_agent.can_move() && current_agent.work_location.location_id != *region_name {
-                    let commuter = Commuter::from(&current_agent);
-                    outgoing_commuters.push((*new_location, commuter));
-                }
-
-                if is_commute_enabled && simulation_hour % 24 == constants::ROUTINE_TRAVEL_END_TIME &&
-                    current_agent.can_move() && current_agent.home_location.location_id != *region_name {
+                if is_commute_enabled && Epidemiology::is_commuting(&current_agent, simulation_hour, day_off, commute_hours, region_name) {
                     let commuter = Commuter::from(&current_agent);
                     outgoing_commuters.push((*new_location, commuter));
                 }
@@ -655,7 +772,34 @@ impl Epidemiology {
                 listeners.citizen_state_updated(simulation_hour, &current_agent, new_location);
             }
         }
+        csv_record.update_turned_away(hospitals.get_turned_away());
         assert_eq!(csv_record.total(), write_buffer.current_population());
+
+        if let Some(self_isolation) = self_isolation.filter(|s| s.household_quarantine) {
+            Epidemiology::quarantine_households(simulation_hour, write_buffer, self_isolation);
+        }
+    }
+
+    /// Nobody leaves for work in another region on a day off, but those away can always come back
+    fn is_commuting(citizen: &Citizen, simulation_hour: Hour, day_off: bool, commute_hours: CommuteHours, region_name: &str) -> bool {
+        let hour_of_day = simulation_hour % 24;
+        let to_work = !day_off && hour_of_day == commute_hours.to_work && citizen.work_location.location_id != region_name;
+        let back_home = hour_of_day == commute_hours.back_home && citizen.home_location.location_id != region_name;
+        citizen.can_move() && (to_work || back_home)
+    }
+
+    /// Keeps home the others living with citizens who started isolating this hour
+    fn quarantine_households(hr: Hour, write_buffer_reference: &mut AgentLocationMap, self_isolation: &SelfIsolation) {
+        let households: HashSet<Area> = write_buffer_reference.iter()
+            .filter(|(_, agent)| agent.started_self_isolating_at(hr))
+            .map(|(_, agent)| agent.home_location.clone())
+            .collect();
+        if households.is_empty() {
+            return;
+        }
+        write_buffer_reference.iter_mut()
+            .filter(|(_, agent)| households.contains(&agent.home_location))
+            .for_each(|(_, agent)| agent.quarantine_until(hr + self_isolation.duration));
     }
 
     fn update_counts(counts_at_hr: &mut Counts, citizen: &Citizen) {
@@ -663,14 +807,44 @@ impl Epidemiology {
             State::Susceptible { .. } => { counts_at_hr.update_susceptible(1) }
             State::Exposed { .. } => { counts_at_hr.update_exposed(1) }
             State::Infected { .. } => {
-                if citizen.is_hospitalized() {
+                if let Some(bed) = citizen.get_hospital_bed() {
                     counts_at_hr.update_hospitalized(1);
+                    if bed.icu {
+                        counts_at_hr.update_in_icu(1);
+                    }
                 } else {
                     counts_at_hr.update_infected(1)
                 }
             }
             State::Recovered { .. } => { counts_at_hr.update_recovered(1) }
-            State::Deceased { .. } => { counts_at_hr.update_deceased(1) }
+            State::Deceased { .. } => {
+                counts_at_hr.update_deceased(1);
+                if citizen.state_machine.is_excess_death() {
+                    counts_at_hr.update_excess_deaths(1);
+                }
+            }
+        }
+    }
+
+    /// Counts what happened to the citizen in this hour
+    fn update_new_counts(counts_at_hr: &mut Counts, before: &Citizen, after: &Citizen) {
+        if before.state_machine.is_susceptible() && after.state_machine.is_exposed() {
+            counts_at_hr.update_new_exposures(1);
+        }
+        if !before.state_machine.is_infected() && after.state_machine.is_infected() {
+            counts_at_hr.update_new_infections(1);
+        }
+        if !before.state_machine.is_symptomatic() && after.state_machine.is_symptomatic() {
+            counts_at_hr.update_new_symptomatic(1);
+        }
+        if before.state_machine.is_infected() && after.state_machine.is_recovered() {
+            counts_at_hr.update_new_recoveries(1);
+        }
+        if !before.state_machine.is_deceased() && after.state_machine.is_deceased() {
+            counts_at_hr.update_new_deaths(1);
+        }
+        if !before.is_hospitalized() && after.is_hospitalized() {
+            counts_at_hr.update_new_hospital_admissions(1);
         }
     }
 
@@ -687,11 +861,26 @@ impl Epidemiology {
             .filter(|(_, agent)| agent.is_isolated())
             .for_each(|(_, agent)| agent.set_isolation(false));
     }
+
+    fn set_mask_use(hr: Hour, write_buffer_reference: &mut AgentLocationMap, masks: &MaskIntervention) {
+        let mask_use = masks.mask_use(hr);
+        info!("Setting mask use {:?}. Hour: {}", mask_use, hr);
+        write_buffer_reference.iter_mut()
+            .for_each(|(_, agent)| agent.set_mask_use(mask_use));
+    }
+
+    fn set_schools_closed(hr: Hour, write_buffer_reference: &mut AgentLocationMap, closed: bool) {
+        info!("{} schools. Hour: {}", if closed { "Closing" } else { "Reopening" }, hr);
+        write_buffer_reference.iter_mut()
+            .filter(|(_, agent)| agent.is_student())
+            .for_each(|(_, agent)| agent.set_school_closed(closed));
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::config::{AutoPopulation, GeographyParameters};
+    use crate::disease_state_machine::InfectionSeverity;
     use crate::geography::Area;
     use crate::geography::Point;
     use crate::interventions::InterventionConfig;
@@ -706,6 +895,7 @@ mod tests {
             number_of_agents: 10,
             public_transport_percentage: 1.0,
             working_percentage: 1.0,
+            age_distribution: Vec::new(),
         };
         let disease = Disease::new(0, 0, 0, 0, 0, 0.0, 0.0, 0.0, 0.0, 0.0, 0, 0);
         let vac = VaccinateConfig {
@@ -729,4 +919,72 @@ mod tests {
 
         assert_eq!(epidemiology.agent_location_map.current_population(), 10);
     }
+
+    #[test]
+    fn should_not_commute_to_work_on_days_off() {
+        let mut rng = RandomWrapper::new();
+        let home = Area::new("engine1".to_string(), Point::new(0, 0), Point::new(10, 10));
+        let work = Area::new("engine2".to_string(), Point::new(11, 0), Point::new(20, 20));
+        let commuter = Citizen::new(home.clone(), work.clone(), Point::new(2, 2), false, agent::WorkStatus::Normal {}, &mut rng);
+        let visitor = Citizen::new(work, home, Point::new(2, 2), false, agent::WorkStatus::Normal {}, &mut rng);
+        let commute_hours = CommuteHours { to_work: 6, back_home: 15 };
+
+        assert!(Epidemiology::is_commuting(&commuter, 24 + 6, false, commute_hours, "engine1"));
+        assert!(!Epidemiology::is_commuting(&commuter, 24 + 6, true, commute_hours, "engine1"));
+        assert!(!Epidemiology::is_commuting(&commuter, 24 + 7, false, commute_hours, "engine1"));
+        assert!(Epidemiology::is_commuting(&visitor, 24 + 15, true, commute_hours, "engine1"));
+        assert!(!Epidemiology::is_commuting(&commuter, 24 + 15, false, commute_hours, "engine1"));
+    }
+
+    #[test]
+    fn should_count_new_deaths_and_hospital_admissions() {
+        let engine_id = "engine1".to_string();
+        let home_location = Area::new(engine_id.clone(), Point::new(0, 0), Point::new(10, 10));
+        let work_location = Area::new(engine_id, Point::new(11, 0), Point::new(20, 20));
+        let mut rng = RandomWrapper::new();
+        let before = Citizen::new(home_location, work_location, Point::new(2, 2), false, agent::WorkStatus::Normal {}, &mut rng);
+        let mut admitted = before.clone();
+        admitted.set_hospital_bed(Some(crate::geography::Bed { hospital: 0, icu: false }));
+        let mut deceased = before.clone();
+        deceased.state_machine.state = State::Deceased {};
+        let mut counts = Counts::new(100, 0, 0);
+
+        Epidemiology::update_new_counts(&mut counts, &before, &admitted);
+        Epidemiology::update_new_counts(&mut counts, &before, &deceased);
+        Epidemiology::update_new_counts(&mut counts, &deceased, &deceased);
+
+        assert_eq!(counts.get_new_hospital_admissions(), 1);
+        assert_eq!(counts.get_new_deaths(), 1);
+    }
+
+    #[test]
+    fn should_count_new_exposures_infections_symptomatic_and_recoveries() {
+        let engine_id = "engine1".to_string();
+        let home_location = Area::new(engine_id.clone(), Point::new(0, 0), Point::new(10, 10));
+        let work_location = Area::new(engine_id, Point::new(11, 0), Point::new(20, 20));
+        let mut rng = RandomWrapper::new();
+        let susceptible = Citizen::new(home_location, work_location, Point::new(2, 2), false, agent::WorkStatus::Normal {}, &mut rng);
+        let with_state = |state: State| {
+            let mut citizen = susceptible.clone();
+            citizen.state_machine.state = state;
+            citizen
+        };
+        let exposed = with_state(State::Exposed { at_hour: 1 });
+        let pre_symptomatic = with_state(State::Infected { symptoms: true, severity: InfectionSeverity::Pre { at_hour: 2 } });
+        let symptomatic = with_state(State::Infected { symptoms: true, severity: InfectionSeverity::Mild });
+        let recovered = with_state(State::Recovered {});
+        let mut counts = Counts::new(100, 0, 0);
+
+        Epidemiology::update_new_counts(&mut counts, &susceptible, &exposed);
+        Epidemiology::update_new_counts(&mut counts, &exposed, &pre_symptomatic);
+        Epidemiology::update_new_counts(&mut counts, &pre_symptomatic, &symptomatic);
+        Epidemiology::update_new_counts(&mut counts, &symptomatic, &recovered);
+        Epidemiology::update_new_counts(&mut counts, &symptomatic, &symptomatic);
+
+        assert_eq!(counts.get_new_exposures(), 1);
+        assert_eq!(counts.get_new_infections(), 1);
+        assert_eq!(counts.get_new_symptomatic(), 1);
+        assert_eq!(counts.get_new_recoveries(), 1);
+        assert_eq!(counts.get_daily_new_recoveries(), 1);
+    }
 }
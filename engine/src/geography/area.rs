@@ -22,7 +22,8 @@ use rand::Rng;
 use crate::geography::Point;
 use crate::random_wrapper::RandomWrapper;
 use std::collections::HashSet;
-use crate::custom_types::{Count};
+use crate::custom_types::{CoOrdinate, Count};
+use crate::config::BuildingSize;
 
 #[derive(Clone, Hash, Eq, Debug, Serialize, Deserialize)]
 pub struct Area {
@@ -42,6 +43,13 @@ impl Area {
         })
     }
 
+    /// The points of the area within the radius of the point, itself excluded
+    pub fn get_neighbors_within(&self, point: Point, radius: CoOrdinate) -> impl Iterator<Item=Point> + '_ {
+        (-radius..=radius)
+            .flat_map(move |dy| (-radius..=radius).map(move |dx| Point::new(point.x + dx, point.y + dy)))
+            .filter(move |p| *p != point && self.contains(p))
+    }
+
     pub fn iter(&self) -> AreaIterator {
         AreaIterator::new(self.clone())
     }
@@ -75,6 +83,11 @@ impl Area {
             && self.start_offset.y <= point.y && self.end_offset.y >= point.y
     }
 
+    /// Number of people that fit in the area, one per cell
+    pub fn capacity(&self) -> Count {
+        ((self.end_offset.x - self.start_offset.x + 1) * (self.end_offset.y - self.start_offset.y + 1)) as Count
+    }
+
     pub fn get_number_of_cells(&self) -> Count {
         ((self.end_offset.x - self.start_offset.x) * (self.end_offset.y - self.start_offset.y)) as Count
     }
@@ -87,24 +100,40 @@ impl PartialEq for Area {
     }
 }
 
-pub fn area_factory(start_point: Point, end_point: Point, size: u32, engine_id: String) -> Vec<Area> {
-    let feasible_houses_in_x_dim = (end_point.x - start_point.x + 1) / size as i32;
-    let feasible_houses_in_y_dim = (end_point.y - start_point.y + 1) / size as i32;
-
-    let mut areas = Vec::with_capacity((feasible_houses_in_y_dim * feasible_houses_in_x_dim) as usize);
+/// Fills the space between the two points with rows of areas, picking the size of each next area
+/// so that the sizes built follow their shares
+pub fn area_factory(start_point: Point, end_point: Point, sizes: &[BuildingSize], engine_id: String) -> Vec<Area> {
+    let total_share: f64 = sizes.iter().map(|size| size.share).sum();
+    let mut built = vec![0; sizes.len()];
+    let mut areas = Vec::new();
     let mut current_start_point = start_point;
-
-    for _i in 0..feasible_houses_in_y_dim {
-        for _j in 0..feasible_houses_in_x_dim {
-            let current_end_point: Point = Point::new(current_start_point.x + size as i32 - 1, current_start_point.y + size as i32 - 1);
-
-            areas.push(Area::new(engine_id.to_string(), current_start_point, current_end_point));
-
-            current_start_point.x += size as i32;
+    let mut row_height = 0;
+
+    loop {
+        let next = (0..sizes.len())
+            .max_by(|a, b| {
+                let deficit = |i: usize| sizes[i].share / total_share * (areas.len() + 1) as f64 - built[i] as f64;
+                deficit(*a).partial_cmp(&deficit(*b)).unwrap().then(b.cmp(a))
+            })
+            .expect("At least one building size is needed");
+        let (width, height) = sizes[next].dimensions();
+        let (width, height) = (width as CoOrdinate, height as CoOrdinate);
+
+        if current_start_point.x + width - 1 > end_point.x {
+            current_start_point.x = start_point.x;
+            current_start_point.y += row_height;
+            row_height = 0;
+        }
+        if current_start_point.x + width - 1 > end_point.x || current_start_point.y + height - 1 > end_point.y {
+            break;
         }
 
-        current_start_point.x = start_point.x;
-        current_start_point.y += size as i32;
+        let current_end_point = Point::new(current_start_point.x + width - 1, current_start_point.y + height - 1);
+        areas.push(Area::new(engine_id.to_string(), current_start_point, current_end_point));
+        built[next] += 1;
+
+        current_start_point.x += width;
+        row_height = row_height.max(height);
     }
 
     areas
@@ -172,6 +201,17 @@ mod tests {
                            Point::new(1, 2), Point::new(2, 2)])
     }
 
+    #[test]
+    fn should_get_neighbors_within_radius() {
+        let area = get_area();
+        let neighbors: Vec<Point> = area.get_neighbors_within(Point::new(0, 0), 2).collect();
+        assert_eq!(neighbors.len(), 8);
+        assert!(!neighbors.contains(&Point::new(0, 0)));
+        assert!(neighbors.contains(&Point::new(2, 2)));
+
+        assert_eq!(area.get_neighbors_within(Point::new(3, 3), 1).count(), 8);
+    }
+
     #[test]
     fn iterator_should_work_multiple_times() {
         let area = Area::new("engine1".to_string(),Point { x: 0, y: 0 }, Point { x: 2, y: 2 });
@@ -184,7 +224,7 @@ mod tests {
 
     #[test]
     fn should_create_areas() {
-        let buildings = area_factory(Point::new(10, 0), Point::new(21, 10), 3, "engine1".to_string());
+        let buildings = area_factory(Point::new(10, 0), Point::new(21, 10), &[BuildingSize::new(9, 1.0)], "engine1".to_string());
 
         buildings.iter().for_each(|b| println!("start {:?}, end {:?}", b.start_offset, b.end_offset));
 
@@ -211,6 +251,27 @@ mod tests {
         assert_eq!(buildings.last().unwrap().end_offset, Point::new(21, 8));
     }
 
+    #[test]
+    fn should_create_areas_of_mixed_sizes() {
+        let sizes = [BuildingSize::new(4, 0.5), BuildingSize::new(9, 0.5)];
+        let buildings = area_factory(Point::new(0, 0), Point::new(9, 9), &sizes, "engine1".to_string());
+
+        assert_eq!(buildings.len(), 12);
+        assert_eq!(buildings.iter().filter(|b| b.capacity() == 4).count(), 6);
+        assert_eq!(buildings.iter().filter(|b| b.capacity() == 9).count(), 6);
+        assert_eq!(buildings[0], Area::new("engine1".to_string(), Point::new(0, 0), Point::new(1, 1)));
+        assert_eq!(buildings[1], Area::new("engine1".to_string(), Point::new(2, 0), Point::new(4, 2)));
+        assert_eq!(buildings[4], Area::new("engine1".to_string(), Point::new(0, 3), Point::new(1, 4)));
+        assert_eq!(buildings.last().unwrap().end_offset, Point::new(9, 8));
+    }
+
+    #[test]
+    fn should_get_dimensions_for_building_capacity() {
+        assert_eq!(BuildingSize::new(4, 1.0).dimensions(), (2, 2));
+        assert_eq!(BuildingSize::new(6, 1.0).dimensions(), (3, 2));
+        assert_eq!(BuildingSize::new(100, 1.0).dimensions(), (10, 10));
+    }
+
     #[test]
     fn should_get_neighbor_of(){
         let area = get_area();
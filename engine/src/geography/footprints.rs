@@ -0,0 +1,264 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use geojson::{Feature, Value};
+
+use crate::config::{AdmissionPolicy, BuildingSize, GeoJsonGeography, GeographyParameters};
+use crate::custom_types::{CoOrdinate, Size};
+use crate::geography::{Area, Grid, Hospital, Point};
+use crate::geojson_service::GeoJsonService;
+use crate::models::buildings::Buildings;
+
+pub const HOUSE: &str = "house";
+pub const OFFICE: &str = "office";
+pub const HOSPITAL: &str = "hospital";
+pub const SCHOOL: &str = "school";
+pub const TRANSPORT: &str = "transport";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct BoundingBox {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+}
+
+impl BoundingBox {
+    fn of(feature: &Feature) -> Option<BoundingBox> {
+        let geometry = feature.geometry.as_ref()?;
+        let mut positions = Vec::new();
+        collect_positions(&geometry.value, &mut positions);
+        positions.iter()
+            .map(|position| BoundingBox { min_x: position[0], min_y: position[1], max_x: position[0], max_y: position[1] })
+            .fold(None, |bbox: Option<BoundingBox>, other| match bbox {
+                None => Some(other),
+                Some(bbox) => Some(bbox.merge(&other)),
+            })
+    }
+
+    fn merge(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+fn collect_positions<'a>(value: &'a Value, positions: &mut Vec<&'a Vec<f64>>) {
+    match value {
+        Value::Point(position) => positions.push(position),
+        Value::MultiPoint(line) | Value::LineString(line) => positions.extend(line.iter()),
+        Value::MultiLineString(lines) | Value::Polygon(lines) => lines.iter().for_each(|line| positions.extend(line.iter())),
+        Value::MultiPolygon(polygons) => polygons.iter().flatten().for_each(|line| positions.extend(line.iter())),
+        Value::GeometryCollection(geometries) => geometries.iter().for_each(|geometry| collect_positions(&geometry.value, positions)),
+    }
+}
+
+/// Maps longitude/latitude onto grid cells. Both axes share one scale so that the footprints keep
+/// their proportions, the longer side of the extent spans the whole grid and north is at y = 0
+struct Raster {
+    extent: BoundingBox,
+    span: f64,
+    grid_size: Size,
+}
+
+impl Raster {
+    fn new(extent: BoundingBox, grid_size: Size) -> Raster {
+        let span = (extent.max_x - extent.min_x).max(extent.max_y - extent.min_y);
+        let span = if span > 0.0 { span } else { 1.0 };
+        Raster { extent, span, grid_size }
+    }
+
+    fn to_cell(&self, distance: f64) -> CoOrdinate {
+        (distance / self.span * (self.grid_size - 1) as f64).floor() as CoOrdinate
+    }
+
+    fn to_point(&self, x: f64, y: f64) -> Point {
+        Point::new(self.to_cell(x - self.extent.min_x), self.to_cell(self.extent.max_y - y))
+    }
+
+    /// Area covering the footprint, grown to at least `min_size` (width, height) so that it can hold its
+    /// occupants, and kept within the grid
+    fn area(&self, bbox: &BoundingBox, min_size: (Size, Size), engine_id: &str) -> Area {
+        let start = self.to_point(bbox.min_x, bbox.max_y);
+        let end = self.to_point(bbox.max_x, bbox.min_y);
+        let (start_x, end_x) = self.fit(start.x, end.x, min_size.0);
+        let (start_y, end_y) = self.fit(start.y, end.y, min_size.1);
+        Area::new(engine_id.to_string(), Point::new(start_x, start_y), Point::new(end_x, end_y))
+    }
+
+    fn fit(&self, start: CoOrdinate, end: CoOrdinate, min_size: Size) -> (CoOrdinate, CoOrdinate) {
+        let min_size = min_size.min(self.grid_size) as CoOrdinate;
+        let last_cell = self.grid_size as CoOrdinate - 1;
+        if end - start + 1 >= min_size {
+            return (start, end);
+        }
+        if start + min_size - 1 > last_cell {
+            (last_cell - min_size + 1, last_cell)
+        } else {
+            (start, start + min_size - 1)
+        }
+    }
+}
+
+fn smallest(sizes: &[BuildingSize]) -> (Size, Size) {
+    sizes.iter().min_by_key(|size| size.capacity)
+        .expect("At least one building size is needed")
+        .dimensions()
+}
+
+fn enclosing_area(areas: &[Area], engine_id: &str) -> Area {
+    let start = Point::new(areas.iter().map(|a| a.start_offset.x).min().unwrap(),
+                           areas.iter().map(|a| a.start_offset.y).min().unwrap());
+    let end = Point::new(areas.iter().map(|a| a.end_offset.x).max().unwrap(),
+                         areas.iter().map(|a| a.end_offset.y).max().unwrap());
+    Area::new(engine_id.to_string(), start, end)
+}
+
+/// Lays the grid out from building footprints: every classified footprint is rasterized to its bounding
+/// box. Houses and offices become the individual homes and workplaces, while the housing, work,
+/// transport and hospital areas enclose all footprints of their kind. Houses and offices are at least as large
+/// as the smallest of the configured sizes, and every hospital footprint is a hospital with a bed per cell
+pub fn define_geography_from_geojson(geography_parameters: &GeographyParameters, geojson: &GeoJsonGeography, engine_id: String) -> Grid {
+    let grid_size = geography_parameters.grid_size;
+    let geo_json = GeoJsonService::new(geojson.file.clone()).geo_json;
+    let buildings = Buildings::buildings_factory(&geo_json, geojson.building_types.clone());
+
+    let footprints: HashMap<&str, Vec<BoundingBox>> = [HOUSE, OFFICE, HOSPITAL, SCHOOL, TRANSPORT].iter()
+        .map(|kind| {
+            let boxes: Vec<BoundingBox> = match buildings.get(*kind) {
+                Some(b) => {
+                    debug!("{} footprints of type {}", b.get_features().len(), b.get_building_type());
+                    b.get_features().iter().filter_map(BoundingBox::of).collect()
+                }
+                None => Vec::new(),
+            };
+            (*kind, boxes)
+        })
+        .collect();
+
+    let extent = footprints.values().flatten()
+        .fold(None, |extent: Option<BoundingBox>, bbox| match extent {
+            None => Some(*bbox),
+            Some(extent) => Some(extent.merge(bbox)),
+        })
+        .unwrap_or_else(|| panic!("No building footprints could be classified in {}", geojson.file));
+    let raster = Raster::new(extent, grid_size);
+
+    let areas_of = |kind: &str, min_size: (Size, Size)| -> Vec<Area> {
+        let mut seen = HashSet::new();
+        footprints[kind].iter()
+            .map(|bbox| raster.area(bbox, min_size, &engine_id))
+            .filter(|area| seen.insert((area.start_offset, area.end_offset)))
+            .collect()
+    };
+    let required = |kind: &str, areas: Vec<Area>| -> Vec<Area> {
+        if areas.is_empty() {
+            panic!("No building footprints of type {} in {}", kind, geojson.file);
+        }
+        areas
+    };
+
+    let houses = required(HOUSE, areas_of(HOUSE, smallest(&geography_parameters.household_sizes)));
+    let offices = required(OFFICE, areas_of(OFFICE, smallest(&geography_parameters.office_sizes)));
+    let hospitals = required(HOSPITAL, areas_of(HOSPITAL, (1, 1)));
+    let transport_hubs = required(TRANSPORT, areas_of(TRANSPORT, (1, 1)));
+    let schools = areas_of(SCHOOL, (1, 1));
+
+    Grid {
+        grid_size,
+        housing_area: enclosing_area(&houses, &engine_id),
+        work_area: enclosing_area(&offices, &engine_id),
+        transport_area: enclosing_area(&transport_hubs, &engine_id),
+        hospital_area: enclosing_area(&hospitals, &engine_id),
+        houses,
+        offices,
+        schools,
+        hospitals: hospitals.into_iter().map(|area| {
+            let beds = area.capacity();
+            Hospital::new(area, beds, 0)
+        }).collect(),
+        admission: AdmissionPolicy::Nearest,
+        icu_percentage: 0.0,
+        school_age: geography_parameters.school_age(),
+        houses_occupancy: HashMap::new(),
+        offices_occupancy: HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AutoPopulation, StartingInfections};
+    use crate::random_wrapper::RandomWrapper;
+
+    fn area(start: (CoOrdinate, CoOrdinate), end: (CoOrdinate, CoOrdinate)) -> Area {
+        Area::new("engine1".to_string(), Point::new(start.0, start.1), Point::new(end.0, end.1))
+    }
+
+    fn test_city() -> Grid {
+        let geojson = GeoJsonGeography::new("./test/resources/test_city.geojson".to_string());
+        define_geography_from_geojson(&GeographyParameters::new(100, 0.003), &geojson, "engine1".to_string())
+    }
+
+    #[test]
+    fn should_rasterize_footprints_by_building_type() {
+        let grid = test_city();
+
+        assert_eq!(grid.houses, vec![area((0, 0), (9, 9)), area((19, 0), (29, 9)), area((29, 49), (30, 50))]);
+        assert_eq!(grid.offices, vec![area((49, 29), (69, 49))]);
+        assert_eq!(grid.schools, vec![area((0, 84), (19, 89))]);
+        assert_eq!(grid.hospital_area, area((79, 79), (99, 89)));
+        assert_eq!(grid.transport_area, area((39, 14), (44, 19)));
+    }
+
+    #[test]
+    fn should_enclose_all_footprints_of_a_type_in_its_area() {
+        let grid = test_city();
+
+        assert_eq!(grid.housing_area, area((0, 0), (30, 50)));
+        assert_eq!(grid.work_area, area((49, 29), (69, 49)));
+    }
+
+    #[test]
+    fn should_grow_small_footprints_within_the_grid() {
+        let raster = Raster::new(BoundingBox { min_x: 0.0, min_y: 0.0, max_x: 10.0, max_y: 10.0 }, 11);
+        let corner = BoundingBox { min_x: 10.0, min_y: 0.0, max_x: 10.0, max_y: 0.0 };
+
+        assert_eq!(raster.area(&corner, (3, 2), "engine1"), area((8, 9), (10, 10)));
+    }
+
+    #[test]
+    fn should_generate_population_on_geojson_geography() {
+        let mut grid = test_city();
+        let auto_pop = AutoPopulation { number_of_agents: 10, public_transport_percentage: 0.2, working_percentage: 0.7,
+                                        age_distribution: Vec::new() };
+        let mut rng = RandomWrapper::new();
+
+        let (home_locations, citizens) = grid.generate_population(&auto_pop, &StartingInfections::default(), &mut rng,
+                                                                  None, "engine1".to_string());
+
+        assert_eq!(citizens.len(), 10);
+        assert!(home_locations.iter().all(|point| grid.houses.iter().any(|house| house.contains(point))));
+    }
+}
@@ -19,15 +19,15 @@
 
 use plotters::prelude::*;
 
-use crate::{agent, constants};
-use crate::agent::{Citizen, PopulationRecord};
-use crate::config::{AutoPopulation, CsvPopulation, StartingInfections};
-use crate::geography::{Area, Point};
+use crate::agent;
+use crate::agent::Citizen;
+use crate::config::{AdmissionPolicy, AutoPopulation, CsvPopulation, HospitalsConfig, SchoolAge, StartingInfections, SyntheticPopulation};
+use crate::geography::{Area, Hospital, Point};
 use crate::random_wrapper::RandomWrapper;
-use std::fs::File;
-use std::collections::HashMap;
-use crate::custom_types::{CoOrdinate, Count, Size};
+use std::collections::{HashMap, HashSet};
+use crate::custom_types::{CoOrdinate, Count, Percentage, Size};
 use crate::kafka_consumer::TravelPlanConfig;
+use crate::population::{csv_population, synthetic_population, PopulationRecord};
 
 #[derive(Serialize)]
 pub struct Grid {
@@ -38,6 +38,14 @@ pub struct Grid {
     pub hospital_area: Area,
     pub houses: Vec<Area>,
     pub offices: Vec<Area>,
+    pub schools: Vec<Area>,
+    pub hospitals: Vec<Hospital>,
+    #[serde(skip_serializing)]
+    pub admission: AdmissionPolicy,
+    #[serde(skip_serializing)]
+    pub icu_percentage: Percentage,
+    #[serde(skip_serializing)]
+    pub school_age: SchoolAge,
     //Occupancy based on home and work locations - updated when travellers arrive/depart
     #[serde(skip_serializing)]
     pub houses_occupancy: HashMap<Area, u32>,
@@ -56,11 +64,20 @@ impl Grid {
         //        TODO: fix the hack
         let number_of_agents_using_public_transport = number_of_agents as f64 * (public_transport_percentage + 0.1) * (working_percentage + 0.1);
 
+        let transport_start = self.transport_area.start_offset;
+        let transport_end = self.transport_area.end_offset;
+        let transport_cells = ((transport_end.x - transport_start.x + 1) * (transport_end.y - transport_start.y + 1)) as f64;
+        if number_of_agents_using_public_transport.ceil() > transport_cells {
+            panic!("Transport area has {} cells, but {} agents could use public transport", transport_cells,
+                   number_of_agents_using_public_transport.ceil());
+        }
+
         let transport_locations = self.transport_area.random_points(number_of_agents_using_public_transport.ceil() as i32, rng);
         debug!("Finished generating transport locations");
 
-        let agent_list = agent::citizen_factory(number_of_agents, &self.houses, &self.offices,
+        let agent_list = agent::citizen_factory(number_of_agents, &self.houses, &self.offices, &self.schools,
                                                 &transport_locations, public_transport_percentage, working_percentage,
+                                                &auto_pop.age_distribution, &self.school_age,
                                                 rng, start_infections, travel_plan_config, region.clone());
         // info!("agent list - {:?} ", agent_list);
         debug!("Finished creating agent list");
@@ -74,16 +91,15 @@ impl Grid {
     fn set_start_locations_and_occupancies(&mut self, rng: &mut RandomWrapper, agent_list: &Vec<Citizen>, region_name: &String) -> (Vec<Point>, Vec<Citizen>) {
         let mut home_loc: Vec<Point> = Vec::new();
         let agents_by_home_locations = Grid::group_agents_by_home_locations(agent_list);
-        let house_capacity = constants::HOME_SIZE * constants::HOME_SIZE;
         debug!("Finished grouping agents by home locations");
         let mut agents_in_order: Vec<Citizen> = Vec::with_capacity(agent_list.len());
         for (home, agents) in agents_by_home_locations {
             trace!("home: {:?} {:?}", home.start_offset, home.end_offset);
             trace!("agents in home: {:?}", agents.len());
 
-            if agents.len() as Count > house_capacity {
+            if agents.len() as Count > home.capacity() {
                 panic!("There are {} agents assigned to a house, but house capacity is {}",
-                       agents.len(), house_capacity)
+                       agents.len(), home.capacity())
             }
 
             let mut random_points_within_home = home.random_points(agents.len() as i32, rng);
@@ -129,6 +145,9 @@ impl Grid {
         for office in offices {
             Grid::draw_rect(&mut draw_backend, office, &plotters::style::RGBColor(51, 153, 255));
         }
+        for school in &self.schools {
+            Grid::draw_rect(&mut draw_backend, school, &plotters::style::GREEN);
+        }
         for home in home_locations {
             draw_backend.draw_pixel((home.x as i32, home.y as i32), &plotters::style::BLACK.to_rgba()).unwrap();
         }
@@ -142,25 +161,43 @@ impl Grid {
 
     pub fn read_population(&mut self, csv_pop: &CsvPopulation, starting_infections: &StartingInfections,
                            rng: &mut RandomWrapper, region_name: &String) -> (Vec<Point>, Vec<Citizen>) {
-        let file = File::open(&csv_pop.file).expect("Could not read population file");
-        let mut rdr = csv::Reader::from_reader(file);
-        let mut homes_iter = self.houses.iter().cycle();
-        let mut offices_iter = self.offices.iter().cycle();
+        let records = csv_population::read(csv_pop);
+        self.populate(records, starting_infections, rng, region_name)
+    }
 
-        let mut citizens = Vec::new();
-        for result in rdr.deserialize() {
-            let record: PopulationRecord = result.expect("Could not deserialize population line");
+    pub fn generate_synthetic_population(&mut self, synthetic_pop: &SyntheticPopulation, starting_infections: &StartingInfections,
+                                         rng: &mut RandomWrapper, region_name: &String) -> (Vec<Point>, Vec<Citizen>) {
+        let records = synthetic_population::generate(synthetic_pop, rng);
+        self.populate(records, starting_infections, rng, region_name)
+    }
 
+    fn populate(&mut self, records: Vec<PopulationRecord>, starting_infections: &StartingInfections,
+                rng: &mut RandomWrapper, region_name: &String) -> (Vec<Point>, Vec<Citizen>) {
+        let homes = self.assign_homes(&records);
+        let (workplaces, open_offices) = self.assign_workplaces(&records);
+        let mut offices_by_capacity = open_offices.into_iter()
+            .flat_map(|office| (0..office.capacity()).map(move |_| office))
+            .cycle();
+        let mut schools_iter = self.schools.iter().cycle();
+
+        let mut citizens = Vec::new();
+        for (record, home) in records.into_iter().zip(homes) {
+            let age = record.age.as_deref().and_then(|age| agent::draw_age(age, rng));
+            let school = if matches!(age, Some(age) if self.school_age.includes(age)) { schools_iter.next().cloned() } else { None };
+            let work_location = match &record.attributes.workplace {
+                _ if !record.working || school.is_some() => home.clone(),
+                Some(workplace) => workplaces[workplace].clone(),
+                None => offices_by_capacity.next().expect("There are no offices to work in").clone(),
+            };
             //TODO seems like transport point isn't being used on the routine() function
-            let home = homes_iter.next().unwrap();
-            let office = offices_iter.next().unwrap();
-            let citizen = Citizen::from_record(record, home.clone(), office.clone(), home.get_random_point(rng), rng);
+            let transport_location = home.get_random_point(rng);
+            let citizen = Citizen::from_record(record, age, home, work_location, school, transport_location, rng);
             citizens.push(citizen);
         }
-        let house_capacity = (constants::HOME_SIZE * constants::HOME_SIZE) as usize;
-        if citizens.len() > house_capacity * self.houses.len() {
+        let home_points: usize = self.houses.iter().map(|house| house.capacity() as usize).sum();
+        if citizens.len() > home_points {
             panic!("Cannot accommodate citizens into homes! There are {} citizens, but {} home points",
-                   citizens.len(), house_capacity * self.houses.len());
+                   citizens.len(), home_points);
         }
 
         let (home_loc, mut agents_in_order) = self.set_start_locations_and_occupancies(rng, &citizens, region_name);
@@ -170,13 +207,143 @@ impl Grid {
         (home_loc, agents_in_order)
     }
 
+    /// Members of a household share a house, the first one free that fits them all, or else are spread over neighbouring
+    /// free houses. Citizens without a household are spread over the houses left
+    fn assign_homes(&self, records: &[PopulationRecord]) -> Vec<Area> {
+        let mut household_sizes: HashMap<&str, Count> = HashMap::new();
+        records.iter().filter_map(|record| record.attributes.household.as_deref())
+            .for_each(|household| *household_sizes.entry(household).or_insert(0) += 1);
+
+        let mut taken = vec![false; self.houses.len()];
+        let mut first_free = 0;
+        let mut household_homes: HashMap<&str, Vec<usize>> = HashMap::new();
+        for household in records.iter().filter_map(|record| record.attributes.household.as_deref()) {
+            if household_homes.contains_key(household) {
+                continue;
+            }
+            let size = household_sizes[household];
+            let houses = match (first_free..self.houses.len()).find(|house| !taken[*house] && self.houses[*house].capacity() >= size) {
+                Some(house) => vec![house],
+                None => {
+                    let mut houses = Vec::new();
+                    let mut room = 0;
+                    for house in (first_free..self.houses.len()).filter(|house| !taken[*house]) {
+                        if room >= size {
+                            break;
+                        }
+                        houses.push(house);
+                        room += self.houses[house].capacity();
+                    }
+                    if room < size {
+                        panic!("There are not enough free houses for household {} of {} citizens", household, size);
+                    }
+                    warn!("There is no free house for household {} of {} citizens, it is spread over {} neighbouring houses",
+                          household, size, houses.len());
+                    houses
+                }
+            };
+            houses.iter().for_each(|house| taken[*house] = true);
+            // one entry per place in the houses, taken from the back as members move in
+            let places = houses.iter().rev().flat_map(|house| (0..self.houses[*house].capacity()).map(move |_| *house)).collect();
+            household_homes.insert(household, places);
+            while first_free < taken.len() && taken[first_free] {
+                first_free += 1;
+            }
+        }
+
+        let free_houses: Vec<&Area> = self.houses.iter().zip(taken).filter(|(_, taken)| !taken).map(|(house, _)| house).collect();
+        let mut other_homes = if free_houses.is_empty() { self.houses.iter().collect() } else { free_houses }.into_iter().cycle();
+        records.iter().map(|record| match record.attributes.household.as_deref() {
+            Some(household) => {
+                let places = household_homes.get_mut(household).unwrap();
+                self.houses[places.pop().expect("The household has more members than counted")].clone()
+            }
+            None => other_homes.next().expect("There are no houses to live in").clone(),
+        }).collect()
+    }
+
+    /// Colleagues of a workplace share an office, one for each workplace while there are enough of them. Returns the
+    /// offices of the workplaces, and the offices left for everyone else
+    fn assign_workplaces(&self, records: &[PopulationRecord]) -> (HashMap<String, &Area>, Vec<&Area>) {
+        let mut workplaces: HashMap<String, &Area> = HashMap::new();
+        let mut offices = self.offices.iter().cycle();
+        for workplace in records.iter().filter(|record| record.working).filter_map(|record| record.attributes.workplace.as_ref()) {
+            if !workplaces.contains_key(workplace) {
+                workplaces.insert(workplace.clone(), offices.next().expect("There are no offices to work in"));
+            }
+        }
+        if workplaces.len() > self.offices.len() {
+            warn!("There are {} workplaces but only {} offices, some workplaces share an office", workplaces.len(), self.offices.len());
+        }
+
+        let taken: HashSet<&Area> = workplaces.values().cloned().collect();
+        let open_offices: Vec<&Area> = self.offices.iter().filter(|office| !taken.contains(office)).collect();
+        if open_offices.is_empty() { (workplaces, self.offices.iter().collect()) } else { (workplaces, open_offices) }
+    }
+
+    /// Grows the hospital area up to the schools on its right, or else to the edge of the grid
     pub fn increase_hospital_size(&mut self, grid_size: Size, sim_id: String) {
         let start_offset = self.hospital_area.start_offset;
-        let end_offset = Point::new(grid_size as CoOrdinate , grid_size as CoOrdinate);
-
+        let end_x = self.schools.iter()
+            .map(|school| school.start_offset.x - 1)
+            .filter(|x| *x >= self.hospital_area.end_offset.x)
+            .min()
+            .unwrap_or(grid_size as CoOrdinate);
+        let end_offset = Point::new(end_x, grid_size as CoOrdinate);
+
+        let new_hospital_start = Point::new(self.hospital_area.end_offset.x + 1, 0);
+        if new_hospital_start.x <= end_offset.x {
+            let new_hospital = Area::new(sim_id.clone(), new_hospital_start, end_offset);
+            let beds = new_hospital.capacity();
+            self.hospitals.push(Hospital::new(new_hospital, beds, 0));
+        }
         self.hospital_area = Area::new(sim_id, start_offset, end_offset)
     }
 
+    /// Places the configured hospitals in the hospital area, one below the other, each with a cell per bed.
+    /// Without configured hospitals, the whole hospital area is a single hospital (unless the geography
+    /// already defines them)
+    pub fn set_hospitals(&mut self, hospitals_config: &HospitalsConfig, sim_id: String) {
+        self.admission = hospitals_config.admission.clone();
+        self.icu_percentage = hospitals_config.icu_percentage;
+        let facilities = &hospitals_config.facilities;
+
+        if !self.hospitals.is_empty() {
+            if facilities.is_empty() {
+                return;
+            }
+            if facilities.len() != self.hospitals.len() {
+                panic!("There are {} hospitals in the geography, but {} configured", self.hospitals.len(), facilities.len());
+            }
+            self.hospitals.iter_mut().zip(facilities.iter()).for_each(|(hospital, config)| {
+                hospital.beds = config.beds;
+                hospital.icu_beds = config.icu_beds;
+            });
+            return;
+        }
+
+        if facilities.is_empty() {
+            let beds = self.hospital_area.capacity();
+            self.hospitals = vec![Hospital::new(self.hospital_area.clone(), beds, 0)];
+            return;
+        }
+
+        let start = self.hospital_area.start_offset;
+        let end = self.hospital_area.end_offset;
+        let width = (end.x - start.x + 1) as Count;
+        let mut next_y = start.y;
+        self.hospitals = facilities.iter().map(|config| {
+            let rows = ((config.beds + config.icu_beds) as f64 / width as f64).ceil().max(1.0) as CoOrdinate;
+            let area = Area::new(sim_id.clone(), Point::new(start.x, next_y), Point::new(end.x, next_y + rows - 1));
+            next_y += rows;
+            Hospital::new(area, config.beds, config.icu_beds)
+        }).collect();
+        if next_y - 1 > end.y {
+            panic!("The hospital area is {} cells high, but {} rows are needed for the configured hospitals",
+                   end.y - start.y + 1, next_y - start.y);
+        }
+    }
+
     pub fn resize_hospital(&mut self, number_of_agents: i32, hospital_staff_percentage: f64, hospital_beds_percentage: f64, sim_id: String) {
         let hospital_bed_count = (number_of_agents as f64 * hospital_beds_percentage +
             number_of_agents as f64 * hospital_staff_percentage).ceil() as Count;
@@ -214,14 +381,12 @@ impl Grid {
     }
 
     pub fn choose_house_with_free_space(&self, _rng: &mut RandomWrapper) -> Area {
-        let house_capacity = constants::HOME_SIZE * constants::HOME_SIZE;
-        self.houses_occupancy.iter().find(|(_house, occupants)| **occupants < house_capacity)
+        self.houses_occupancy.iter().find(|(house, occupants)| **occupants < house.capacity())
             .expect("Couldn't find any house with free space!").0.clone()
     }
 
     pub fn choose_office_with_free_space(&self, _rng: &mut RandomWrapper) -> Area {
-        let office_capacity = constants::OFFICE_SIZE * constants::OFFICE_SIZE;
-        self.offices_occupancy.iter().find(|(_office, occupants)| **occupants < office_capacity)
+        self.offices_occupancy.iter().find(|(office, occupants)| **occupants < office.capacity())
             .expect("Couldn't find any offices with free space!").0.clone()
     }
 
@@ -246,12 +411,14 @@ impl Grid {
 mod tests {
     use super::*;
     use crate::geography::define_geography;
+    use crate::agent::Attributes;
+    use crate::config::{AgeGroup, GeographyParameters, HospitalConfig, SchoolsConfig};
 
     #[test]
     fn should_generate_population() {
         let mut rng = RandomWrapper::new();
 
-        let mut grid = define_geography(100, "engine1".to_string());
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
         let housing_area = grid.housing_area.clone();
         let transport_area = grid.transport_area.clone();
         let work_area = grid.work_area.clone();
@@ -260,6 +427,7 @@ mod tests {
             number_of_agents: 10,
             public_transport_percentage: 0.2,
             working_percentage: 0.2,
+            age_distribution: Vec::new(),
         };
         let start_infections = StartingInfections::new(0, 0, 0, 1);
         let (home_locations, agent_list) = grid.generate_population(&pop, &start_infections, &mut rng, None, "engine1".to_string());
@@ -276,9 +444,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_generate_students_going_to_schools() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = define_geography(&geography_parameters_with_schools(), "engine1".to_string());
+        let pop = AutoPopulation {
+            number_of_agents: 10,
+            public_transport_percentage: 0.2,
+            working_percentage: 0.2,
+            age_distribution: vec![AgeGroup::new("10-14", 1.0)],
+        };
+
+        let (_, agent_list) = grid.generate_population(&pop, &StartingInfections::new(0, 0, 0, 1), &mut rng, None,
+                                                       "engine1".to_string());
+
+        assert!(agent_list.iter().all(|agent| agent.is_student() && grid.schools.contains(&agent.work_location)));
+        assert!(grid.offices_occupancy.values().all(|occupants| *occupants == 0));
+    }
+
+    #[test]
+    fn should_generate_synthetic_population() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = define_geography(&geography_parameters_with_schools(), "engine1".to_string());
+        let pop = SyntheticPopulation {
+            number_of_agents: 10,
+            age_distribution: vec![AgeGroup::new("10-14", 0.5), AgeGroup::new("30-34", 0.5)],
+            male_percentage: 0.4,
+            working_percentage: 0.5,
+            public_transport_percentage: 0.2,
+            tolerance: 1e-6,
+            max_iterations: 100,
+        };
+
+        let (_, agent_list) = grid.generate_synthetic_population(&pop, &StartingInfections::new(0, 0, 0, 1), &mut rng,
+                                                                 &"engine1".to_string());
+
+        assert_eq!(agent_list.len(), 10);
+        assert!(agent_list.iter().all(|agent| agent.get_sex().is_some()));
+        assert!(agent_list.iter().all(|agent| agent.is_student() == (agent.get_age().unwrap() < 30)));
+    }
+
+    fn record(working: bool, household: Option<&str>, workplace: Option<&str>) -> PopulationRecord {
+        PopulationRecord {
+            age: Some("30-34".to_string()),
+            sex: None,
+            working,
+            pub_transport: false,
+            attributes: Attributes {
+                household: household.map(|household| household.to_string()),
+                workplace: workplace.map(|workplace| workplace.to_string()),
+                ..Attributes::default()
+            },
+        }
+    }
+
+    #[test]
+    fn should_place_households_together() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let records = vec![record(true, Some("a"), None), record(false, Some("b"), None), record(false, Some("a"), None),
+                           record(false, Some("b"), None), record(true, None, None)];
+
+        let homes = grid.assign_homes(&records);
+        let (_, agent_list) = grid.populate(records, &StartingInfections::new(0, 0, 0, 1), &mut rng, &"engine1".to_string());
+
+        assert_eq!(homes[0], homes[2]);
+        assert_eq!(homes[1], homes[3]);
+        assert_ne!(homes[0], homes[1]);
+        assert!(homes[4] != homes[0] && homes[4] != homes[1]);
+        assert_eq!(Grid::group_agents_by_home_locations(&agent_list).len(), 3);
+    }
+
+    #[test]
+    fn should_spread_household_larger_than_houses_over_neighbouring_houses() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let capacity = grid.houses[0].capacity() as usize;
+        let mut records: Vec<PopulationRecord> = (0..capacity + 1).map(|_| record(false, Some("a"), None)).collect();
+        records.push(record(false, Some("b"), None));
+
+        let homes = grid.assign_homes(&records);
+
+        assert!(homes[..capacity].iter().all(|home| *home == grid.houses[0]));
+        assert_eq!(homes[capacity], grid.houses[1]);
+        assert_eq!(homes[capacity + 1], grid.houses[2]);
+
+        let (_, citizens) = grid.populate(records, &StartingInfections::new(0, 0, 0, 1), &mut rng, &"engine1".to_string());
+        assert_eq!(citizens.len(), capacity + 2);
+        assert_eq!(grid.houses_occupancy[&grid.houses[1]], 1);
+    }
+
+    #[test]
+    fn should_assign_offices_by_workplace_and_capacity() {
+        let mut rng = RandomWrapper::new();
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let mut records = vec![record(true, None, Some("factory")), record(true, None, Some("factory")), record(false, None, Some("shop"))];
+        records.extend((0..150).map(|_| record(true, None, None)));
+
+        let (_, agent_list) = grid.populate(records, &StartingInfections::new(0, 0, 0, 1), &mut rng, &"engine1".to_string());
+
+        let factory = &grid.offices[0];
+        assert_eq!(grid.offices_occupancy[factory], 2);
+        assert_eq!(grid.offices_occupancy[&grid.offices[1]], grid.offices[1].capacity());
+        assert_eq!(grid.offices_occupancy[&grid.offices[2]], 150 - grid.offices[1].capacity());
+        assert!(agent_list.iter().filter(|agent| !agent.is_working()).all(|agent| agent.work_location == agent.home_location));
+    }
+
+    fn geography_parameters_with_schools() -> GeographyParameters {
+        let mut geography_parameters = GeographyParameters::new(100, 0.003);
+        geography_parameters.schools = Some(SchoolsConfig::default());
+        geography_parameters
+    }
+
     #[test]
     fn should_increase_hospital_size() {
-        let mut grid = define_geography(100, "engine1".to_string());
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
 
         grid.increase_hospital_size(120, "engine1".to_string());
 
@@ -286,27 +566,86 @@ mod tests {
         assert_eq!(grid.hospital_area.end_offset, Point::new(120, 120));
     }
 
+    #[test]
+    fn should_build_new_hospital_next_to_existing_ones() {
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        grid.set_hospitals(&HospitalsConfig::default(), "engine1".to_string());
+
+        grid.increase_hospital_size(120, "engine1".to_string());
+
+        assert_eq!(grid.hospitals.len(), 2);
+        assert_eq!(grid.hospitals[1].area, Area::new("engine1".to_string(), Point::new(80, 0), Point::new(120, 120)));
+        assert_eq!(grid.hospitals[1].beds, 41 * 121);
+    }
+
+    #[test]
+    fn should_not_build_new_hospital_over_schools() {
+        let mut grid = define_geography(&geography_parameters_with_schools(), "engine1".to_string());
+        grid.set_hospitals(&HospitalsConfig::default(), "engine1".to_string());
+
+        grid.increase_hospital_size(100, "engine1".to_string());
+
+        assert_eq!(grid.hospitals[1].area, Area::new("engine1".to_string(), Point::new(80, 0), Point::new(89, 100)));
+        assert!(grid.schools.iter().all(|school| school.start_offset.x == 90));
+    }
+
+    #[test]
+    fn should_use_hospital_area_as_single_hospital_by_default() {
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        grid.resize_hospital(1000, 0.02, 0.01, "engine1".to_string());
+
+        grid.set_hospitals(&HospitalsConfig::default(), "engine1".to_string());
+
+        assert_eq!(grid.hospitals, vec![Hospital::new(grid.hospital_area.clone(), 40, 0)]);
+    }
+
+    #[test]
+    fn should_place_configured_hospitals_in_hospital_area() {
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let config = HospitalsConfig { facilities: vec![HospitalConfig::new(25, 5), HospitalConfig::new(10, 0)],
+            admission: AdmissionPolicy::LeastLoaded, icu_percentage: 0.2 };
+
+        grid.set_hospitals(&config, "engine1".to_string());
+
+        assert_eq!(grid.hospitals, vec![
+            Hospital::new(Area::new("engine1".to_string(), Point::new(70, 0), Point::new(79, 2)), 25, 5),
+            Hospital::new(Area::new("engine1".to_string(), Point::new(70, 3), Point::new(79, 3)), 10, 0)]);
+        assert_eq!(grid.admission, AdmissionPolicy::LeastLoaded);
+        assert_eq!(grid.icu_percentage, 0.2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_not_place_more_hospitals_than_fit_in_hospital_area() {
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
+        let config = HospitalsConfig { facilities: vec![HospitalConfig::new(2000, 0)], ..HospitalsConfig::default() };
+
+        grid.set_hospitals(&config, "engine1".to_string());
+    }
+
     #[test]
     fn grid_should_be_serializable_and_should_not_serialize_skipped_keys() {
-        let grid: Grid = define_geography(75, "engine1".to_string());
+        let grid: Grid = define_geography(&GeographyParameters::new(75, 0.003), "engine1".to_string());
 
         let grid_message = serde_json::to_value(&grid).unwrap();
 
         let message = grid_message.as_object().unwrap();
         let keys = message.keys();
-        assert_eq!(keys.len(), 7);
+        assert_eq!(keys.len(), 9);
         assert!(message.contains_key("grid_size"));
         assert!(message.contains_key("housing_area"));
         assert!(message.contains_key("work_area"));
         assert!(message.contains_key("transport_area"));
         assert!(message.contains_key("hospital_area"));
         assert!(message.contains_key("houses"));
+        assert!(message.contains_key("schools"));
         assert!(message.contains_key("offices"));
+        assert!(message.contains_key("hospitals"));
     }
 
     #[test]
     fn should_resize_hospital() {
-        let mut grid = define_geography(100, "engine1".to_string());
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
         grid.resize_hospital(1000, 0.02, 0.01, "engine1".to_string());
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(70, 0));
@@ -315,7 +654,7 @@ mod tests {
 
     #[test]
     fn should_not_resize_hospital_if_population_is_too_high() {
-        let mut grid = define_geography(100, "engine1".to_string());
+        let mut grid = define_geography(&GeographyParameters::new(100, 0.003), "engine1".to_string());
         grid.resize_hospital(50000, 0.02, 0.01, "engine1".to_string());
 
         assert_eq!(grid.hospital_area.start_offset, Point::new(70, 0));
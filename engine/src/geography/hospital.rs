@@ -0,0 +1,188 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::config::AdmissionPolicy;
+use crate::custom_types::{CoOrdinate, Count};
+use crate::geography::{Area, Point};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Hospital {
+    pub area: Area,
+    pub beds: Count,
+    pub icu_beds: Count,
+}
+
+impl Hospital {
+    pub fn new(area: Area, beds: Count, icu_beds: Count) -> Hospital {
+        Hospital { area, beds, icu_beds }
+    }
+
+    fn distance_squared(&self, point: &Point) -> CoOrdinate {
+        let center_x = (self.area.start_offset.x + self.area.end_offset.x) / 2;
+        let center_y = (self.area.start_offset.y + self.area.end_offset.y) / 2;
+        (center_x - point.x).pow(2) + (center_y - point.y).pow(2)
+    }
+}
+
+/// The hospital (index into `Grid::hospitals`) and kind of bed a patient was admitted to
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bed {
+    pub hospital: usize,
+    pub icu: bool,
+}
+
+/// Beds in use in every hospital during an hour, built from the patients admitted so far
+pub struct HospitalOccupancy {
+    ward: Vec<Count>,
+    icu: Vec<Count>,
+    turned_away: Count,
+}
+
+impl HospitalOccupancy {
+    pub fn new<'a>(hospitals: &[Hospital], beds_in_use: impl Iterator<Item=&'a Bed>) -> HospitalOccupancy {
+        let mut occupancy = HospitalOccupancy { ward: vec![0; hospitals.len()], icu: vec![0; hospitals.len()], turned_away: 0 };
+        beds_in_use.filter(|bed| bed.hospital < hospitals.len())
+            .for_each(|bed| occupancy.occupy(*bed));
+        occupancy
+    }
+
+    /// Finds a bed for a patient at `point`, preferring an ICU bed when one is needed and falling back to a ward bed
+    pub fn admit(&mut self, hospitals: &[Hospital], policy: &AdmissionPolicy, point: &Point, needs_icu: bool) -> Option<Bed> {
+        let icu_bed = if needs_icu { self.find_bed(hospitals, policy, point, true) } else { None };
+        let bed = icu_bed.or_else(|| self.find_bed(hospitals, policy, point, false));
+        if let Some(bed) = bed {
+            self.occupy(bed);
+        }
+        bed
+    }
+
+    fn find_bed(&self, hospitals: &[Hospital], policy: &AdmissionPolicy, point: &Point, icu: bool) -> Option<Bed> {
+        let (occupied, capacity): (&Vec<Count>, fn(&Hospital) -> Count) = if icu {
+            (&self.icu, |hospital| hospital.icu_beds)
+        } else {
+            (&self.ward, |hospital| hospital.beds)
+        };
+        let available = hospitals.iter().enumerate()
+            .filter(|(i, hospital)| occupied[*i] < capacity(hospital));
+        let chosen = match policy {
+            AdmissionPolicy::Nearest => available.min_by_key(|(_, hospital)| hospital.distance_squared(point)),
+            AdmissionPolicy::LeastLoaded => available.min_by(|(a, first), (b, second)| {
+                let load = |i: usize, hospital: &Hospital| occupied[i] as f64 / capacity(hospital) as f64;
+                load(*a, first).partial_cmp(&load(*b, second)).unwrap()
+            }),
+        };
+        chosen.map(|(hospital, _)| Bed { hospital, icu })
+    }
+
+    fn occupy(&mut self, bed: Bed) {
+        if bed.icu {
+            self.icu[bed.hospital] += 1;
+        } else {
+            self.ward[bed.hospital] += 1;
+        }
+    }
+
+    pub fn release(&mut self, bed: Bed) {
+        if bed.icu {
+            self.icu[bed.hospital] -= 1;
+        } else {
+            self.ward[bed.hospital] -= 1;
+        }
+    }
+
+    pub fn turn_away(&mut self) {
+        self.turned_away += 1;
+    }
+
+    pub fn get_turned_away(&self) -> Count {
+        self.turned_away
+    }
+
+    #[cfg(test)]
+    pub fn get_ward_occupancy(&self) -> &Vec<Count> {
+        &self.ward
+    }
+
+    #[cfg(test)]
+    pub fn get_icu_occupancy(&self) -> &Vec<Count> {
+        &self.icu
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hospitals() -> Vec<Hospital> {
+        vec![Hospital::new(Area::new("engine1".to_string(), Point::new(0, 0), Point::new(4, 4)), 2, 1),
+             Hospital::new(Area::new("engine1".to_string(), Point::new(20, 0), Point::new(24, 4)), 4, 0)]
+    }
+
+    #[test]
+    fn should_admit_to_nearest_hospital_with_free_bed() {
+        let hospitals = hospitals();
+        let mut occupancy = HospitalOccupancy::new(&hospitals, vec![].iter());
+        let point = Point::new(1, 1);
+
+        assert_eq!(occupancy.admit(&hospitals, &AdmissionPolicy::Nearest, &point, false), Some(Bed { hospital: 0, icu: false }));
+        assert_eq!(occupancy.admit(&hospitals, &AdmissionPolicy::Nearest, &point, false), Some(Bed { hospital: 0, icu: false }));
+        assert_eq!(occupancy.admit(&hospitals, &AdmissionPolicy::Nearest, &point, false), Some(Bed { hospital: 1, icu: false }));
+    }
+
+    #[test]
+    fn should_admit_to_least_loaded_hospital() {
+        let hospitals = hospitals();
+        let beds_in_use = vec![Bed { hospital: 0, icu: false }, Bed { hospital: 1, icu: false }];
+        let mut occupancy = HospitalOccupancy::new(&hospitals, beds_in_use.iter());
+
+        let bed = occupancy.admit(&hospitals, &AdmissionPolicy::LeastLoaded, &Point::new(1, 1), false);
+
+        assert_eq!(bed, Some(Bed { hospital: 1, icu: false }));
+        assert_eq!(occupancy.get_ward_occupancy(), &vec![1, 2]);
+    }
+
+    #[test]
+    fn should_fall_back_to_ward_bed_when_icu_is_full() {
+        let hospitals = hospitals();
+        let mut occupancy = HospitalOccupancy::new(&hospitals, vec![Bed { hospital: 0, icu: true }].iter());
+
+        let bed = occupancy.admit(&hospitals, &AdmissionPolicy::Nearest, &Point::new(22, 2), true);
+
+        assert_eq!(bed, Some(Bed { hospital: 1, icu: false }));
+        assert_eq!(occupancy.get_icu_occupancy(), &vec![1, 0]);
+    }
+
+    #[test]
+    fn should_not_admit_when_all_beds_are_taken() {
+        let hospitals = vec![Hospital::new(Area::new("engine1".to_string(), Point::new(0, 0), Point::new(4, 4)), 1, 0)];
+        let mut occupancy = HospitalOccupancy::new(&hospitals, vec![Bed { hospital: 0, icu: false }].iter());
+
+        assert_eq!(occupancy.admit(&hospitals, &AdmissionPolicy::Nearest, &Point::new(1, 1), true), None);
+    }
+
+    #[test]
+    fn should_release_beds() {
+        let hospitals = hospitals();
+        let mut occupancy = HospitalOccupancy::new(&hospitals, vec![Bed { hospital: 0, icu: true }].iter());
+
+        occupancy.release(Bed { hospital: 0, icu: true });
+
+        assert_eq!(occupancy.get_icu_occupancy(), &vec![0, 0]);
+    }
+}
@@ -17,34 +17,59 @@
  *
  */
 
+use crate::config::{AdmissionPolicy, GeographyParameters};
 use crate::constants;
 
 mod area;
+mod footprints;
 mod grid;
+mod hospital;
 mod point;
 
 pub use self::area::Area;
+pub use self::footprints::define_geography_from_geojson;
 pub use self::grid::Grid;
+pub use self::hospital::{Bed, Hospital, HospitalOccupancy};
 pub use self::point::Point;
 use std::collections::HashMap;
-use crate::custom_types::{CoOrdinate, Size};
+use crate::custom_types::CoOrdinate;
 
-pub fn define_geography(grid_size: Size, engine_id: String) -> Grid {
-    let home_width = (grid_size as f64 * constants::HOUSE_AREA_RELATIVE_SIZE).ceil() as i32;
+pub fn define_geography(geography_parameters: &GeographyParameters, engine_id: String) -> Grid {
+    let grid_size = geography_parameters.grid_size;
+    let layout = &geography_parameters.layout;
+    let school_width = match &geography_parameters.schools {
+        Some(_) if layout.school > 0.0 => layout.school,
+        Some(_) => constants::SCHOOL_AREA_RELATIVE_SIZE,
+        None => 0.0,
+    };
+    if layout.housing + layout.transport + layout.work + layout.hospital + school_width > 1.0 {
+        panic!("The areas of the grid layout add up to more than the grid: {:?}", layout);
+    }
+    let home_width = (grid_size as f64 * layout.housing).ceil() as i32;
     let transport_start = home_width;
-    let transport_end = home_width + (grid_size as f64 * constants::TRANSPORT_AREA_RELATIVE_SIZE).ceil() as i32;
+    let transport_end = home_width + (grid_size as f64 * layout.transport).ceil() as i32;
     let work_area_start = transport_end;
-    let work_area_end = transport_end + (grid_size as f64 * constants::WORK_AREA_RELATIVE_SIZE).ceil() as i32;
+    let work_area_end = transport_end + (grid_size as f64 * layout.work).ceil() as i32;
     let hospital_start = work_area_end;
-    let hospital_end = work_area_end + (grid_size as f64 * constants::INITIAL_HOSPITAL_RELATIVE_SIZE).ceil() as i32;
+    let hospital_end = work_area_end + (grid_size as f64 * layout.hospital).ceil() as i32;
+    // schools are at the far edge, leaving room for the hospital to grow
+    let school_end = grid_size as i32;
+    let school_start = school_end - (grid_size as f64 * school_width).ceil() as i32;
 
     let housing_area = Area::new(engine_id.to_string(), Point::new(0, 0), Point::new(home_width - 1, grid_size as CoOrdinate));
     let transport_area = Area::new(engine_id.to_string(), Point::new(transport_start, 0), Point::new(transport_end - 1, grid_size as CoOrdinate));
     let work_area = Area::new(engine_id.to_string(), Point::new(work_area_start, 0), Point::new(work_area_end - 1, grid_size as CoOrdinate));
     let hospital_area = Area::new(engine_id.to_string(), Point::new(hospital_start, 0), Point::new(hospital_end - 1, grid_size as CoOrdinate));
 
-    let houses = area::area_factory(housing_area.start_offset, housing_area.end_offset, constants::HOME_SIZE, engine_id.clone());
-    let offices = area::area_factory(work_area.start_offset, work_area.end_offset, constants::OFFICE_SIZE, engine_id);
+    let houses = area::area_factory(housing_area.start_offset, housing_area.end_offset, &geography_parameters.household_sizes, engine_id.clone());
+    let offices = area::area_factory(work_area.start_offset, work_area.end_offset, &geography_parameters.office_sizes, engine_id.clone());
+    let schools = match &geography_parameters.schools {
+        Some(schools) if school_end > school_start => {
+            area::area_factory(Point::new(school_start, 0), Point::new(school_end - 1, grid_size as CoOrdinate),
+                               &schools.sizes, engine_id)
+        }
+        _ => Vec::new(),
+    };
 
     Grid {
         grid_size,
@@ -54,6 +79,11 @@ pub fn define_geography(grid_size: Size, engine_id: String) -> Grid {
         work_area,
         houses,
         offices,
+        schools,
+        hospitals: Vec::new(),
+        admission: AdmissionPolicy::Nearest,
+        icu_percentage: 0.0,
+        school_age: geography_parameters.school_age(),
         houses_occupancy: HashMap::new(),
         offices_occupancy: HashMap::new(),
     }
@@ -62,10 +92,11 @@ pub fn define_geography(grid_size: Size, engine_id: String) -> Grid {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{BuildingSize, GridLayout, SchoolsConfig};
 
     #[test]
     fn should_define_geography(){
-        let grid = define_geography(10, "sim_id".to_string());
+        let grid = define_geography(&GeographyParameters::new(10, 0.003), "sim_id".to_string());
         assert_eq!(grid.housing_area.start_offset, Point::new(0,0));
         assert_eq!(grid.housing_area.end_offset, Point::new(3,10));
 
@@ -78,4 +109,53 @@ mod tests {
         assert_eq!(grid.hospital_area.start_offset, Point::new(7,0));
         assert_eq!(grid.hospital_area.end_offset, Point::new(7,10));
     }
+
+    #[test]
+    fn should_define_schools_at_the_edge_of_the_grid(){
+        let mut geography_parameters = GeographyParameters::new(100, 0.003);
+        geography_parameters.schools = Some(SchoolsConfig::default());
+        let grid = define_geography(&geography_parameters, "sim_id".to_string());
+
+        assert_eq!(grid.schools.len(), 10);
+        assert_eq!(grid.schools[0], Area::new("sim_id".to_string(), Point::new(90, 0), Point::new(99, 9)));
+        assert!(grid.schools.iter().all(|school| school.capacity() == 100));
+    }
+
+    #[test]
+    fn should_not_define_schools_unless_configured(){
+        let mut geography_parameters = GeographyParameters::new(100, 0.003);
+        geography_parameters.layout.school = 0.2;
+
+        let grid = define_geography(&geography_parameters, "sim_id".to_string());
+
+        assert!(grid.schools.is_empty());
+    }
+
+    #[test]
+    fn should_define_geography_with_configured_layout_and_sizes(){
+        let mut geography_parameters = GeographyParameters::new(10, 0.003);
+        geography_parameters.layout = GridLayout { housing: 0.6, transport: 0.1, work: 0.2, hospital: 0.1, school: 0.0 };
+        geography_parameters.household_sizes = vec![BuildingSize::new(9, 1.0)];
+        geography_parameters.office_sizes = vec![BuildingSize::new(2, 1.0)];
+
+        let grid = define_geography(&geography_parameters, "sim_id".to_string());
+
+        assert_eq!(grid.housing_area.end_offset, Point::new(5,10));
+        assert_eq!(grid.transport_area.start_offset, Point::new(6,0));
+        assert_eq!(grid.hospital_area.end_offset, Point::new(9,10));
+        assert!(grid.schools.is_empty());
+        assert_eq!(grid.houses.len(), 6);
+        assert!(grid.houses.iter().all(|house| house.capacity() == 9));
+        assert_eq!(grid.offices.len(), 11);
+        assert!(grid.offices.iter().all(|office| office.capacity() == 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_not_define_geography_larger_than_grid(){
+        let mut geography_parameters = GeographyParameters::new(10, 0.003);
+        geography_parameters.layout.housing = 0.8;
+
+        define_geography(&geography_parameters, "sim_id".to_string());
+    }
 }
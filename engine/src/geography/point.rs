@@ -38,6 +38,11 @@ impl Point {
     pub fn neighbor_iterator(&self) -> NeighborIterator {
         NeighborIterator::new(*self)
     }
+
+    /// The number of steps to the other point, diagonal steps included
+    pub fn chebyshev_distance(&self, other: &Point) -> CoOrdinate {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
 }
 
 impl Add for Point {
@@ -90,6 +95,13 @@ mod tests {
         assert_eq!(output, Point::new(2, 2));
     }
 
+    #[test]
+    fn should_calculate_chebyshev_distance() {
+        assert_eq!(Point::new(1, 1).chebyshev_distance(&Point::new(3, 2)), 2);
+        assert_eq!(Point::new(1, 1).chebyshev_distance(&Point::new(0, 0)), 1);
+        assert_eq!(Point::new(1, 1).chebyshev_distance(&Point::new(1, 1)), 0);
+    }
+
     #[test]
     fn should_iterate_over_neighbor_cells() {
         let cell = Point::new(1, 1);
@@ -0,0 +1,117 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+
+use validator::{Validate, ValidationError, ValidationErrors};
+
+use crate::constants;
+use crate::custom_types::{Hour, Percentage};
+
+/// How the transmission rate of an infected citizen changes with the hours since they got infected
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum InfectiousnessProfile {
+    /// The rate on each day of the infection, the first day first, and none after the last
+    Daily(Vec<Percentage>),
+    /// The rate in each hour of the infection, the first hour first, and none after the last
+    Hourly(Vec<Percentage>),
+    /// A curve shaped like a gamma density over hours, scaled to `peak_rate` at its peak. Its shape must be above 1,
+    /// as checked when the config is read
+    Gamma { shape: f64, scale: f64, peak_rate: Percentage },
+}
+
+impl InfectiousnessProfile {
+    pub fn rate_at(&self, hours_infected: Hour) -> Percentage {
+        match self {
+            InfectiousnessProfile::Daily(rates) => {
+                rates.get((hours_infected / constants::HOURS_IN_A_DAY) as usize).copied().unwrap_or(0.0)
+            }
+            InfectiousnessProfile::Hourly(rates) => rates.get(hours_infected as usize).copied().unwrap_or(0.0),
+            InfectiousnessProfile::Gamma { shape, scale, peak_rate } => {
+                let hours = hours_infected as f64;
+                let peak = (shape - 1.0) * scale;
+                peak_rate * (hours / peak).powf(shape - 1.0) * (-(hours - peak) / scale).exp()
+            }
+        }
+    }
+
+    fn invalid_reason(&self) -> Option<&'static str> {
+        let is_rate = |rate: &Percentage| (0.0..=1.0).contains(rate);
+        match self {
+            InfectiousnessProfile::Daily(rates) | InfectiousnessProfile::Hourly(rates) if !rates.iter().all(is_rate) => {
+                Some("infectiousness rates need to be between 0 and 1")
+            }
+            InfectiousnessProfile::Gamma { shape, .. } if *shape <= 1.0 => Some("the shape of a gamma infectiousness profile must be above 1"),
+            InfectiousnessProfile::Gamma { scale, .. } if *scale <= 0.0 => Some("the scale of a gamma infectiousness profile must be positive"),
+            InfectiousnessProfile::Gamma { peak_rate, .. } if !is_rate(peak_rate) => Some("the peak rate needs to be between 0 and 1"),
+            _ => None,
+        }
+    }
+}
+
+impl Validate for InfectiousnessProfile {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self.invalid_reason() {
+            None => Ok(()),
+            Some(reason) => {
+                let mut errors = ValidationErrors::new();
+                errors.add("profile", ValidationError::new(reason));
+                Err(errors)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_look_up_daily_and_hourly_rates() {
+        let daily = InfectiousnessProfile::Daily(vec![0.0, 0.1, 0.3]);
+        assert_eq!(daily.rate_at(0), 0.0);
+        assert_eq!(daily.rate_at(30), 0.1);
+        assert_eq!(daily.rate_at(71), 0.3);
+        assert_eq!(daily.rate_at(72), 0.0);
+
+        let hourly = InfectiousnessProfile::Hourly(vec![0.2, 0.4]);
+        assert_eq!(hourly.rate_at(1), 0.4);
+        assert_eq!(hourly.rate_at(2), 0.0);
+    }
+
+    #[test]
+    fn should_peak_gamma_profile_at_its_mode() {
+        let gamma = InfectiousnessProfile::Gamma { shape: 3.0, scale: 24.0, peak_rate: 0.2 };
+
+        assert_eq!(gamma.rate_at(0), 0.0);
+        assert!((gamma.rate_at(48) - 0.2).abs() < 1e-9);
+        assert!(gamma.rate_at(24) < 0.2);
+        assert!(gamma.rate_at(96) < 0.2);
+    }
+
+    #[test]
+    fn should_reject_profiles_that_cannot_be_evaluated() {
+        assert!(InfectiousnessProfile::Gamma { shape: 3.0, scale: 24.0, peak_rate: 0.2 }.validate().is_ok());
+        assert!(InfectiousnessProfile::Daily(vec![0.0, 0.1]).validate().is_ok());
+
+        assert!(InfectiousnessProfile::Gamma { shape: 1.0, scale: 24.0, peak_rate: 0.2 }.validate().is_err());
+        assert!(InfectiousnessProfile::Gamma { shape: 3.0, scale: 0.0, peak_rate: 0.2 }.validate().is_err());
+        assert!(InfectiousnessProfile::Gamma { shape: 3.0, scale: 24.0, peak_rate: 1.2 }.validate().is_err());
+        assert!(InfectiousnessProfile::Hourly(vec![0.1, -0.1]).validate().is_err());
+    }
+}
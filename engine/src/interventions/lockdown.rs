@@ -36,6 +36,7 @@ pub struct LockdownIntervention {
     is_locked_down: bool,
     intervention: Option<LockdownConfig>,
     pub zero_infection_hour: Hour,
+    is_global: bool,
 }
 
 impl LockdownIntervention {
@@ -53,11 +54,12 @@ impl LockdownIntervention {
             is_locked_down: false,
             intervention: LockdownIntervention::get_lock_down_intervention(config),
             zero_infection_hour: 0,
+            is_global: false,
         }
     }
 
     pub fn should_apply(&self, counts: &Counts) -> bool {
-        !self.is_locked_down && counts.get_hour() % constants::HOURS_IN_A_DAY == 0 && self.above_threshold(counts)
+        !self.is_global && !self.is_locked_down && counts.get_hour() % constants::HOURS_IN_A_DAY == 0 && self.above_threshold(counts)
     }
 
     fn above_threshold(&self, counts: &Counts) -> bool {
@@ -76,6 +78,9 @@ impl LockdownIntervention {
     }
 
     pub fn should_unlock(&self, counts: &Counts) -> bool {
+        if self.is_global {
+            return false;
+        }
         if counts.get_hour() == self.zero_infection_hour +
             (constants::QUARANTINE_DAYS as f64 * 1.5).round() as Hour * constants::HOURS_IN_A_DAY {
             return self.is_locked_down
@@ -92,6 +97,18 @@ impl LockdownIntervention {
         }
     }
 
+    /// Once the orchestrator has ordered a lockdown, only the orchestrator lifts it, and the local threshold is
+    /// no longer evaluated. Returns true when there was no local config, so the essential workers are yet to be assigned
+    pub fn apply_global(&mut self, essential_workers_population: Percentage) -> bool {
+        self.is_global = true;
+        self.is_locked_down = true;
+        if self.intervention.is_none() {
+            self.intervention = Some(LockdownConfig { at_number_of_infections: 0, essential_workers_population });
+            return true;
+        }
+        false
+    }
+
     pub fn unapply(&mut self) {
         self.is_locked_down = false;
         self.zero_infection_hour = 0;
@@ -136,6 +153,7 @@ mod tests {
             is_locked_down,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         }
     }
 
@@ -149,6 +167,7 @@ mod tests {
             is_locked_down: false,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         };
 
         assert!(!lockdown.should_apply(&Counts::new_test(0, 99, 0, 1, 0, 0, 0)));
@@ -170,6 +189,7 @@ mod tests {
             is_locked_down: false,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         };
 
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
@@ -187,6 +207,7 @@ mod tests {
             is_locked_down: false,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         };
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
 
@@ -210,6 +231,7 @@ mod tests {
             is_locked_down: false,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         };
         assert!(lockdown.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
 
@@ -234,6 +256,7 @@ mod tests {
             is_locked_down: false,
             intervention: Some(config),
             zero_infection_hour: 0,
+            is_global: false,
         };
         lockdown.apply();
         lockdown.set_zero_infection_hour(28);
@@ -241,6 +264,34 @@ mod tests {
         assert!(!lockdown.should_apply(&Counts::new_test(540, 70, 0, 30, 0, 0, 0)));
     }
 
+    #[test]
+    fn should_ignore_local_rules_once_locked_down_globally() {
+        let mut lockdown = LockdownIntervention {
+            is_locked_down: false,
+            intervention: None,
+            zero_infection_hour: 0,
+            is_global: false,
+        };
+
+        assert!(lockdown.apply_global(0.2));
+        assert!(lockdown.is_locked_down());
+        assert_eq!(0.2, lockdown.get_essential_workers_percentage());
+
+        lockdown.set_zero_infection_hour(28);
+        assert!(!lockdown.should_unlock(&Counts::new_test(532, 80, 0, 0, 0, 20, 0)));
+
+        lockdown.unapply();
+        assert!(!lockdown.should_apply(&Counts::new_test(552, 50, 0, 50, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_keep_local_essential_workers_when_locked_down_globally() {
+        let mut lockdown = get_test_lockdown_intervention(false);
+
+        assert!(!lockdown.apply_global(0.5));
+        assert_eq!(0.1, lockdown.get_essential_workers_percentage());
+    }
+
     #[test]
     fn should_return_intervention_name_as_lockdown() {
         let lockdown_intervention = get_test_lockdown_intervention(false);
@@ -0,0 +1,259 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+
+use rand::Rng;
+use validator::Validate;
+
+use crate::config::Config;
+use crate::constants;
+use crate::custom_types::{Count, Hour, Percentage, validate_percentage};
+use crate::interventions::InterventionConfig;
+use crate::interventions::intervention_type::InterventionType;
+use crate::listeners::events::counts::Counts;
+use crate::random_wrapper::RandomWrapper;
+use crate::transmission::Setting;
+
+/// Citizens start wearing masks at the start of a day with more infections than the threshold, each setting
+/// with its own share of them (`adoption`). A mask keeps `source_efficacy` of the infection from an infectious
+/// wearer, and `wearer_efficacy` from a susceptible one. Compliance falls by `compliance_decay` every day after
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone, Validate)]
+pub struct MaskConfig {
+    pub at_number_of_infections: Count,
+    #[serde(default)]
+    #[validate]
+    pub adoption: MaskAdoption,
+    #[validate(custom = "validate_percentage")]
+    pub source_efficacy: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub wearer_efficacy: Percentage,
+    #[serde(default)]
+    #[validate(custom = "validate_percentage")]
+    pub compliance_decay: Percentage,
+}
+
+/// Share of the citizens wearing a mask in each setting, none where not given
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Copy, Clone, Validate)]
+#[serde(default)]
+pub struct MaskAdoption {
+    #[validate(custom = "validate_percentage")]
+    pub home: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub work: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub school: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub transport: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub hospital: Percentage,
+    #[validate(custom = "validate_percentage")]
+    pub community: Percentage,
+}
+
+impl MaskAdoption {
+    pub fn of(&self, setting: Setting) -> Percentage {
+        match setting {
+            Setting::Home => self.home,
+            Setting::Work => self.work,
+            Setting::School => self.school,
+            Setting::Transport => self.transport,
+            Setting::Hospital => self.hospital,
+            Setting::Community => self.community,
+        }
+    }
+}
+
+/// How citizens wear masks on a day, as set on each of them by the intervention
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct MaskUse {
+    adoption: MaskAdoption,
+    compliance: Percentage,
+    source_efficacy: Percentage,
+    wearer_efficacy: Percentage,
+}
+
+impl MaskUse {
+    #[cfg(test)]
+    pub fn new(adoption: MaskAdoption, compliance: Percentage, source_efficacy: Percentage, wearer_efficacy: Percentage) -> MaskUse {
+        MaskUse { adoption, compliance, source_efficacy, wearer_efficacy }
+    }
+
+    pub fn wears_mask(&self, setting: Setting, rng: &mut RandomWrapper) -> bool {
+        rng.get().gen_bool(self.adoption.of(setting) * self.compliance)
+    }
+
+    pub fn get_source_efficacy(&self) -> Percentage {
+        self.source_efficacy
+    }
+
+    pub fn get_wearer_efficacy(&self) -> Percentage {
+        self.wearer_efficacy
+    }
+}
+
+pub struct MaskIntervention {
+    started_at: Option<Hour>,
+    intervention: Option<MaskConfig>,
+}
+
+impl MaskIntervention {
+    pub fn get_mask_intervention(config: &Config) -> Option<MaskConfig> {
+        config.get_interventions().iter().filter_map(|i| {
+            match i {
+                InterventionConfig::Masks(x) => Some(x),
+                _ => None
+            }
+        }).next().copied()
+    }
+
+    pub fn init(config: &Config) -> MaskIntervention {
+        MaskIntervention {
+            started_at: None,
+            intervention: MaskIntervention::get_mask_intervention(config),
+        }
+    }
+
+    pub fn should_apply(&self, counts: &Counts) -> bool {
+        match self.intervention {
+            Some(i) => self.started_at.is_none() && MaskIntervention::start_of_day(counts)
+                && counts.get_infected() > i.at_number_of_infections,
+            None => false
+        }
+    }
+
+    /// Compliance changes at the start of every day after the masks are on
+    pub fn should_update(&self, counts: &Counts) -> bool {
+        match (self.intervention, self.started_at) {
+            (Some(i), Some(start)) => i.compliance_decay > 0.0 && counts.get_hour() > start
+                && MaskIntervention::start_of_day(counts),
+            _ => false
+        }
+    }
+
+    fn start_of_day(counts: &Counts) -> bool {
+        counts.get_hour() % constants::HOURS_IN_A_DAY == 0
+    }
+
+    pub fn apply(&mut self, hour: Hour) {
+        self.started_at = Some(hour);
+    }
+
+    pub fn mask_use(&self, hour: Hour) -> Option<MaskUse> {
+        match (self.intervention, self.started_at) {
+            (Some(i), Some(start)) => {
+                let days = (hour.saturating_sub(start) / constants::HOURS_IN_A_DAY) as i32;
+                Some(MaskUse {
+                    adoption: i.adoption,
+                    compliance: (1.0 - i.compliance_decay).powi(days),
+                    source_efficacy: i.source_efficacy,
+                    wearer_efficacy: i.wearer_efficacy,
+                })
+            }
+            _ => None
+        }
+    }
+}
+
+impl InterventionType for MaskIntervention {
+    fn name(&self) -> String {
+        "masks".to_string()
+    }
+
+    fn json_data(&self) -> String {
+        if self.started_at.is_some() {
+            r#"{"status": "masks_on"}"#.to_string()
+        } else {
+            r#"{"status": "masks_off"}"#.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_masks(compliance_decay: Percentage) -> MaskIntervention {
+        let adoption = MaskAdoption { work: 1.0, school: 1.0, transport: 1.0, hospital: 1.0, community: 1.0, ..MaskAdoption::default() };
+        let config = MaskConfig { at_number_of_infections: 20, adoption, source_efficacy: 0.5, wearer_efficacy: 0.3, compliance_decay };
+        MaskIntervention { started_at: None, intervention: Some(config) }
+    }
+
+    #[test]
+    fn should_start_masks_at_start_of_day_above_threshold() {
+        let mut masks = get_test_masks(0.0);
+
+        assert!(!masks.should_apply(&Counts::new_test(24, 80, 0, 20, 0, 0, 0)));
+        assert!(!masks.should_apply(&Counts::new_test(30, 79, 0, 21, 0, 0, 0)));
+        assert!(masks.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
+
+        masks.apply(48);
+        assert!(!masks.should_apply(&Counts::new_test(72, 70, 0, 30, 0, 0, 0)));
+        assert_eq!(masks.json_data(), r#"{"status": "masks_on"}"#);
+    }
+
+    #[test]
+    fn should_decay_compliance_every_day() {
+        let mut masks = get_test_masks(0.5);
+        assert_eq!(masks.mask_use(48), None);
+        assert!(!masks.should_update(&Counts::new_test(72, 70, 0, 30, 0, 0, 0)));
+
+        masks.apply(48);
+        assert!(!masks.should_update(&Counts::new_test(48, 70, 0, 30, 0, 0, 0)));
+        assert!(masks.should_update(&Counts::new_test(72, 70, 0, 30, 0, 0, 0)));
+        assert_eq!(masks.mask_use(48).unwrap().compliance, 1.0);
+        assert_eq!(masks.mask_use(72).unwrap().compliance, 0.5);
+        assert_eq!(masks.mask_use(96).unwrap().compliance, 0.25);
+    }
+
+    #[test]
+    fn should_not_update_without_compliance_decay() {
+        let mut masks = get_test_masks(0.0);
+        masks.apply(48);
+
+        assert!(!masks.should_update(&Counts::new_test(72, 70, 0, 30, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_read_adoption_as_shares_that_default_to_none() {
+        let json = r#"{"at_number_of_infections": 100, "adoption": {"work": 0.6}, "source_efficacy": 0.5, "wearer_efficacy": 0.3}"#;
+        let config: MaskConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.adoption, MaskAdoption { work: 0.6, ..MaskAdoption::default() });
+        assert_eq!(config.adoption.of(Setting::Home), 0.0);
+        assert!(config.validate().is_ok());
+
+        let json = r#"{"at_number_of_infections": 100, "source_efficacy": 0.5, "wearer_efficacy": 0.3}"#;
+        let config: MaskConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.adoption, MaskAdoption::default());
+
+        let invalid = MaskConfig { adoption: MaskAdoption { transport: 1.5, ..MaskAdoption::default() }, ..config };
+        assert!(invalid.validate().is_err());
+        assert!(InterventionConfig::Masks(invalid).validate().is_err());
+    }
+
+    #[test]
+    fn should_wear_masks_by_adoption_of_setting() {
+        let mut masks = get_test_masks(0.0);
+        masks.apply(48);
+        let mask_use = masks.mask_use(48).unwrap();
+        let mut rng = RandomWrapper::new();
+
+        assert!(mask_use.wears_mask(Setting::Work, &mut rng));
+        assert!(!mask_use.wears_mask(Setting::Home, &mut rng));
+    }
+}
@@ -20,10 +20,18 @@
 use crate::interventions::vaccination::{VaccinateConfig, VaccinateIntervention};
 use crate::interventions::lockdown::{LockdownConfig, LockdownIntervention};
 use crate::interventions::hospital::{BuildNewHospitalConfig, BuildNewHospital};
+use crate::custom_types::Percentage;
+use crate::interventions::travel_restriction::TravelRestrictions;
+use crate::interventions::school_closure::{SchoolClosureConfig, SchoolClosureIntervention};
+use crate::interventions::masks::{MaskConfig, MaskIntervention};
+use validator::{Validate, ValidationErrors};
 
 pub mod hospital;
 pub mod lockdown;
 pub mod vaccination;
+pub mod travel_restriction;
+pub mod school_closure;
+pub mod masks;
 pub mod intervention_type;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
@@ -32,10 +40,35 @@ pub enum InterventionConfig {
     Vaccinate(VaccinateConfig),
     Lockdown(LockdownConfig),
     BuildNewHospital(BuildNewHospitalConfig),
+    SchoolClosure(SchoolClosureConfig),
+    Masks(MaskConfig),
+}
+
+impl Validate for InterventionConfig {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            InterventionConfig::Vaccinate(config) => config.validate(),
+            InterventionConfig::Lockdown(config) => config.validate(),
+            InterventionConfig::Masks(config) => config.validate(),
+            InterventionConfig::BuildNewHospital(_) | InterventionConfig::SchoolClosure(_) => Ok(()),
+        }
+    }
+}
+
+/// Decided by the orchestrator over the counts of all the regions, and received along with the ticks
+#[derive(Debug, PartialEq, Deserialize, Clone)]
+pub enum InterventionCommand {
+    Lockdown { essential_workers_population: Percentage },
+    LiftLockdown {},
+    RestrictTravel(TravelRestrictions),
+    LiftTravelRestrictions {},
 }
 
 pub struct Interventions {
     pub vaccinate: VaccinateIntervention,
     pub lockdown: LockdownIntervention,
     pub build_new_hospital: BuildNewHospital,
+    pub travel_restrictions: TravelRestrictions,
+    pub school_closure: SchoolClosureIntervention,
+    pub masks: MaskIntervention,
 }
@@ -0,0 +1,162 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::config::Config;
+use crate::constants;
+use crate::custom_types::Count;
+use crate::interventions::InterventionConfig;
+use crate::interventions::intervention_type::InterventionType;
+use crate::listeners::events::counts::Counts;
+
+/// Schools close at the start of a day with more infections than the threshold, and reopen at the start of a
+/// day with fewer infections than the reopening threshold, if there is one
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct SchoolClosureConfig {
+    pub at_number_of_infections: Count,
+    #[serde(default)]
+    pub reopen_below_number_of_infections: Option<Count>,
+}
+
+pub struct SchoolClosureIntervention {
+    is_closed: bool,
+    intervention: Option<SchoolClosureConfig>,
+}
+
+impl SchoolClosureIntervention {
+    pub fn get_school_closure_intervention(config: &Config) -> Option<SchoolClosureConfig> {
+        config.get_interventions().iter().filter_map(|i| {
+            match i {
+                InterventionConfig::SchoolClosure(x) => Some(x),
+                _ => None
+            }
+        }).next().copied()
+    }
+
+    pub fn init(config: &Config) -> SchoolClosureIntervention {
+        SchoolClosureIntervention {
+            is_closed: false,
+            intervention: SchoolClosureIntervention::get_school_closure_intervention(config),
+        }
+    }
+
+    pub fn should_apply(&self, counts: &Counts) -> bool {
+        match self.intervention {
+            Some(i) => !self.is_closed && SchoolClosureIntervention::start_of_day(counts)
+                && counts.get_infected() > i.at_number_of_infections,
+            None => false
+        }
+    }
+
+    pub fn should_reopen(&self, counts: &Counts) -> bool {
+        match self.intervention.and_then(|i| i.reopen_below_number_of_infections) {
+            Some(threshold) => self.is_closed && SchoolClosureIntervention::start_of_day(counts)
+                && counts.get_infected() < threshold,
+            None => false
+        }
+    }
+
+    fn start_of_day(counts: &Counts) -> bool {
+        counts.get_hour() % constants::HOURS_IN_A_DAY == 0
+    }
+
+    pub fn apply(&mut self) {
+        self.is_closed = true;
+    }
+
+    pub fn unapply(&mut self) {
+        self.is_closed = false;
+    }
+}
+
+impl InterventionType for SchoolClosureIntervention {
+    fn name(&self) -> String {
+        "school_closure".to_string()
+    }
+
+    fn json_data(&self) -> String {
+        if self.is_closed {
+            r#"{"status": "schools_closed"}"#.to_string()
+        } else {
+            r#"{"status": "schools_reopened"}"#.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_school_closure(reopen_below_number_of_infections: Option<Count>) -> SchoolClosureIntervention {
+        let config = SchoolClosureConfig { at_number_of_infections: 20, reopen_below_number_of_infections };
+        SchoolClosureIntervention { is_closed: false, intervention: Some(config) }
+    }
+
+    #[test]
+    fn should_close_schools_at_start_of_day_above_threshold() {
+        let mut school_closure = get_test_school_closure(None);
+
+        assert!(!school_closure.should_apply(&Counts::new_test(24, 80, 0, 20, 0, 0, 0)));
+        assert!(!school_closure.should_apply(&Counts::new_test(30, 79, 0, 21, 0, 0, 0)));
+        assert!(school_closure.should_apply(&Counts::new_test(48, 79, 0, 21, 0, 0, 0)));
+
+        school_closure.apply();
+        assert!(school_closure.is_closed);
+        assert!(!school_closure.should_apply(&Counts::new_test(72, 70, 0, 30, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_reopen_schools_at_start_of_day_below_threshold() {
+        let mut school_closure = get_test_school_closure(Some(10));
+        assert!(!school_closure.should_reopen(&Counts::new_test(24, 95, 0, 5, 0, 0, 0)));
+
+        school_closure.apply();
+        assert!(!school_closure.should_reopen(&Counts::new_test(48, 90, 0, 10, 0, 0, 0)));
+        assert!(!school_closure.should_reopen(&Counts::new_test(50, 91, 0, 9, 0, 0, 0)));
+        assert!(school_closure.should_reopen(&Counts::new_test(72, 91, 0, 9, 0, 0, 0)));
+
+        school_closure.unapply();
+        assert!(!school_closure.is_closed);
+        assert!(school_closure.should_apply(&Counts::new_test(96, 70, 0, 30, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_not_reopen_schools_without_reopening_threshold() {
+        let mut school_closure = get_test_school_closure(None);
+        school_closure.apply();
+
+        assert!(!school_closure.should_reopen(&Counts::new_test(48, 100, 0, 0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_not_close_schools_when_absent() {
+        let school_closure = SchoolClosureIntervention { is_closed: false, intervention: None };
+
+        assert!(!school_closure.should_apply(&Counts::new_test(48, 0, 0, 100, 0, 0, 0)));
+    }
+
+    #[test]
+    fn should_return_json_data_with_school_status() {
+        let mut school_closure = get_test_school_closure(None);
+        assert_eq!(school_closure.name(), "school_closure");
+        assert_eq!(school_closure.json_data(), r#"{"status": "schools_reopened"}"#);
+
+        school_closure.apply();
+        assert_eq!(school_closure.json_data(), r#"{"status": "schools_closed"}"#);
+    }
+}
@@ -0,0 +1,117 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::disease_state_machine::DiseaseStateMachine;
+
+/// Scales the flow between two regions, a scale of 0 blocks it. Without `from` it applies to the flows
+/// from all regions into `to`, and without `to` to all flows out of `from`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FlowRestriction {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    pub scale: f64,
+}
+
+impl FlowRestriction {
+    fn matches(&self, from_region: &str, to_region: &str) -> bool {
+        self.from.iter().all(|from| from == from_region) && self.to.iter().all(|to| to == to_region)
+    }
+}
+
+/// Ordered by the orchestrator, so that all engines agree on which flows are restricted
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct TravelRestrictions {
+    #[serde(default)]
+    flows: Vec<FlowRestriction>,
+    #[serde(default)]
+    screen_symptomatic: bool,
+}
+
+impl TravelRestrictions {
+    #[cfg(test)]
+    pub fn new(flows: Vec<FlowRestriction>, screen_symptomatic: bool) -> TravelRestrictions {
+        TravelRestrictions { flows, screen_symptomatic }
+    }
+
+    /// The strictest of the matching restrictions, 1.0 when the flow is not restricted
+    pub fn scale(&self, from_region: &str, to_region: &str) -> f64 {
+        self.flows.iter()
+            .filter(|flow| flow.matches(from_region, to_region))
+            .map(|flow| flow.scale)
+            .fold(1.0, f64::min)
+    }
+
+    pub fn is_blocked(&self, from_region: &str, to_region: &str) -> bool {
+        self.scale(from_region, to_region) <= 0.0
+    }
+
+    /// Entry screening turns back symptomatic travellers, who stay in their current region
+    pub fn allows(&self, state_machine: &DiseaseStateMachine) -> bool {
+        !(self.screen_symptomatic && state_machine.is_symptomatic())
+    }
+
+    /// Number of travellers allowed out of the planned count
+    pub fn allowed_count(&self, from_region: &str, to_region: &str, count: usize) -> usize {
+        (count as f64 * self.scale(from_region, to_region)).round() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restrictions() -> TravelRestrictions {
+        TravelRestrictions::new(vec![
+            FlowRestriction { from: Some("engine1".to_string()), to: Some("engine2".to_string()), scale: 0.5 },
+            FlowRestriction { from: None, to: Some("engine3".to_string()), scale: 0.0 },
+        ], true)
+    }
+
+    #[test]
+    fn should_scale_matching_flows() {
+        let restrictions = restrictions();
+
+        assert_eq!(0.5, restrictions.scale("engine1", "engine2"));
+        assert_eq!(1.0, restrictions.scale("engine2", "engine1"));
+        assert_eq!(6, restrictions.allowed_count("engine1", "engine2", 12));
+        assert_eq!(7, restrictions.allowed_count("engine2", "engine1", 7));
+    }
+
+    #[test]
+    fn should_block_all_flows_into_region() {
+        let restrictions = restrictions();
+
+        assert!(restrictions.is_blocked("engine1", "engine3"));
+        assert!(restrictions.is_blocked("engine2", "engine3"));
+        assert!(!restrictions.is_blocked("engine3", "engine1"));
+    }
+
+    #[test]
+    fn should_screen_symptomatic_travellers() {
+        let restrictions = restrictions();
+        let mut state_machine = DiseaseStateMachine::new();
+        assert!(restrictions.allows(&state_machine));
+
+        state_machine.set_mild_symptomatic();
+        assert!(!restrictions.allows(&state_machine));
+        assert!(TravelRestrictions::default().allows(&state_machine));
+    }
+}
@@ -0,0 +1,127 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+
+use rand::Rng;
+
+use crate::constants;
+use crate::custom_types::{Hour, Percentage, validate_percentage};
+use crate::random_wrapper::RandomWrapper;
+use validator::Validate;
+
+/// How symptomatic citizens isolate at home. Each of them does so with the probability, `delay` hours after
+/// their symptoms start, for `duration` hours. With `household_quarantine` the others living in the same house
+/// stay home for as long
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone, Validate)]
+#[serde(default)]
+pub struct SelfIsolation {
+    #[validate(custom = "validate_percentage")]
+    pub probability: Percentage,
+    pub delay: Hour,
+    pub duration: Hour,
+    pub household_quarantine: bool,
+}
+
+impl Default for SelfIsolation {
+    fn default() -> Self {
+        SelfIsolation {
+            probability: 1.0,
+            delay: 0,
+            duration: constants::QUARANTINE_DAYS * constants::HOURS_IN_A_DAY,
+            household_quarantine: false,
+        }
+    }
+}
+
+impl SelfIsolation {
+    pub fn decide(&self, symptoms_at: Hour, rng: &mut RandomWrapper) -> Isolation {
+        if rng.get().gen_bool(self.probability) {
+            let from = symptoms_at + self.delay;
+            Isolation::SelfIsolating { from, until: from + self.duration }
+        } else {
+            Isolation::Declined
+        }
+    }
+}
+
+/// What a citizen does about isolating
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum Isolation {
+    Declined,
+    SelfIsolating { from: Hour, until: Hour },
+    HouseholdQuarantine { until: Hour },
+}
+
+impl Isolation {
+    pub fn is_isolating_at(&self, hour: Hour) -> bool {
+        match *self {
+            Isolation::Declined => false,
+            Isolation::SelfIsolating { from, until } => from <= hour && hour < until,
+            Isolation::HouseholdQuarantine { until } => hour < until,
+        }
+    }
+
+    /// Whether it is the citizen's own decision, rather than one for the household
+    pub fn is_decided(&self) -> bool {
+        !matches!(self, Isolation::HouseholdQuarantine { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_isolate_after_delay_for_duration() {
+        let self_isolation = SelfIsolation { probability: 1.0, delay: 12, duration: 48, household_quarantine: false };
+        let isolation = self_isolation.decide(100, &mut RandomWrapper::new());
+
+        assert_eq!(isolation, Isolation::SelfIsolating { from: 112, until: 160 });
+        assert!(!isolation.is_isolating_at(111));
+        assert!(isolation.is_isolating_at(112));
+        assert!(isolation.is_isolating_at(159));
+        assert!(!isolation.is_isolating_at(160));
+    }
+
+    #[test]
+    fn should_decline_to_isolate_by_probability() {
+        let self_isolation = SelfIsolation { probability: 0.0, ..SelfIsolation::default() };
+        let isolation = self_isolation.decide(100, &mut RandomWrapper::new());
+
+        assert_eq!(isolation, Isolation::Declined);
+        assert!(!isolation.is_isolating_at(100));
+        assert!(isolation.is_decided());
+    }
+
+    #[test]
+    fn should_not_validate_probability_out_of_range() {
+        assert!(SelfIsolation::default().validate().is_ok());
+        assert!(SelfIsolation { probability: 1.2, ..SelfIsolation::default() }.validate().is_err());
+        assert!(SelfIsolation { probability: -0.2, ..SelfIsolation::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn should_quarantine_household_until_hour() {
+        let isolation = Isolation::HouseholdQuarantine { until: 50 };
+
+        assert!(isolation.is_isolating_at(10));
+        assert!(!isolation.is_isolating_at(50));
+        assert!(!isolation.is_decided());
+    }
+}
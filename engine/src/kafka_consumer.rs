@@ -1,4 +1,7 @@
+use std::collections::HashMap;
 use std::error::Error;
+use std::thread;
+use std::thread::JoinHandle;
 
 use futures::StreamExt;
 use rdkafka::ClientConfig;
@@ -7,11 +10,18 @@ use rdkafka::consumer::Consumer;
 use rdkafka::error::KafkaError;
 use rdkafka::message::BorrowedMessage;
 use rdkafka::message::Message;
+use tokio::runtime::Runtime;
+use tokio::task;
+use validator::{Validate, ValidationErrors};
 
 use crate::config::Config;
 use crate::epidemiology_simulation::Epidemiology;
 use crate::{RunMode, environment};
 use crate::commute::{Commute, CommutePlan};
+use crate::custom_types::Hour;
+use crate::travel_plan;
+use crate::travel_plan::ScheduledMatrix;
+use crate::calendar::Calendar;
 
 pub struct KafkaConsumer<'a> {
     engine_id: &'a str,
@@ -61,24 +71,63 @@ impl KafkaConsumer<'_> {
                 epidemiology.run(&req.config, None, run_mode).await;
             }
             Request::MultiSimRequest(req) => {
+                let regions = req.regions_for(self.engine_id);
                 let travel_plan_config = req.travel_plan;
-                let sim_req = req.engine_configs.iter().find(|c| c.engine_id == self.engine_id);
-                match sim_req {
-                    None => { error!("Couldn't find any work for engine_id: {}", self.engine_id) }
-                    Some(req) => {
+                let mut sim_reqs: Vec<SimRequestByEngine> = req.engine_configs.into_iter()
+                    .filter(|c| regions.contains(&c.engine_id))
+                    .collect();
+                match sim_reqs.len() {
+                    0 => { error!("Couldn't find any work for engine_id: {}", self.engine_id) }
+                    1 => {
+                        let req = sim_reqs.remove(0);
+                        let region_run_mode = RunMode::MultiEngine { engine_id: req.engine_id.clone() };
                         let mut epidemiology = Epidemiology::new(&req.config.config, Some(travel_plan_config.clone()), req.engine_id.to_string());
-                        epidemiology.run(&req.config.config, Some(travel_plan_config.clone()), run_mode).await;
+                        epidemiology.run(&req.config.config, Some(travel_plan_config.clone()), &region_run_mode).await;
+                    }
+                    _ => {
+                        info!("Engine {} is hosting regions {:?}", self.engine_id, regions);
+                        KafkaConsumer::run_regions(sim_reqs, travel_plan_config).await;
                     }
                 }
             }
         }
     }
 
+    /// Each region runs on its own thread and runtime, and talks to the orchestrator and the other regions
+    /// through its own topics, exactly like a region hosted in a separate engine. The threads are joined off the
+    /// async loop, and a region that fails is reported without bringing down the others
+    async fn run_regions(sim_reqs: Vec<SimRequestByEngine>, travel_plan_config: TravelPlanConfig) {
+        let handles: Vec<(String, JoinHandle<()>)> = sim_reqs.into_iter().map(|req| {
+            let travel_plan_config = travel_plan_config.clone();
+            let region = req.engine_id.clone();
+            let handle = thread::spawn(move || {
+                let region_run_mode = RunMode::MultiEngine { engine_id: req.engine_id.clone() };
+                let mut runtime = Runtime::new().expect("Failed to create runtime for region");
+                let mut epidemiology = Epidemiology::new(&req.config.config, Some(travel_plan_config.clone()), req.engine_id.to_string());
+                runtime.block_on(epidemiology.run(&req.config.config, Some(travel_plan_config), &region_run_mode));
+            });
+            (region, handle)
+        }).collect();
+        let failed_regions = task::spawn_blocking(move || KafkaConsumer::join_regions(handles)).await
+            .expect("Failed to wait for the regions");
+        if !failed_regions.is_empty() {
+            error!("Simulation failed for regions {:?}", failed_regions);
+        }
+    }
+
+    fn join_regions(handles: Vec<(String, JoinHandle<()>)>) -> Vec<String> {
+        handles.into_iter()
+            .filter_map(|(region, handle)| handle.join().err().map(|_| region))
+            .collect()
+    }
+
     fn parse_message(&self, message: Result<BorrowedMessage, KafkaError>) -> Result<Request, Box<dyn Error>> {
         let borrowed_message = message?;
         let parsed_message = borrowed_message.payload_view::<str>().unwrap()?;
         debug!("Received: {}", parsed_message);
-        serde_json::from_str(parsed_message).map_err(|e| e.into())
+        let request: Request = serde_json::from_str(parsed_message)?;
+        request.validate_configs()?;
+        Ok(request)
     }
 }
 
@@ -101,6 +150,8 @@ pub struct Migration {
     matrix: Option<Vec<Vec<u32>>>,
     start_migration_hour: u32,
     end_migration_hour: u32,
+    #[serde(default)]
+    schedule: Vec<ScheduledMatrix>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -123,12 +174,20 @@ impl TravelPlanConfig {
         self.migration.matrix.clone()
     }
 
+    pub fn get_migration_matrix_at(&self, hour: Hour, calendar: &Calendar) -> Option<Vec<Vec<u32>>> {
+        self.migration.matrix.as_ref().map(|matrix| travel_plan::matrix_at(matrix, &self.migration.schedule, hour, calendar).to_vec())
+    }
+
     pub fn get_regions(&self) -> Vec<String> {
         self.regions.clone()
     }
 
     pub fn commute_plan(&self) -> CommutePlan {
-        CommutePlan {regions: self.regions.clone(), matrix: self.commute.matrix.as_ref().unwrap().clone()}
+        CommutePlan {
+            regions: self.regions.clone(),
+            matrix: self.commute.matrix.as_ref().unwrap().clone(),
+            schedule: self.commute.schedule.clone(),
+        }
     }
 
 }
@@ -136,12 +195,77 @@ impl TravelPlanConfig {
 #[derive(Debug, Deserialize)]
 struct MultiSimRequest {
     engine_configs: Vec<SimRequestByEngine>,
-    travel_plan: TravelPlanConfig
+    travel_plan: TravelPlanConfig,
+    /// Regions hosted by each engine, set by the orchestrator when the regions are packed into fewer engines
+    #[serde(default)]
+    region_assignment: HashMap<String, Vec<String>>,
+}
+
+impl MultiSimRequest {
+    fn regions_for(&self, engine_id: &str) -> Vec<String> {
+        if self.region_assignment.is_empty() {
+            return vec![engine_id.to_string()];
+        }
+        self.region_assignment.get(engine_id).cloned().unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 enum Request {
-    SimulationRequest(SimulationRequest),
-    MultiSimRequest(MultiSimRequest),
+    SimulationRequest(Box<SimulationRequest>),
+    MultiSimRequest(Box<MultiSimRequest>),
+}
+
+impl Request {
+    fn validate_configs(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Request::SimulationRequest(req) => req.config.validate(),
+            Request::MultiSimRequest(req) => req.engine_configs.iter().try_for_each(|c| c.config.config.validate()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multi_sim_request(region_assignment: &str) -> MultiSimRequest {
+        let json = format!(r#"{{
+            "engine_configs": [],
+            "travel_plan": {{
+                "regions": ["engine1", "engine2", "engine3"],
+                "migration": {{"enabled": false, "matrix": null, "start_migration_hour": 0, "end_migration_hour": 0}},
+                "commute": {{"enabled": false, "matrix": null}}
+            }}
+            {}
+        }}"#, region_assignment);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn should_host_own_region_without_assignment() {
+        let request = multi_sim_request("");
+
+        assert_eq!(vec!["engine1".to_string()], request.regions_for("engine1"));
+    }
+
+    #[test]
+    fn should_host_assigned_regions() {
+        let request = multi_sim_request(r#", "region_assignment": {"engine-a": ["engine1", "engine3"], "engine-b": ["engine2"]}"#);
+
+        assert_eq!(vec!["engine1".to_string(), "engine3".to_string()], request.regions_for("engine-a"));
+        assert_eq!(vec!["engine2".to_string()], request.regions_for("engine-b"));
+        assert!(request.regions_for("engine1").is_empty());
+    }
+
+    #[test]
+    fn should_report_failed_regions() {
+        let handles = vec![
+            ("engine1".to_string(), thread::spawn(|| {})),
+            ("engine2".to_string(), thread::spawn(|| panic!("No citizens!"))),
+        ];
+
+        assert_eq!(vec!["engine2".to_string()], KafkaConsumer::join_regions(handles));
+    }
 }
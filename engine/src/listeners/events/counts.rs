@@ -17,27 +17,65 @@
  *
  */
 
+use chrono::NaiveDate;
+
+use crate::constants;
 use crate::custom_types::{Count, Hour};
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
 pub struct Counts {
     hour: Hour,
+    #[serde(default)]
+    date: Option<NaiveDate>,
     susceptible: Count,
     exposed: Count,
     infected: Count,
     hospitalized: Count,
     recovered: Count,
     deceased: Count,
+    #[serde(default)]
+    in_icu: Count,
+    #[serde(default)]
+    turned_away: Count,
+    #[serde(default)]
+    excess_deaths: Count,
+    #[serde(default)]
+    new_deaths: Count,
+    #[serde(default)]
+    new_hospital_admissions: Count,
+    #[serde(default)]
+    new_exposures: Count,
+    #[serde(default)]
+    new_infections: Count,
+    #[serde(default)]
+    new_symptomatic: Count,
+    #[serde(default)]
+    new_recoveries: Count,
+    #[serde(default)]
+    daily_new_exposures: Count,
+    #[serde(default)]
+    daily_new_infections: Count,
+    #[serde(default)]
+    daily_new_symptomatic: Count,
+    #[serde(default)]
+    daily_new_hospital_admissions: Count,
+    #[serde(default)]
+    daily_new_recoveries: Count,
+    #[serde(default)]
+    daily_new_deaths: Count,
 }
 
 impl Counts {
     #[cfg(test)]
     pub fn new_test(hour: Hour, susceptible: Count, exposed: Count, infected: Count, hospitalized: Count, recovered: Count, deceased: Count) -> Counts {
-        Counts { hour, susceptible, exposed, infected, hospitalized, recovered, deceased }
+        Counts { hour, hospitalized, recovered, deceased, ..Counts::new(susceptible, exposed, infected) }
     }
 
     pub fn new(susceptible: Count, exposed: Count, infected: Count) -> Counts {
-        Counts { hour: 0, susceptible, exposed, infected, hospitalized: 0, recovered: 0, deceased: 0 }
+        Counts { hour: 0, date: None, susceptible, exposed, infected, hospitalized: 0, recovered: 0, deceased: 0, in_icu: 0, turned_away: 0, excess_deaths: 0,
+                 new_deaths: 0, new_hospital_admissions: 0, new_exposures: 0, new_infections: 0, new_symptomatic: 0, new_recoveries: 0,
+                 daily_new_exposures: 0, daily_new_infections: 0, daily_new_symptomatic: 0, daily_new_hospital_admissions: 0,
+                 daily_new_recoveries: 0, daily_new_deaths: 0 }
     }
 
     pub fn get_susceptible(&self) -> Count {
@@ -64,6 +102,77 @@ impl Counts {
         self.deceased
     }
 
+    /// Hospitalized patients in an ICU bed, these are also counted as hospitalized
+    pub fn get_in_icu(&self) -> Count {
+        self.in_icu
+    }
+
+    /// Patients that needed a hospital bed in this hour but could not get one
+    pub fn get_turned_away(&self) -> Count {
+        self.turned_away
+    }
+
+    /// Deceased that were denied the hospital or ICU bed they needed, and would have survived with it.
+    /// These are also counted as deceased
+    pub fn get_excess_deaths(&self) -> Count {
+        self.excess_deaths
+    }
+
+    /// Citizens that died in this hour
+    pub fn get_new_deaths(&self) -> Count {
+        self.new_deaths
+    }
+
+    /// Citizens admitted to a hospital in this hour
+    pub fn get_new_hospital_admissions(&self) -> Count {
+        self.new_hospital_admissions
+    }
+
+    /// Susceptible citizens that got exposed in this hour
+    pub fn get_new_exposures(&self) -> Count {
+        self.new_exposures
+    }
+
+    /// Exposed citizens that became infectious in this hour
+    pub fn get_new_infections(&self) -> Count {
+        self.new_infections
+    }
+
+    /// Infected citizens that developed symptoms in this hour
+    pub fn get_new_symptomatic(&self) -> Count {
+        self.new_symptomatic
+    }
+
+    /// Infected citizens that recovered in this hour
+    pub fn get_new_recoveries(&self) -> Count {
+        self.new_recoveries
+    }
+
+    /// Exposures since the start of the current day, up to and including this hour
+    pub fn get_daily_new_exposures(&self) -> Count {
+        self.daily_new_exposures
+    }
+
+    pub fn get_daily_new_infections(&self) -> Count {
+        self.daily_new_infections
+    }
+
+    pub fn get_daily_new_symptomatic(&self) -> Count {
+        self.daily_new_symptomatic
+    }
+
+    pub fn get_daily_new_hospital_admissions(&self) -> Count {
+        self.daily_new_hospital_admissions
+    }
+
+    pub fn get_daily_new_recoveries(&self) -> Count {
+        self.daily_new_recoveries
+    }
+
+    pub fn get_daily_new_deaths(&self) -> Count {
+        self.daily_new_deaths
+    }
+
     pub fn get_hour(&self) -> Hour {
         self.hour
     }
@@ -90,6 +199,48 @@ impl Counts {
         self.hospitalized += count;
     }
 
+    pub fn update_in_icu(&mut self, count: Count) {
+        self.in_icu += count;
+    }
+
+    pub fn update_turned_away(&mut self, count: Count) {
+        self.turned_away += count;
+    }
+
+    pub fn update_excess_deaths(&mut self, count: Count) {
+        self.excess_deaths += count;
+    }
+
+    pub fn update_new_deaths(&mut self, count: Count) {
+        self.new_deaths += count;
+        self.daily_new_deaths += count;
+    }
+
+    pub fn update_new_hospital_admissions(&mut self, count: Count) {
+        self.new_hospital_admissions += count;
+        self.daily_new_hospital_admissions += count;
+    }
+
+    pub fn update_new_exposures(&mut self, count: Count) {
+        self.new_exposures += count;
+        self.daily_new_exposures += count;
+    }
+
+    pub fn update_new_infections(&mut self, count: Count) {
+        self.new_infections += count;
+        self.daily_new_infections += count;
+    }
+
+    pub fn update_new_symptomatic(&mut self, count: Count) {
+        self.new_symptomatic += count;
+        self.daily_new_symptomatic += count;
+    }
+
+    pub fn update_new_recoveries(&mut self, count: Count) {
+        self.new_recoveries += count;
+        self.daily_new_recoveries += count;
+    }
+
     pub fn remove_susceptible(&mut self, count: Count) { self.susceptible -= count; }
 
     pub fn remove_exposed(&mut self, count: Count) {
@@ -109,6 +260,10 @@ impl Counts {
     //     self.hospitalized -= count;
     // }
 
+    pub fn set_date(&mut self, date: Option<NaiveDate>) {
+        self.date = date;
+    }
+
     pub fn increment_hour(&mut self) {
         self.hour += 1;
     }
@@ -120,6 +275,23 @@ impl Counts {
         self.hospitalized = 0;
         self.recovered = 0;
         self.deceased = 0;
+        self.in_icu = 0;
+        self.turned_away = 0;
+        self.excess_deaths = 0;
+        self.new_deaths = 0;
+        self.new_hospital_admissions = 0;
+        self.new_exposures = 0;
+        self.new_infections = 0;
+        self.new_symptomatic = 0;
+        self.new_recoveries = 0;
+        if self.hour % constants::HOURS_IN_A_DAY == 0 {
+            self.daily_new_exposures = 0;
+            self.daily_new_infections = 0;
+            self.daily_new_symptomatic = 0;
+            self.daily_new_hospital_admissions = 0;
+            self.daily_new_recoveries = 0;
+            self.daily_new_deaths = 0;
+        }
     }
 
     pub fn total(&self) -> Count {
@@ -132,15 +304,22 @@ impl Counts {
     }
 
     pub fn log(&self) {
-        info!("S: {}, E:{}, I: {}, H: {}, R: {}, D: {}", self.get_susceptible(), self.get_exposed(),
-              self.get_infected(), self.get_hospitalized(), self.get_recovered(),
-              self.get_deceased())
+        info!("S: {}, E:{}, I: {}, H: {} (ICU: {}, turned away: {}), R: {}, D: {} (excess: {})", self.get_susceptible(), self.get_exposed(),
+              self.get_infected(), self.get_hospitalized(), self.get_in_icu(), self.get_turned_away(), self.get_recovered(),
+              self.get_deceased(), self.get_excess_deaths());
+        info!("New this hour (today) - E: {} ({}), I: {} ({}), symptomatic: {} ({}), H: {} ({}), R: {} ({}), D: {} ({})",
+              self.get_new_exposures(), self.get_daily_new_exposures(), self.get_new_infections(), self.get_daily_new_infections(),
+              self.get_new_symptomatic(), self.get_daily_new_symptomatic(), self.get_new_hospital_admissions(),
+              self.get_daily_new_hospital_admissions(), self.get_new_recoveries(), self.get_daily_new_recoveries(),
+              self.get_new_deaths(), self.get_daily_new_deaths())
     }
 }
 
 
 #[cfg(test)]
 mod tests {
+    use chrono::NaiveDate;
+
     use crate::listeners::events::counts::Counts;
 
     #[test]
@@ -245,4 +424,61 @@ mod tests {
         assert_eq!(counts.deceased, 0);
         assert_eq!(counts.hour, 1);
     }
+
+    #[test]
+    fn should_clear_new_deaths_and_hospital_admissions() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.update_new_deaths(2);
+        counts.update_new_hospital_admissions(3);
+        assert_eq!(counts.get_new_deaths(), 2);
+        assert_eq!(counts.get_new_hospital_admissions(), 3);
+
+        counts.clear();
+        assert_eq!(counts.get_new_deaths(), 0);
+        assert_eq!(counts.get_new_hospital_admissions(), 0);
+    }
+
+    #[test]
+    fn should_keep_daily_incidence_until_the_day_ends() {
+        let mut counts = Counts::new(100, 1, 0);
+        for _ in 0..23 {
+            counts.increment_hour();
+            counts.clear();
+            counts.update_new_exposures(1);
+            counts.update_new_recoveries(2);
+        }
+        assert_eq!(counts.get_hour(), 23);
+        assert_eq!(counts.get_new_exposures(), 1);
+        assert_eq!(counts.get_daily_new_exposures(), 23);
+        assert_eq!(counts.get_new_recoveries(), 2);
+        assert_eq!(counts.get_daily_new_recoveries(), 46);
+
+        counts.increment_hour();
+        counts.clear();
+        assert_eq!(counts.get_new_exposures(), 0);
+        assert_eq!(counts.get_daily_new_exposures(), 0);
+        assert_eq!(counts.get_daily_new_recoveries(), 0);
+
+        counts.update_new_infections(1);
+        counts.update_new_symptomatic(1);
+        counts.update_new_hospital_admissions(1);
+        counts.update_new_deaths(1);
+        assert_eq!(counts.get_daily_new_infections(), 1);
+        assert_eq!(counts.get_daily_new_symptomatic(), 1);
+        assert_eq!(counts.get_daily_new_hospital_admissions(), 1);
+        assert_eq!(counts.get_daily_new_deaths(), 1);
+    }
+
+    #[test]
+    fn should_write_date_next_to_hour() {
+        let mut counts = Counts::new(100, 1, 0);
+        counts.set_date(Some(NaiveDate::from_ymd(2020, 3, 6)));
+        let mut writer = csv::Writer::from_writer(vec![]);
+
+        writer.serialize(counts).unwrap();
+
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(csv.starts_with("hour,date,susceptible"));
+        assert!(csv.contains("\n0,2020-03-06,100,"));
+    }
 }
@@ -20,6 +20,7 @@
 use std::any::Any;
 
 use crate::agent::Citizen;
+use crate::allocation_map::AgentLocationMap;
 use crate::custom_types::Hour;
 use crate::geography::{Point, Grid};
 use crate::listeners::events::counts::Counts;
@@ -34,6 +35,7 @@ pub trait Listener {
     fn grid_updated(&self, _grid: &Grid) {}
     fn intervention_applied(&mut self, _at_hour: Hour, _intervention: &dyn InterventionType) {}
     fn outgoing_migrators_added(&mut self, _hr: Hour, _travellers: &[MigratorsByRegion]) {}
+    fn population_updated(&mut self, _hr: Hour, _population: &AgentLocationMap) {}
     fn as_any(&self) -> &dyn Any;
 }
 
@@ -80,6 +82,10 @@ impl Listeners {
     pub fn outgoing_migrators_added(&mut self, hr: Hour, travellers: &Vec<MigratorsByRegion>) {
         self.listeners.iter_mut().for_each(|l| l.outgoing_migrators_added(hr, travellers));
     }
+
+    pub fn population_updated(&mut self, hr: Hour, population: &AgentLocationMap) {
+        self.listeners.iter_mut().for_each(|l| l.population_updated(hr, population));
+    }
 }
 
 #[cfg(test)]
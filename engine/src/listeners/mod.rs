@@ -24,3 +24,5 @@ pub mod listener;
 pub mod events;
 pub mod travel_counter;
 pub mod intervention_reporter;
+pub mod risk_groups;
+pub mod stratified_counts;
@@ -0,0 +1,126 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::any::Any;
+
+use crate::allocation_map::AgentLocationMap;
+use crate::custom_types::{Count, Hour};
+use crate::environment;
+use crate::listeners::csv_service;
+use crate::listeners::listener::Listener;
+
+const NO_RISK_FACTOR: &str = "none";
+
+#[derive(Serialize, Debug, PartialEq)]
+struct RiskGroupCounts {
+    hour: Hour,
+    risk_group: String,
+    severe: Count,
+    deceased: Count,
+}
+
+/// Writes the severe cases and deaths of each risk factor every hour. Citizens with several risk factors count
+/// in each of their groups, and those with none in the "none" group
+pub struct RiskGroupsReporter {
+    output_file_name: String,
+    risk_groups: Vec<String>,
+    counts: Vec<RiskGroupCounts>,
+}
+
+impl RiskGroupsReporter {
+    pub fn new(output_file_name: String, risk_factors: Vec<String>) -> RiskGroupsReporter {
+        RiskGroupsReporter { output_file_name, risk_groups: risk_factors, counts: Vec::new() }
+    }
+}
+
+impl Listener for RiskGroupsReporter {
+    fn simulation_ended(&mut self) {
+        let mut output_path = environment::output_dir();
+        output_path.push(&self.output_file_name);
+        csv_service::write(&output_path, &self.counts).expect("Failed to write risk groups file");
+    }
+
+    fn population_updated(&mut self, hr: Hour, population: &AgentLocationMap) {
+        let mut severe_and_deceased: Vec<(Count, Count)> = vec![(0, 0); self.risk_groups.len() + 1];
+        let no_risk_factor = self.risk_groups.len();
+        for (_, citizen) in population.iter() {
+            let severe = citizen.state_machine.is_severe();
+            let deceased = citizen.state_machine.is_deceased();
+            if !severe && !deceased {
+                continue;
+            }
+            let groups: Vec<usize> = self.risk_groups.iter().enumerate()
+                .filter(|(_, group)| citizen.get_comorbidities().contains(group))
+                .map(|(index, _)| index)
+                .collect();
+            let groups = if groups.is_empty() { vec![no_risk_factor] } else { groups };
+            for group in groups {
+                severe_and_deceased[group].0 += severe as Count;
+                severe_and_deceased[group].1 += deceased as Count;
+            }
+        }
+
+        let names = self.risk_groups.iter().map(|group| group.as_str()).chain(std::iter::once(NO_RISK_FACTOR));
+        for (risk_group, (severe, deceased)) in names.zip(severe_and_deceased) {
+            self.counts.push(RiskGroupCounts { hour: hr, risk_group: risk_group.to_string(), severe, deceased });
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::{Citizen, WorkStatus};
+    use crate::geography::{Area, Point};
+    use crate::random_wrapper::RandomWrapper;
+    use super::*;
+
+    #[test]
+    fn should_count_severe_cases_and_deaths_by_risk_group() {
+        let mut rng = RandomWrapper::new();
+        let area = Area::new("engine1".to_string(), Point::new(0, 0), Point::new(5, 5));
+        let citizen = |comorbidities: &[&str], rng: &mut RandomWrapper| {
+            let mut citizen = Citizen::new(area.clone(), area.clone(), Point::new(0, 0), false, WorkStatus::NA, rng);
+            citizen.set_comorbidities(comorbidities.iter().map(|comorbidity| comorbidity.to_string()).collect());
+            citizen
+        };
+        let mut severe_diabetic = citizen(&["diabetes", "asthma"], &mut rng);
+        severe_diabetic.state_machine.set_severe_infected();
+        let mut deceased_diabetic = citizen(&["diabetes"], &mut rng);
+        deceased_diabetic.state_machine.state = crate::disease_state_machine::State::Deceased {};
+        let mut severe = citizen(&[], &mut rng);
+        severe.state_machine.set_severe_infected();
+        let healthy_diabetic = citizen(&["diabetes"], &mut rng);
+        let citizens = vec![severe_diabetic, deceased_diabetic, severe, healthy_diabetic];
+        let points: Vec<Point> = (0..4).map(|x| Point::new(x, 0)).collect();
+        let map = AgentLocationMap::new(10, &citizens, &points);
+
+        let mut reporter = RiskGroupsReporter::new("risk_groups.csv".to_string(), vec!["diabetes".to_string(), "hypertension".to_string()]);
+        reporter.population_updated(5, &map);
+
+        assert_eq!(reporter.counts, vec![
+            RiskGroupCounts { hour: 5, risk_group: "diabetes".to_string(), severe: 1, deceased: 1 },
+            RiskGroupCounts { hour: 5, risk_group: "hypertension".to_string(), severe: 0, deceased: 0 },
+            RiskGroupCounts { hour: 5, risk_group: "none".to_string(), severe: 1, deceased: 0 },
+        ]);
+    }
+}
@@ -0,0 +1,374 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::agent::{Citizen, Sex, WorkStatus};
+use crate::allocation_map::AgentLocationMap;
+use crate::config::age_bounds;
+use crate::constants;
+use crate::custom_types::{Count, Hour};
+use crate::environment;
+use crate::listeners::csv_service;
+use crate::listeners::listener::Listener;
+
+const UNKNOWN: &str = "unknown";
+
+/// The groups of citizens to write counts for, and how often
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct StratifiedCountsConfig {
+    strata: Vec<Stratum>,
+    #[serde(default = "default_interval")]
+    interval: ReportingInterval,
+}
+
+fn default_interval() -> ReportingInterval {
+    ReportingInterval::Hourly
+}
+
+impl StratifiedCountsConfig {
+    #[cfg(test)]
+    pub fn new(strata: Vec<Stratum>, interval: ReportingInterval) -> StratifiedCountsConfig {
+        StratifiedCountsConfig { strata, interval }
+    }
+}
+
+/// A way to split the population into groups. Age is split into bands such as "0-17", "18-64" and "65+"
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Stratum {
+    Age { bands: Vec<String> },
+    Sex,
+    WorkStatus,
+    Vaccination,
+    HomeRegion,
+}
+
+impl Stratum {
+    fn name(&self) -> &'static str {
+        match self {
+            Stratum::Age { .. } => "age",
+            Stratum::Sex => "sex",
+            Stratum::WorkStatus => "work_status",
+            Stratum::Vaccination => "vaccination",
+            Stratum::HomeRegion => "home_region",
+        }
+    }
+
+    /// The groups known up front, in the order they are written. Home regions are added as citizens are seen
+    fn groups(&self) -> Vec<String> {
+        let groups: &[&str] = match self {
+            Stratum::Age { bands } => return bands.iter().cloned().chain(std::iter::once(UNKNOWN.to_string())).collect(),
+            Stratum::Sex => &["male", "female", UNKNOWN],
+            Stratum::WorkStatus => &["normal", "essential", "hospital_staff", "student", "na"],
+            Stratum::Vaccination => &["vaccinated", "unvaccinated"],
+            Stratum::HomeRegion => &[],
+        };
+        groups.iter().map(|group| group.to_string()).collect()
+    }
+
+    fn group_of(&self, citizen: &Citizen) -> String {
+        let group = match self {
+            Stratum::Age { bands } => {
+                let band = citizen.get_age().and_then(|age| bands.iter()
+                    .find(|band| matches!(age_bounds(band), Some((youngest, oldest)) if (youngest..=oldest).contains(&age))));
+                return band.cloned().unwrap_or_else(|| UNKNOWN.to_string());
+            }
+            Stratum::Sex => match citizen.get_sex() {
+                Some(Sex::Male) => "male",
+                Some(Sex::Female) => "female",
+                None => UNKNOWN,
+            },
+            Stratum::WorkStatus => match citizen.get_work_status() {
+                WorkStatus::Normal => "normal",
+                WorkStatus::Essential => "essential",
+                WorkStatus::HospitalStaff { .. } => "hospital_staff",
+                WorkStatus::Student { .. } => "student",
+                WorkStatus::NA => "na",
+            },
+            Stratum::Vaccination => if citizen.is_vaccinated() { "vaccinated" } else { "unvaccinated" },
+            Stratum::HomeRegion => return citizen.home_location.location_id.clone(),
+        };
+        group.to_string()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub enum ReportingInterval {
+    Hourly,
+    Daily,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+struct StratumCounts {
+    hour: Hour,
+    stratum: String,
+    group: String,
+    susceptible: Count,
+    exposed: Count,
+    infected: Count,
+    hospitalized: Count,
+    recovered: Count,
+    deceased: Count,
+    new_exposures: Count,
+    new_infections: Count,
+    new_symptomatic: Count,
+    new_hospital_admissions: Count,
+    new_recoveries: Count,
+    new_deaths: Count,
+}
+
+impl StratumCounts {
+    fn new(stratum: &Stratum, group: String) -> StratumCounts {
+        StratumCounts { stratum: stratum.name().to_string(), group, ..Default::default() }
+    }
+
+    fn update(&mut self, before: &Status, after: &Status) {
+        if after.hospitalized {
+            self.hospitalized += 1;
+        } else if after.susceptible {
+            self.susceptible += 1;
+        } else if after.exposed {
+            self.exposed += 1;
+        } else if after.infected {
+            self.infected += 1;
+        } else if after.recovered {
+            self.recovered += 1;
+        } else if after.deceased {
+            self.deceased += 1;
+        }
+
+        self.new_exposures += (before.susceptible && after.exposed) as Count;
+        self.new_infections += (!before.infected && after.infected) as Count;
+        self.new_symptomatic += (!before.symptomatic && after.symptomatic) as Count;
+        self.new_hospital_admissions += (!before.hospitalized && after.hospitalized) as Count;
+        self.new_recoveries += (before.infected && after.recovered) as Count;
+        self.new_deaths += (!before.deceased && after.deceased) as Count;
+    }
+
+    fn clear_prevalence(&mut self) {
+        self.susceptible = 0;
+        self.exposed = 0;
+        self.infected = 0;
+        self.hospitalized = 0;
+        self.recovered = 0;
+        self.deceased = 0;
+    }
+
+    fn clear_incidence(&mut self) {
+        self.new_exposures = 0;
+        self.new_infections = 0;
+        self.new_symptomatic = 0;
+        self.new_hospital_admissions = 0;
+        self.new_recoveries = 0;
+        self.new_deaths = 0;
+    }
+}
+
+/// What a citizen's state counts as, kept from one hour to the next to find what happened in between
+#[derive(Debug, Default, Clone, Copy)]
+struct Status {
+    susceptible: bool,
+    exposed: bool,
+    infected: bool,
+    symptomatic: bool,
+    hospitalized: bool,
+    recovered: bool,
+    deceased: bool,
+}
+
+impl Status {
+    fn of(citizen: &Citizen) -> Status {
+        let state_machine = &citizen.state_machine;
+        Status {
+            susceptible: state_machine.is_susceptible(),
+            exposed: state_machine.is_exposed(),
+            infected: state_machine.is_infected(),
+            symptomatic: state_machine.is_symptomatic(),
+            hospitalized: state_machine.is_infected() && citizen.is_hospitalized(),
+            recovered: state_machine.is_recovered(),
+            deceased: state_machine.is_deceased(),
+        }
+    }
+}
+
+/// Writes the S/E/I/H/R/D counts of each group of each stratum, and what happened to them since the last row (new
+/// exposures, infections, symptomatic, hospital admissions, recoveries and deaths), every hour or at the last hour of
+/// every day. Each stratum splits the whole population, so a citizen counts once in each stratum
+pub struct StratifiedCountsReporter {
+    output_file_name: String,
+    strata: Vec<Stratum>,
+    interval: ReportingInterval,
+    current: Vec<Vec<StratumCounts>>,
+    statuses: HashMap<Uuid, Status>,
+    counts: Vec<StratumCounts>,
+}
+
+impl StratifiedCountsReporter {
+    pub fn new(output_file_name: String, config: StratifiedCountsConfig) -> StratifiedCountsReporter {
+        let current = config.strata.iter()
+            .map(|stratum| stratum.groups().into_iter().map(|group| StratumCounts::new(stratum, group)).collect())
+            .collect();
+        StratifiedCountsReporter { output_file_name, strata: config.strata, interval: config.interval, current,
+            statuses: HashMap::new(), counts: Vec::new() }
+    }
+
+    fn should_report(&self, hr: Hour) -> bool {
+        match self.interval {
+            ReportingInterval::Hourly => true,
+            ReportingInterval::Daily => hr % constants::HOURS_IN_A_DAY == constants::HOURS_IN_A_DAY - 1,
+        }
+    }
+}
+
+impl Listener for StratifiedCountsReporter {
+    fn simulation_ended(&mut self) {
+        let mut output_path = environment::output_dir();
+        output_path.push(&self.output_file_name);
+        csv_service::write(&output_path, &self.counts).expect("Failed to write stratified counts file");
+    }
+
+    fn population_updated(&mut self, hr: Hour, population: &AgentLocationMap) {
+        self.current.iter_mut().flatten().for_each(|group| group.clear_prevalence());
+        for (_, citizen) in population.iter() {
+            let after = Status::of(citizen);
+            let before = self.statuses.insert(citizen.id, after).unwrap_or(after);
+            for (stratum, groups) in self.strata.iter().zip(self.current.iter_mut()) {
+                let name = stratum.group_of(citizen);
+                let index = match groups.iter().position(|group| group.group == name) {
+                    Some(index) => index,
+                    None => {
+                        groups.push(StratumCounts::new(stratum, name));
+                        groups.len() - 1
+                    }
+                };
+                groups[index].update(&before, &after);
+            }
+        }
+
+        if self.should_report(hr) {
+            for group in self.current.iter_mut().flatten() {
+                self.counts.push(StratumCounts { hour: hr, ..group.clone() });
+                group.clear_incidence();
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::{Citizen, Sex, WorkStatus};
+    use crate::disease_state_machine::State;
+    use crate::geography::{Area, Point};
+    use crate::random_wrapper::RandomWrapper;
+    use super::*;
+
+    fn citizens(rng: &mut RandomWrapper) -> Vec<Citizen> {
+        let area = Area::new("engine1".to_string(), Point::new(0, 0), Point::new(5, 5));
+        let mut child = Citizen::new(area.clone(), area.clone(), Point::new(0, 0), false, WorkStatus::NA, rng);
+        child.set_age_and_sex(Some(8), Some(Sex::Female));
+        let mut adult = Citizen::new(area.clone(), area.clone(), Point::new(0, 0), false, WorkStatus::Normal, rng);
+        adult.set_age_and_sex(Some(40), Some(Sex::Male));
+        let elder = Citizen::new(area.clone(), area, Point::new(0, 0), false, WorkStatus::NA, rng);
+        vec![child, adult, elder]
+    }
+
+    fn map_of(citizens: &[Citizen]) -> AgentLocationMap {
+        let points: Vec<Point> = (0..citizens.len() as i32).map(|x| Point::new(x, 0)).collect();
+        AgentLocationMap::new(10, citizens, &points)
+    }
+
+    fn row(hour: Hour, stratum: &str, group: &str) -> StratumCounts {
+        StratumCounts { hour, stratum: stratum.to_string(), group: group.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn should_count_each_group_of_each_stratum_every_hour() {
+        let mut rng = RandomWrapper::new();
+        let mut citizens = citizens(&mut rng);
+        let strata = vec![Stratum::Age { bands: vec!["0-17".to_string(), "18-64".to_string()] }, Stratum::Sex];
+        let mut reporter = StratifiedCountsReporter::new("stratified.csv".to_string(),
+                                                         StratifiedCountsConfig::new(strata, ReportingInterval::Hourly));
+
+        reporter.population_updated(1, &map_of(&citizens));
+        citizens[1].state_machine.state = State::Exposed { at_hour: 2 };
+        reporter.population_updated(2, &map_of(&citizens));
+
+        assert_eq!(reporter.counts.len(), 12);
+        assert_eq!(reporter.counts[0], StratumCounts { susceptible: 1, ..row(1, "age", "0-17") });
+        assert_eq!(&reporter.counts[6..], &[
+            StratumCounts { susceptible: 1, ..row(2, "age", "0-17") },
+            StratumCounts { exposed: 1, new_exposures: 1, ..row(2, "age", "18-64") },
+            StratumCounts { susceptible: 1, ..row(2, "age", "unknown") },
+            StratumCounts { exposed: 1, new_exposures: 1, ..row(2, "sex", "male") },
+            StratumCounts { susceptible: 1, ..row(2, "sex", "female") },
+            StratumCounts { susceptible: 1, ..row(2, "sex", "unknown") },
+        ]);
+    }
+
+    #[test]
+    fn should_add_up_incidence_until_the_last_hour_of_the_day() {
+        let mut rng = RandomWrapper::new();
+        let mut citizens = citizens(&mut rng);
+        let strata = vec![Stratum::WorkStatus, Stratum::HomeRegion];
+        let mut reporter = StratifiedCountsReporter::new("stratified.csv".to_string(),
+                                                         StratifiedCountsConfig::new(strata, ReportingInterval::Daily));
+
+        reporter.population_updated(1, &map_of(&citizens));
+        citizens[0].state_machine.state = State::Exposed { at_hour: 2 };
+        reporter.population_updated(2, &map_of(&citizens));
+        citizens[2].state_machine.state = State::Exposed { at_hour: 3 };
+        for hr in 3..23 {
+            reporter.population_updated(hr, &map_of(&citizens));
+        }
+        assert!(reporter.counts.is_empty());
+
+        citizens[0].state_machine.state = State::Recovered {};
+        reporter.population_updated(23, &map_of(&citizens));
+        reporter.population_updated(24, &map_of(&citizens));
+
+        assert_eq!(reporter.counts, vec![
+            StratumCounts { susceptible: 1, ..row(23, "work_status", "normal") },
+            row(23, "work_status", "essential"),
+            row(23, "work_status", "hospital_staff"),
+            row(23, "work_status", "student"),
+            StratumCounts { exposed: 1, recovered: 1, new_exposures: 2, ..row(23, "work_status", "na") },
+            StratumCounts { susceptible: 1, exposed: 1, recovered: 1, new_exposures: 2, ..row(23, "home_region", "engine1") },
+        ]);
+    }
+
+    #[test]
+    fn should_read_strata_from_config() {
+        let json = r#"{"strata": [{"Age": {"bands": ["0-17", "65+"]}}, "Sex", "Vaccination"], "interval": "Daily"}"#;
+
+        let config: StratifiedCountsConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(config, StratifiedCountsConfig::new(vec![Stratum::Age { bands: vec!["0-17".to_string(), "65+".to_string()] },
+                                                            Stratum::Sex, Stratum::Vaccination], ReportingInterval::Daily));
+        let config: StratifiedCountsConfig = serde_json::from_str(r#"{"strata": ["HomeRegion"]}"#).unwrap();
+        assert_eq!(config.interval, ReportingInterval::Hourly);
+    }
+}
@@ -37,8 +37,8 @@ mod disease;
 mod random_wrapper;
 mod listeners;
 mod population;
-// mod geojson_service;
-// mod models;
+mod geojson_service;
+mod models;
 mod config;
 mod interventions;
 mod kafka_consumer;
@@ -49,6 +49,12 @@ mod disease_state_machine;
 mod travel_plan;
 mod custom_types;
 mod commute;
+mod schedule;
+mod calendar;
+mod transmission;
+mod isolation;
+mod durations;
+mod infectiousness;
 mod travel_consumer;
 
 const STANDALONE_SIM_ID: &str = "0";
@@ -20,9 +20,9 @@
 use geojson::{Feature, GeoJson};
 use std::collections::HashMap;
 
-pub const BUILDING:&str = &"building";
+pub const BUILDING:&str = "building";
 
-struct Buildings {
+pub struct Buildings {
     building_type: String,
     allowed_subtypes: Vec<String>,
     features: Vec<Feature>
@@ -39,6 +39,10 @@ impl Buildings {
         &self.building_type
     }
 
+    pub fn get_features(&self) -> &Vec<Feature>{
+        &self.features
+    }
+
     pub fn buildings_factory(geo_json: &GeoJson, buildings_type_map: HashMap<String, Vec<String>>) -> HashMap<String, Buildings>{
         let mut feature_types_map: HashMap<String, Buildings> = HashMap::new();
 
@@ -46,28 +50,32 @@ impl Buildings {
             feature_types_map.insert(key.clone(), Buildings::new(key.to_string(), value.clone()));
         }
 
-        match *geo_json {
-            GeoJson::FeatureCollection(ref ctn) => for feature in &ctn.features {
-                if let Some(ref properties) = feature.properties {
-                    let building_sub_type = properties.get(BUILDING).unwrap().as_str().unwrap();
-                    let building_type = Buildings::get_key_based_on_sub_type(building_sub_type, &buildings_type_map);
-                    let buildings_option = feature_types_map.get_mut(&building_type);
-
-                    match buildings_option {
-                        Some(x) => x.features.push(feature.clone()),
-                        None => println!("{} Key not found", building_sub_type)
+        if let GeoJson::FeatureCollection(ref ctn) = *geo_json {
+            for feature in &ctn.features {
+                let building_sub_type = feature.properties.as_ref()
+                    .and_then(|properties| properties.get(BUILDING))
+                    .and_then(|building| building.as_str());
+                let building_sub_type = match building_sub_type {
+                    Some(sub_type) => sub_type,
+                    None => {
+                        debug!("Skipping a feature without a building type");
+                        continue;
                     }
+                };
+                let building_type = Buildings::get_key_based_on_sub_type(building_sub_type, &feature_types_map);
+                match feature_types_map.get_mut(&building_type) {
+                    Some(x) => x.features.push(feature.clone()),
+                    None => debug!("Skipping unmapped building type {}", building_sub_type)
                 }
-            },
-            _ => {}
+            }
         }
 
         feature_types_map
     }
 
-    fn get_key_based_on_sub_type(building_sub_type: &str, map: &HashMap<String, Vec<String>>) -> String{
-        for (key, values) in map.iter(){
-            if values.contains(&building_sub_type.to_string()) {
+    fn get_key_based_on_sub_type(building_sub_type: &str, map: &HashMap<String, Buildings>) -> String{
+        for (key, buildings) in map.iter(){
+            if buildings.allowed_subtypes.contains(&building_sub_type.to_string()) {
                 return key.to_string();
             }
         }
@@ -107,4 +115,23 @@ mod tests{
         assert_eq!(expected_buildings.get("residential").unwrap().features.len(), 3);
         assert_eq!(expected_buildings.get("commercial").unwrap().features.len(), 2);
     }
+
+    #[test]
+    fn should_skip_features_without_a_building_type() {
+        let polygon = r#"{"type": "Polygon", "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]}"#;
+        let geo_json: GeoJson = format!(r#"{{"type": "FeatureCollection", "features": [
+            {{"type": "Feature", "properties": {{"building": "apartments"}}, "geometry": {p}}},
+            {{"type": "Feature", "properties": {{"name": "park"}}, "geometry": {p}}},
+            {{"type": "Feature", "properties": {{"building": 1}}, "geometry": {p}}},
+            {{"type": "Feature", "properties": null, "geometry": {p}}},
+            {{"type": "Feature", "properties": {{"building": "church"}}, "geometry": {p}}}
+        ]}}"#, p = polygon).parse().unwrap();
+
+        let mut buildings_type: HashMap<String, Vec<String>> = HashMap::new();
+        buildings_type.insert("residential".to_string(), vec![String::from("apartments")]);
+
+        let buildings = Buildings::buildings_factory(&geo_json, buildings_type);
+
+        assert_eq!(buildings.get("residential").unwrap().features.len(), 1);
+    }
 }
@@ -17,4 +17,4 @@
  *
  */
 
-mod buildings;
+pub mod buildings;
@@ -0,0 +1,241 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+use crate::agent::{Attributes, Sex};
+use crate::config::{age_bounds, CsvPopulation};
+use crate::constants;
+use crate::population::PopulationRecord;
+
+const FIELDS: [&str; 9] = ["age", "sex", "working", "pub_transport", "household", "workplace", "ward", "occupation", "comorbidities"];
+
+/// A row of the population file that could not be read
+#[derive(Debug, PartialEq)]
+pub struct RowError {
+    line: u64,
+    column: Option<String>,
+    message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// Reads the citizens of a population file through its columns. Invalid rows are skipped with a warning if the
+/// config allows it, and otherwise fail the run listing them
+pub fn read(csv_pop: &CsvPopulation) -> Vec<PopulationRecord> {
+    let file = File::open(&csv_pop.file)
+        .unwrap_or_else(|e| panic!("Could not read population file {}: {}", csv_pop.file, e));
+    let (records, errors) = read_records(csv::Reader::from_reader(file), &csv_pop.cols.mapping(), &csv_pop.file);
+
+    if !errors.is_empty() {
+        if csv_pop.skip_invalid_rows {
+            errors.iter().for_each(|error| warn!("Skipping {} of population file {}", error, csv_pop.file));
+        } else {
+            let reported: Vec<String> = errors.iter().take(constants::MAX_REPORTED_ROW_ERRORS).map(|error| error.to_string()).collect();
+            panic!("Population file {} has {} invalid rows:\n{}", csv_pop.file, errors.len(), reported.join("\n"));
+        }
+    }
+    records
+}
+
+fn read_records<R: Read>(mut reader: csv::Reader<R>, mapping: &HashMap<String, String>, file: &str) -> (Vec<PopulationRecord>, Vec<RowError>) {
+    let headers = reader.headers()
+        .unwrap_or_else(|e| panic!("Could not read the header of population file {}: {}", file, e))
+        .clone();
+    let columns: HashMap<&str, (usize, &str)> = mapping.iter().map(|(field, column)| {
+        let field = FIELDS.iter().find(|known| *known == field)
+            .unwrap_or_else(|| panic!("Unknown population field {}, expected one of {:?}", field, FIELDS));
+        let index = headers.iter().position(|header| header.trim() == column)
+            .unwrap_or_else(|| panic!("Population file {} has no column {} for {}", file, column, field));
+        (*field, (index, column.as_str()))
+    }).collect();
+
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    for result in reader.records() {
+        let parsed = result
+            .map_err(|e| RowError { line: e.position().map(|position| position.line()).unwrap_or(0), column: None, message: e.to_string() })
+            .and_then(|row| parse_row(&row, &columns));
+        match parsed {
+            Ok(record) => records.push(record),
+            Err(error) => errors.push(error),
+        }
+    }
+    (records, errors)
+}
+
+fn parse_row(row: &csv::StringRecord, columns: &HashMap<&str, (usize, &str)>) -> Result<PopulationRecord, RowError> {
+    let line = row.position().map(|position| position.line()).unwrap_or(0);
+    let value = |field: &str| columns.get(field)
+        .map(|(index, column)| (row.get(*index).unwrap_or("").trim(), *column));
+    let error = |column: &str, message: String| RowError { line, column: Some(column.to_string()), message };
+    let text = |field: &str| value(field).map(|(value, _)| value.to_string()).filter(|value| !value.is_empty());
+
+    let age = match value("age") {
+        Some((age, column)) => parse_age(age).map_err(|message| error(column, message))?,
+        None => None,
+    };
+    let sex = match value("sex") {
+        Some((sex, column)) => parse_sex(sex).map_err(|message| error(column, message))?,
+        None => None,
+    };
+    let working = match value("working") {
+        Some((working, column)) => parse_bool(working).map_err(|message| error(column, message))?,
+        None => false,
+    };
+    let pub_transport = match value("pub_transport") {
+        Some((pub_transport, column)) => parse_bool(pub_transport).map_err(|message| error(column, message))?,
+        None => false,
+    };
+    let comorbidities = text("comorbidities")
+        .map(|comorbidities| comorbidities.split(&[';', '|'][..])
+            .map(|comorbidity| comorbidity.trim().to_string())
+            .filter(|comorbidity| !comorbidity.is_empty())
+            .collect())
+        .unwrap_or_default();
+
+    Ok(PopulationRecord {
+        age,
+        sex,
+        working,
+        pub_transport,
+        attributes: Attributes {
+            household: text("household"),
+            workplace: text("workplace"),
+            ward: text("ward"),
+            occupation: text("occupation"),
+            comorbidities,
+        },
+    })
+}
+
+/// An age group such as "30-34" or "80+", or an age, which may be fractional
+fn parse_age(value: &str) -> Result<Option<String>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if age_bounds(value).is_some() {
+        return Ok(Some(value.to_string()));
+    }
+    match value.parse::<f64>() {
+        Ok(age) if age >= 0.0 => Ok(Some((age.floor() as u32).to_string())),
+        _ => Err(format!("{:?} is not an age or an age group such as 30-34", value)),
+    }
+}
+
+fn parse_sex(value: &str) -> Result<Option<Sex>, String> {
+    match value.to_lowercase().as_str() {
+        "" => Ok(None),
+        "m" | "male" => Ok(Some(Sex::Male)),
+        "f" | "female" => Ok(Some(Sex::Female)),
+        _ => Err(format!("{:?} is not a sex, expected Male or Female", value)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Ok(true),
+        "false" | "f" | "no" | "n" | "0" => Ok(false),
+        _ => Err(format!("{:?} is not a boolean, expected True or False", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_str(csv: &str, mapping: &[(&str, &str)]) -> (Vec<PopulationRecord>, Vec<RowError>) {
+        let mapping = mapping.iter().map(|(field, column)| (field.to_string(), column.to_string())).collect();
+        read_records(csv::Reader::from_reader(csv.as_bytes()), &mapping, "test.csv")
+    }
+
+    #[test]
+    fn should_read_fields_from_mapped_columns() {
+        let csv = "id,AGE,Gender,employed,bus,hh,diseases\n1,34,F,yes,0,h1,diabetes; hypertension\n2,80+,male,False,True,h1,\n";
+
+        let (records, errors) = read_str(csv, &[("age", "AGE"), ("sex", "Gender"), ("working", "employed"),
+            ("pub_transport", "bus"), ("household", "hh"), ("comorbidities", "diseases")]);
+
+        assert!(errors.is_empty());
+        assert_eq!(records[0], PopulationRecord {
+            age: Some("34".to_string()),
+            sex: Some(Sex::Female),
+            working: true,
+            pub_transport: false,
+            attributes: Attributes {
+                household: Some("h1".to_string()),
+                comorbidities: vec!["diabetes".to_string(), "hypertension".to_string()],
+                ..Attributes::default()
+            },
+        });
+        assert_eq!(records[1].age, Some("80+".to_string()));
+        assert_eq!(records[1].sex, Some(Sex::Male));
+        assert!(!records[1].working && records[1].pub_transport);
+        assert!(records[1].attributes.comorbidities.is_empty());
+    }
+
+    #[test]
+    fn should_default_fields_without_columns() {
+        let (records, _) = read_str("ind,age\n1,42.7\n", &[("age", "age")]);
+
+        assert_eq!(records, vec![PopulationRecord {
+            age: Some("42".to_string()),
+            sex: None,
+            working: false,
+            pub_transport: false,
+            attributes: Attributes::default(),
+        }]);
+    }
+
+    #[test]
+    fn should_report_invalid_rows() {
+        let csv = "age,working\n30-34,True\nold,True\n20,maybe\n40\n";
+
+        let (records, errors) = read_str(csv, &[("age", "age"), ("working", "working")]);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].to_string(), "line 3, column age: \"old\" is not an age or an age group such as 30-34");
+        assert_eq!(errors[1].to_string(), "line 4, column working: \"maybe\" is not a boolean, expected True or False");
+        assert_eq!(errors[2].line, 5);
+        assert_eq!(errors[2].column, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Population file test.csv has no column AGE for age")]
+    fn should_panic_on_missing_column() {
+        read_str("age,working\n30,True\n", &[("age", "AGE")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown population field height")]
+    fn should_panic_on_unknown_field() {
+        read_str("age,height\n30,180\n", &[("height", "height")]);
+    }
+}
@@ -17,6 +17,17 @@
  *
  */
 
-#[allow(dead_code)]
-//TODO: Remove once the application starts generating synthetic population - Jayanta
-mod synthetic_population;
+use crate::agent::{Attributes, Sex};
+
+pub mod csv_population;
+pub mod synthetic_population;
+
+/// A citizen as read from a population file, or generated for a synthetic population
+#[derive(Debug, PartialEq)]
+pub struct PopulationRecord {
+    pub age: Option<String>,
+    pub sex: Option<Sex>,
+    pub working: bool,
+    pub pub_transport: bool,
+    pub attributes: Attributes,
+}
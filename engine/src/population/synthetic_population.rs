@@ -16,80 +16,137 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  *
  */
-
-use ndarray::{Array2, Axis, Array1};
-
-pub type Matrix = Array2<f64>;
-
-pub fn ipfn(margins: &Array1<Array1<f64>>) -> Matrix {
-    let row_count = margins[0].len();
-    let column_count = margins[1].len();
-    let seed_matrix: Matrix = Array2::ones((row_count, column_count));
-    //TODO: repeat the adjustment until difference between resultant matrices is within acceptable threshold - Jayanta
-    let m1 = adjustment(&seed_matrix, margins);
-    adjustment(&m1, margins)
-}
-
-fn adjustment(current_matrix: &Matrix, margins: &Array1<Array1<f64>>) -> Matrix {
-    let totals = compute_totals(current_matrix);
-    let row_adjusted_matrix =
-        adjust(current_matrix, margins, &totals, true);
-    let new_totals = compute_totals(&row_adjusted_matrix);
-    adjust(&row_adjusted_matrix, margins, &new_totals, false)
-
-}
-
-fn adjust(current_matrix: &Matrix, margins: &Array1<Array1<f64>>, totals: &(Array1<f64>, Array1<f64>), is_row_adjustment: bool) -> Matrix {
-    if is_row_adjustment {
-        let mut row_adjusted_matrix: Matrix = Matrix::zeros(current_matrix.dim());
-        for ((x, y), value) in current_matrix.indexed_iter() {
-            let row_total = &totals.0;
-            row_adjusted_matrix[[x, y]] = value *
-                margins.get(0).unwrap()[x] / row_total.get(x).unwrap();
+use ndarray::{Array1, ArrayD, IxDyn};
+use rand::seq::SliceRandom;
+
+use crate::agent::{Attributes, Sex};
+use crate::config::SyntheticPopulation;
+use crate::population::PopulationRecord;
+use crate::random_wrapper::RandomWrapper;
+
+pub type Table = ArrayD<f64>;
+
+const SEXES: [Sex; 2] = [Sex::Male, Sex::Female];
+
+/// Fits the seed table to the margins, one for each of its dimensions, by iterative proportional fitting. Stops
+/// once every margin is met within the tolerance, or after the maximum number of iterations
+pub fn ipfn(seed: &Table, margins: &[Array1<f64>], tolerance: f64, max_iterations: u32) -> Table {
+    assert_eq!(seed.ndim(), margins.len(), "Need a margin for every dimension of the seed");
+    let mut table = seed.clone();
+    for _ in 0..max_iterations {
+        for (axis, margin) in margins.iter().enumerate() {
+            let totals = totals_along(&table, axis);
+            for (index, value) in table.indexed_iter_mut() {
+                let total = totals[index[axis]];
+                *value = if total > 0.0 { *value * margin[index[axis]] / total } else { 0.0 };
+            }
+        }
+        if largest_difference(&table, margins) <= tolerance {
+            break;
         }
-        return row_adjusted_matrix;
     }
+    table
+}
 
-    let mut column_adjusted_matrix: Matrix = Matrix::zeros(current_matrix.dim());
-    for ((x, y), value) in current_matrix.indexed_iter() {
-        let column_total = &totals.1;
-        column_adjusted_matrix[[x, y]] = value *
-            margins.get(1).unwrap()[y] / column_total.get(y).unwrap();
+fn totals_along(table: &Table, axis: usize) -> Array1<f64> {
+    let mut totals = Array1::zeros(table.shape()[axis]);
+    for (index, value) in table.indexed_iter() {
+        totals[index[axis]] += value;
     }
+    totals
+}
 
-    column_adjusted_matrix
+fn largest_difference(table: &Table, margins: &[Array1<f64>]) -> f64 {
+    margins.iter().enumerate()
+        .flat_map(|(axis, margin)| (&totals_along(table, axis) - margin).mapv(f64::abs).to_vec())
+        .fold(0.0, f64::max)
 }
 
-fn column_adjust(current: &mut Matrix, last_iteration: &Matrix, row_count: usize, column_count: usize) {
-    for ((x, y), _old_value) in last_iteration.indexed_iter() {
-        if x != row_count && y != column_count {
-            current[[x, y]] =
-                (last_iteration[[x, column_count]] *
-                    last_iteration[[row_count, y]]) / current[[row_count, y]];
-        } else {
-            current[[x, y]] = last_iteration[[x, y]];
-        }
+/// Rounds the table to whole people adding up to the total. The people left over after rounding down go to the
+/// cells with the largest remainders
+pub fn integerize(table: &Table, total: u32) -> ArrayD<u32> {
+    let sum = table.sum();
+    let scaled = if sum > 0.0 { table.mapv(|value| value * total as f64 / sum) } else { table.clone() };
+    let mut counts = scaled.mapv(|value| value.floor() as u32);
+    let left_over = total.saturating_sub(counts.sum());
+
+    let mut remainders: Vec<(IxDyn, f64)> = scaled.indexed_iter()
+        .map(|(index, value)| (index, value - value.floor()))
+        .collect();
+    remainders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    for (index, _remainder) in remainders.into_iter().take(left_over as usize) {
+        counts[index] += 1;
     }
+    counts
 }
 
-fn compute_totals(matrix: &Matrix) -> (Array1<f64>, Array1<f64>) {
-    (matrix.sum_axis(Axis(1)), matrix.sum_axis(Axis(0)))
+/// Citizens matching the age, sex, working and public transport margins of the population. The percentages given
+/// for an age group make up the seed, so that they shape the fitted table beyond the population-wide margins
+pub fn generate(population: &SyntheticPopulation, rng: &mut RandomWrapper) -> Vec<PopulationRecord> {
+    let age_groups = &population.age_distribution;
+    if age_groups.is_empty() {
+        panic!("A synthetic population needs an age_distribution");
+    }
+    let total = population.number_of_agents as f64;
+    let share_sum: f64 = age_groups.iter().map(|group| group.share).sum();
+    let binary = |percentage: f64| Array1::from(vec![percentage * total, (1.0 - percentage) * total]);
+    let margins = vec![
+        age_groups.iter().map(|group| group.share / share_sum * total).collect::<Array1<f64>>(),
+        binary(population.male_percentage),
+        binary(population.working_percentage),
+        binary(population.public_transport_percentage),
+    ];
+
+    let seed = Table::from_shape_fn(IxDyn(&[age_groups.len(), 2, 2, 2]), |index| {
+        let group = &age_groups[index[0]];
+        let within_group = |percentage: Option<f64>, category: usize| match percentage {
+            Some(percentage) => if category == 0 { percentage } else { 1.0 - percentage },
+            None => 1.0,
+        };
+        within_group(group.male_percentage, index[1]) * within_group(group.working_percentage, index[2])
+            * within_group(group.public_transport_percentage, index[3])
+    });
+
+    let table = ipfn(&seed, &margins, population.tolerance, population.max_iterations);
+    let counts = integerize(&table, population.number_of_agents);
+
+    let mut people: Vec<PopulationRecord> = Vec::with_capacity(population.number_of_agents as usize);
+    for (index, count) in counts.indexed_iter() {
+        for _ in 0..*count {
+            people.push(PopulationRecord {
+                age: Some(age_groups[index[0]].age.clone()),
+                sex: Some(SEXES[index[1]]),
+                working: index[2] == 0,
+                pub_transport: index[3] == 0,
+                attributes: Attributes::default(),
+            });
+        }
+    }
+    people.shuffle(rng.get());
+    people
 }
 
 #[cfg(test)]
 mod tests {
     use ndarray::arr1;
-    use crate::population::synthetic_population::ipfn;
+    use crate::config::AgeGroup;
+    use super::*;
+
+    fn seed(shape: &[usize]) -> Table {
+        Table::ones(IxDyn(shape))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} is not {}", actual, expected);
+    }
 
     #[test]
     fn should_generate_population_for_square_matrix() {
         let row_margin = arr1(&[5.0, 15.0, 8.0]);
         let column_margin = arr1(&[11.0, 8.0, 9.0]);
 
-        let margins = arr1(&[row_margin, column_margin]);
-
-        let citizen_distribution = ipfn(&margins);
-        assert_eq!(citizen_distribution[[0, 0]], 1.9642857142857146);
+        let citizen_distribution = ipfn(&seed(&[3, 3]), &[row_margin, column_margin], 1e-6, 100);
+        assert_close(citizen_distribution[[0, 0]], 1.9642857142857146);
     }
 
     #[test]
@@ -97,9 +154,60 @@ mod tests {
         let row_margin = arr1(&[45.0, 85.0, 45.0]);
         let column_margin = arr1(&[68.0, 54.0, 53.0]);
 
-        let margins = arr1(&[row_margin, column_margin]);
-        let citizen_distribution = ipfn(&margins);
+        let citizen_distribution = ipfn(&seed(&[3, 3]), &[row_margin, column_margin], 1e-6, 100);
+        assert_close(citizen_distribution[[0, 0]], 17.485714285714288);
+    }
+
+    #[test]
+    fn should_iterate_until_margins_are_met() {
+        let mut seed = seed(&[2, 2, 2]);
+        seed[[0, 0, 0]] = 8.0;
+        seed[[1, 1, 0]] = 0.5;
+        let margins = [arr1(&[30.0, 70.0]), arr1(&[60.0, 40.0]), arr1(&[25.0, 75.0])];
+
+        let table = ipfn(&seed, &margins, 1e-6, 1000);
+
+        for (axis, margin) in margins.iter().enumerate() {
+            let totals = totals_along(&table, axis);
+            margin.iter().zip(totals.iter()).for_each(|(expected, actual)| assert_close(*actual, *expected));
+        }
+    }
+
+    #[test]
+    fn should_integerize_to_total_by_largest_remainders() {
+        let table = Table::from_shape_vec(IxDyn(&[2, 2]), vec![1.5, 2.25, 3.6, 2.65]).unwrap();
 
-        assert_eq!(citizen_distribution[[0, 0]], 17.485714285714288);
+        let counts = integerize(&table, 10);
+
+        assert_eq!(counts.sum(), 10);
+        assert_eq!(counts.into_raw_vec(), vec![1, 2, 4, 3]);
+    }
+
+    #[test]
+    fn should_generate_citizens_matching_margins() {
+        let population = SyntheticPopulation {
+            number_of_agents: 1000,
+            age_distribution: vec![
+                AgeGroup { working_percentage: Some(0.0), ..AgeGroup::new("0-14", 0.3) },
+                AgeGroup::new("15-59", 0.6),
+                AgeGroup { working_percentage: Some(0.0), ..AgeGroup::new("60+", 0.1) },
+            ],
+            male_percentage: 0.5,
+            working_percentage: 0.4,
+            public_transport_percentage: 0.2,
+            tolerance: 1e-6,
+            max_iterations: 1000,
+        };
+
+        let people = generate(&population, &mut RandomWrapper::new());
+
+        let count = |predicate: &dyn Fn(&PopulationRecord) -> bool| people.iter().filter(|person| predicate(person)).count();
+        let adults = Some("15-59".to_string());
+        assert_eq!(people.len(), 1000);
+        assert_eq!(count(&|person| person.age == Some("0-14".to_string())), 300);
+        assert_eq!(count(&|person| person.sex == Some(Sex::Male)), 500);
+        assert_eq!(count(&|person| person.working), 400);
+        assert_eq!(count(&|person| person.pub_transport), 200);
+        assert_eq!(count(&|person| person.working && person.age != adults), 0);
     }
 }
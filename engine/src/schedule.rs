@@ -0,0 +1,309 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::constants;
+use crate::custom_types::Hour;
+
+/// Where a citizen goes at the start of a block of the schedule. For the rest of the block the citizen moves
+/// around there
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub enum Activity {
+    /// Stays at home without moving
+    Sleep,
+    Home,
+    /// The transport area for citizens who use public transport
+    Transport,
+    Work,
+    /// Closed schools are skipped
+    School,
+    /// The housing area around the citizen's home
+    Neighbourhood,
+    /// Hospital staff start their shift
+    Hospital,
+    /// Isolated where they are, as hospital staff are between shifts
+    OffShift,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct ScheduleBlock {
+    pub from: Hour,
+    pub to: Hour,
+    pub activity: Activity,
+}
+
+impl ScheduleBlock {
+    pub fn new(from: Hour, to: Hour, activity: Activity) -> ScheduleBlock {
+        ScheduleBlock { from, to, activity }
+    }
+}
+
+/// The blocks of a day, by hour of the day. The start and the end of the day are kept for the disease, so blocks
+/// over them have no effect, and in hours without a block the citizen moves around where they are
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Schedule(Vec<ScheduleBlock>);
+
+impl Schedule {
+    pub fn new(blocks: Vec<ScheduleBlock>) -> Schedule {
+        Schedule(blocks)
+    }
+
+    /// The activity at an hour of the day, and whether the hour starts its block
+    pub fn activity_at(&self, hour_of_day: Hour) -> Option<(Activity, bool)> {
+        self.0.iter()
+            .find(|block| block.from <= hour_of_day && hour_of_day <= block.to)
+            .map(|block| (block.activity, block.from == hour_of_day))
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Segment {
+    Worker,
+    EssentialWorker,
+    Student,
+    Elderly,
+    NonWorker,
+    HospitalStaff,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Schedules {
+    #[serde(default = "default_worker_schedule")]
+    pub worker: Schedule,
+    #[serde(default = "default_worker_schedule")]
+    pub essential_worker: Schedule,
+    #[serde(default = "default_student_schedule")]
+    pub student: Schedule,
+    #[serde(default = "default_non_worker_schedule")]
+    pub elderly: Schedule,
+    #[serde(default = "default_non_worker_schedule")]
+    pub non_worker: Schedule,
+    #[serde(default = "default_hospital_staff_schedule")]
+    pub hospital_staff: Schedule,
+    /// Non working citizens from this age on follow the elderly schedule
+    #[serde(default = "default_elderly_age")]
+    pub elderly_age: u32,
+    #[serde(default)]
+    pub day_off: DayOffSchedules,
+}
+
+impl Schedules {
+    pub fn of(&self, segment: Segment, day_off: bool) -> &Schedule {
+        if day_off {
+            return self.day_off_schedule_of(segment);
+        }
+        match segment {
+            Segment::Worker => &self.worker,
+            Segment::EssentialWorker => &self.essential_worker,
+            Segment::Student => &self.student,
+            Segment::Elderly => &self.elderly,
+            Segment::NonWorker => &self.non_worker,
+            Segment::HospitalStaff => &self.hospital_staff,
+        }
+    }
+
+    /// Commuters leave at the start of the first transport block of the worker schedule and come back at the
+    /// start of the last one, or at the default hours when it doesn't have two of them
+    pub fn commute_hours(&self) -> CommuteHours {
+        let transport: Vec<Hour> = self.worker.0.iter()
+            .filter(|block| block.activity == Activity::Transport)
+            .map(|block| block.from)
+            .collect();
+        match (transport.first(), transport.last()) {
+            (Some(to_work), Some(back_home)) if to_work < back_home => CommuteHours { to_work: *to_work, back_home: *back_home },
+            _ => CommuteHours::default(),
+        }
+    }
+
+    fn day_off_schedule_of(&self, segment: Segment) -> &Schedule {
+        let configured = match segment {
+            Segment::Worker => &self.day_off.worker,
+            Segment::EssentialWorker => &self.day_off.essential_worker,
+            Segment::Student => &self.day_off.student,
+            Segment::Elderly => &self.day_off.elderly,
+            Segment::NonWorker => &self.day_off.non_worker,
+            Segment::HospitalStaff => &self.day_off.hospital_staff,
+        };
+        match (configured, segment) {
+            (Some(schedule), _) => schedule,
+            (None, Segment::Worker) | (None, Segment::Student) => &self.non_worker,
+            (None, _) => self.of(segment, false),
+        }
+    }
+}
+
+/// The hours of the day commuters travel to their work in another region and back home
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CommuteHours {
+    pub to_work: Hour,
+    pub back_home: Hour,
+}
+
+impl CommuteHours {
+    pub fn includes(&self, hour_of_day: Hour) -> bool {
+        hour_of_day == self.to_work || hour_of_day == self.back_home
+    }
+}
+
+impl Default for CommuteHours {
+    fn default() -> Self {
+        CommuteHours { to_work: constants::ROUTINE_TRAVEL_START_TIME, back_home: constants::ROUTINE_TRAVEL_END_TIME }
+    }
+}
+
+/// Schedules on weekends and holidays. Workers and students left out spend the day as non workers do on a
+/// weekday, and the other segments as on a weekday
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct DayOffSchedules {
+    #[serde(default)]
+    pub worker: Option<Schedule>,
+    #[serde(default)]
+    pub essential_worker: Option<Schedule>,
+    #[serde(default)]
+    pub student: Option<Schedule>,
+    #[serde(default)]
+    pub elderly: Option<Schedule>,
+    #[serde(default)]
+    pub non_worker: Option<Schedule>,
+    #[serde(default)]
+    pub hospital_staff: Option<Schedule>,
+}
+
+impl Default for Schedules {
+    fn default() -> Self {
+        Schedules {
+            worker: default_worker_schedule(),
+            essential_worker: default_worker_schedule(),
+            student: default_student_schedule(),
+            elderly: default_non_worker_schedule(),
+            non_worker: default_non_worker_schedule(),
+            hospital_staff: default_hospital_staff_schedule(),
+            elderly_age: default_elderly_age(),
+            day_off: DayOffSchedules::default(),
+        }
+    }
+}
+
+fn sleep() -> ScheduleBlock {
+    ScheduleBlock::new(constants::SLEEP_START_TIME, constants::SLEEP_END_TIME, Activity::Sleep)
+}
+
+fn default_worker_schedule() -> Schedule {
+    Schedule::new(vec![
+        sleep(),
+        ScheduleBlock::new(constants::ROUTINE_TRAVEL_START_TIME, constants::ROUTINE_TRAVEL_START_TIME, Activity::Transport),
+        ScheduleBlock::new(constants::ROUTINE_WORK_TIME, constants::ROUTINE_WORK_END_TIME - 1, Activity::Work),
+        ScheduleBlock::new(constants::ROUTINE_WORK_END_TIME, constants::ROUTINE_WORK_END_TIME, Activity::Home),
+        ScheduleBlock::new(constants::ROUTINE_TRAVEL_END_TIME, constants::ROUTINE_END_TIME - 1, Activity::Transport),
+    ])
+}
+
+fn default_student_schedule() -> Schedule {
+    Schedule::new(vec![
+        sleep(),
+        ScheduleBlock::new(constants::SCHOOL_START_TIME, constants::SCHOOL_END_TIME - 1, Activity::School),
+        ScheduleBlock::new(constants::SCHOOL_END_TIME, constants::ROUTINE_END_TIME - 1, Activity::Home),
+    ])
+}
+
+fn default_non_worker_schedule() -> Schedule {
+    Schedule::new(vec![
+        sleep(),
+        ScheduleBlock::new(constants::ROUTINE_WORK_TIME, constants::NON_WORKING_TRAVEL_END_TIME - 1, Activity::Neighbourhood),
+        ScheduleBlock::new(constants::NON_WORKING_TRAVEL_END_TIME, constants::ROUTINE_END_TIME - 1, Activity::Home),
+    ])
+}
+
+fn default_hospital_staff_schedule() -> Schedule {
+    Schedule::new(vec![
+        sleep(),
+        ScheduleBlock::new(constants::ROUTINE_WORK_TIME, constants::ROUTINE_WORK_END_TIME - 1, Activity::Hospital),
+        ScheduleBlock::new(constants::ROUTINE_WORK_END_TIME, constants::ROUTINE_END_TIME - 1, Activity::OffShift),
+    ])
+}
+
+fn default_elderly_age() -> u32 {
+    constants::ELDERLY_AGE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_activity_and_start_of_its_block() {
+        let schedule = default_worker_schedule();
+
+        assert_eq!(schedule.activity_at(3), Some((Activity::Sleep, false)));
+        assert_eq!(schedule.activity_at(7), Some((Activity::Transport, true)));
+        assert_eq!(schedule.activity_at(8), Some((Activity::Work, true)));
+        assert_eq!(schedule.activity_at(15), Some((Activity::Work, false)));
+        assert_eq!(schedule.activity_at(16), Some((Activity::Home, true)));
+        assert_eq!(schedule.activity_at(20), Some((Activity::Transport, false)));
+    }
+
+    #[test]
+    fn should_not_find_activity_for_hours_without_a_block() {
+        let schedule = default_student_schedule();
+
+        assert_eq!(schedule.activity_at(7), None);
+        assert_eq!(schedule.activity_at(23), None);
+    }
+
+    #[test]
+    fn should_take_commute_hours_from_worker_schedule() {
+        let json = r#"{"worker": [{"from": 1, "to": 5, "activity": "Sleep"}, {"from": 6, "to": 6, "activity": "Transport"},
+            {"from": 7, "to": 14, "activity": "Work"}, {"from": 15, "to": 16, "activity": "Transport"}]}"#;
+        let schedules: Schedules = serde_json::from_str(json).unwrap();
+
+        assert_eq!(schedules.commute_hours(), CommuteHours { to_work: 6, back_home: 15 });
+        assert_eq!(Schedules::default().commute_hours(), CommuteHours { to_work: 7, back_home: 17 });
+
+        let without_transport = r#"{"worker": [{"from": 6, "to": 22, "activity": "Work"}]}"#;
+        let schedules: Schedules = serde_json::from_str(without_transport).unwrap();
+        assert_eq!(schedules.commute_hours(), CommuteHours::default());
+    }
+
+    #[test]
+    fn should_read_schedules_with_defaults() {
+        let json = r#"{"worker": [{"from": 1, "to": 5, "activity": "Sleep"}, {"from": 6, "to": 22, "activity": "Work"}],
+            "elderly_age": 65}"#;
+
+        let schedules: Schedules = serde_json::from_str(json).unwrap();
+
+        assert_eq!(schedules.of(Segment::Worker, false), &Schedule::new(vec![ScheduleBlock::new(1, 5, Activity::Sleep),
+                                                                      ScheduleBlock::new(6, 22, Activity::Work)]));
+        assert_eq!(schedules.of(Segment::EssentialWorker, false), &default_worker_schedule());
+        assert_eq!(schedules.of(Segment::Elderly, false), &default_non_worker_schedule());
+        assert_eq!(schedules.elderly_age, 65);
+    }
+
+    #[test]
+    fn should_keep_essential_services_and_send_workers_and_students_home_on_days_off() {
+        let json = r#"{"day_off": {"elderly": [{"from": 1, "to": 22, "activity": "Home"}]}}"#;
+
+        let schedules: Schedules = serde_json::from_str(json).unwrap();
+
+        assert_eq!(schedules.of(Segment::Worker, true), &default_non_worker_schedule());
+        assert_eq!(schedules.of(Segment::Student, true), &default_non_worker_schedule());
+        assert_eq!(schedules.of(Segment::EssentialWorker, true), &default_worker_schedule());
+        assert_eq!(schedules.of(Segment::HospitalStaff, true), &default_hospital_staff_schedule());
+        assert_eq!(schedules.of(Segment::Elderly, true), &Schedule::new(vec![ScheduleBlock::new(1, 22, Activity::Home)]));
+        assert_eq!(schedules.of(Segment::Elderly, false), &default_non_worker_schedule());
+    }
+}
@@ -22,6 +22,7 @@ use rdkafka::consumer::{Consumer, StreamConsumer};
 use rdkafka::error::KafkaResult;
 use rdkafka::message::BorrowedMessage;
 use crate::custom_types::Hour;
+use crate::interventions::InterventionCommand;
 
 use crate::environment;
 
@@ -70,10 +71,12 @@ fn parse_tick(message: &str) -> Tick {
     serde_json::from_str(message).expect("Could not parse tick")
 }
 
-#[derive(Debug, Copy, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Tick {
     hour: Hour,
     terminate: bool,
+    #[serde(default)]
+    interventions: Vec<InterventionCommand>,
 }
 
 impl Tick {
@@ -84,6 +87,10 @@ impl Tick {
     pub fn terminate(&self) -> bool {
         self.terminate
     }
+
+    pub fn interventions(&self) -> &[InterventionCommand] {
+        &self.interventions
+    }
 }
 
 #[cfg(test)]
@@ -97,7 +104,24 @@ mod tests {
             "hour": 1,
             "terminate": false
         }"#;
-        let expected = Tick { hour: 1, terminate: false };
+        let expected = Tick { hour: 1, terminate: false, interventions: vec![] };
+        assert_eq!(expected, parse_tick(json));
+    }
+
+    #[test]
+    fn should_parse_tick_with_interventions() {
+        let json = r#"
+        {
+            "hour": 48,
+            "terminate": false,
+            "interventions": [{"Lockdown": {"essential_workers_population": 0.1}}, {"LiftLockdown": {}}]
+        }"#;
+        let expected = Tick {
+            hour: 48,
+            terminate: false,
+            interventions: vec![InterventionCommand::Lockdown { essential_workers_population: 0.1 },
+                                InterventionCommand::LiftLockdown {}],
+        };
         assert_eq!(expected, parse_tick(json));
     }
 }
@@ -0,0 +1,183 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::custom_types::{CoOrdinate, Hour, Percentage, validate_non_negative};
+use validator::{Validate, ValidationError};
+
+/// Where a susceptible citizen is exposed, for the transmission multipliers of each setting
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Setting {
+    Home,
+    Work,
+    School,
+    Transport,
+    Hospital,
+    /// The neighbourhood, or anywhere else
+    Community,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Validate)]
+#[serde(default)]
+pub struct SettingMultipliers {
+    #[validate(custom = "validate_non_negative")]
+    pub home: f64,
+    #[validate(custom = "validate_non_negative")]
+    pub work: f64,
+    #[validate(custom = "validate_non_negative")]
+    pub school: f64,
+    #[validate(custom = "validate_non_negative")]
+    pub transport: f64,
+    #[validate(custom = "validate_non_negative")]
+    pub hospital: f64,
+    #[validate(custom = "validate_non_negative")]
+    pub community: f64,
+}
+
+impl Default for SettingMultipliers {
+    fn default() -> Self {
+        SettingMultipliers { home: 1.0, work: 1.0, school: 1.0, transport: 1.0, hospital: 1.0, community: 1.0 }
+    }
+}
+
+impl SettingMultipliers {
+    pub fn of(&self, setting: Setting) -> f64 {
+        match setting {
+            Setting::Home => self.home,
+            Setting::Work => self.work,
+            Setting::School => self.school,
+            Setting::Transport => self.transport,
+            Setting::Hospital => self.hospital,
+            Setting::Community => self.community,
+        }
+    }
+}
+
+/// How infection spreads from infectious citizens within `radius` cells. The transmission rate of each of them
+/// decays by `exp(-distance_decay * (distance - 1))`, is scaled by the multiplier of the setting, and grows by
+/// `build_up` for every further consecutive hour in contact with infectious citizens. The defaults are the
+/// adjacent cells alone, with every hour independent of the ones before
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Validate)]
+#[serde(default)]
+pub struct TransmissionKernel {
+    #[validate(custom = "validate_radius")]
+    radius: CoOrdinate,
+    #[validate(custom = "validate_non_negative")]
+    distance_decay: f64,
+    #[validate]
+    settings: SettingMultipliers,
+    #[validate(custom = "validate_non_negative")]
+    build_up: f64,
+}
+
+impl Default for TransmissionKernel {
+    fn default() -> Self {
+        TransmissionKernel { radius: 1, distance_decay: 0.0, settings: SettingMultipliers::default(), build_up: 0.0 }
+    }
+}
+
+fn validate_radius(radius: &CoOrdinate) -> Result<(), ValidationError> {
+    if *radius < 1 {
+        return Err(ValidationError::new("radius needs to be at least 1"));
+    }
+    Ok(())
+}
+
+impl TransmissionKernel {
+    #[cfg(test)]
+    pub fn new(radius: CoOrdinate, distance_decay: f64, settings: SettingMultipliers, build_up: f64) -> TransmissionKernel {
+        TransmissionKernel { radius, distance_decay, settings, build_up }
+    }
+
+    pub fn get_radius(&self) -> CoOrdinate {
+        self.radius
+    }
+
+    /// The chance of infection in an hour from contacts at a distance with a transmission rate each, after
+    /// `contact_hours` consecutive hours in contact (this one included). Every contact is an independent chance
+    pub fn infection_probability(&self, contacts: &[(CoOrdinate, Percentage)], setting: Setting, contact_hours: Hour) -> Percentage {
+        let multiplier = self.settings.of(setting) * (1.0 + self.build_up * contact_hours.saturating_sub(1) as f64);
+        let escape: f64 = contacts.iter()
+            .map(|(distance, rate)| {
+                let decay = (-self.distance_decay * (*distance - 1).max(0) as f64).exp();
+                (1.0 - rate).powf(decay * multiplier)
+            })
+            .product();
+        1.0 - escape
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close_to(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-9
+    }
+
+    #[test]
+    fn should_combine_contacts_as_independent_chances_by_default() {
+        let kernel = TransmissionKernel::default();
+
+        let probability = kernel.infection_probability(&[(1, 0.5), (1, 0.2)], Setting::Work, 5);
+
+        assert!(close_to(probability, 1.0 - 0.5 * 0.8));
+        assert_eq!(kernel.infection_probability(&[], Setting::Home, 0), 0.0);
+    }
+
+    #[test]
+    fn should_decay_with_distance() {
+        let kernel = TransmissionKernel::new(3, 0.5, SettingMultipliers::default(), 0.0);
+
+        let near = kernel.infection_probability(&[(1, 0.3)], Setting::Community, 1);
+        let far = kernel.infection_probability(&[(3, 0.3)], Setting::Community, 1);
+
+        assert!(close_to(near, 0.3));
+        assert!(close_to(far, 1.0 - 0.7f64.powf((-1.0f64).exp())));
+    }
+
+    #[test]
+    fn should_scale_by_setting_and_build_up_over_hours() {
+        let settings = SettingMultipliers { home: 2.0, hospital: 0.0, ..SettingMultipliers::default() };
+        let kernel = TransmissionKernel::new(1, 0.0, settings, 0.5);
+
+        assert!(close_to(kernel.infection_probability(&[(1, 0.5)], Setting::Home, 1), 0.75));
+        assert!(close_to(kernel.infection_probability(&[(1, 0.5)], Setting::Work, 3), 0.75));
+        assert_eq!(kernel.infection_probability(&[(1, 1.0)], Setting::Hospital, 1), 0.0);
+    }
+
+    #[test]
+    fn should_read_kernel_with_defaults() {
+        let kernel: TransmissionKernel = serde_json::from_str(r#"{"radius": 2, "settings": {"transport": 3.0}}"#).unwrap();
+
+        assert_eq!(kernel.get_radius(), 2);
+        assert_eq!(kernel.settings.transport, 3.0);
+        assert_eq!(kernel.settings.home, 1.0);
+        assert_eq!(kernel.build_up, 0.0);
+    }
+
+    #[test]
+    fn should_not_validate_kernel_without_radius_or_with_negative_factors() {
+        assert!(TransmissionKernel::default().validate().is_ok());
+        assert!(TransmissionKernel::new(0, 0.0, SettingMultipliers::default(), 0.0).validate().is_err());
+        assert!(TransmissionKernel::new(1, -0.5, SettingMultipliers::default(), 0.0).validate().is_err());
+        assert!(TransmissionKernel::new(1, 0.0, SettingMultipliers::default(), -0.5).validate().is_err());
+        let settings = SettingMultipliers { transport: -1.0, ..SettingMultipliers::default() };
+        assert!(TransmissionKernel::new(1, 0.0, settings, 0.0).validate().is_err());
+    }
+}
@@ -17,12 +17,71 @@
  *
  */
 
-use crate::agent::Citizen;
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::agent::{Citizen, Sex};
+use crate::calendar::Calendar;
 use crate::geography::Point;
 use uuid::Uuid;
 use crate::commute::Commuter;
-use crate::custom_types::Count;
+use crate::constants;
+use crate::custom_types::{Count, Day, Hour};
 use crate::disease_state_machine::DiseaseStateMachine;
+use crate::interventions::travel_restriction::TravelRestrictions;
+
+const DAYS_IN_A_CYCLE: Day = 7;
+
+/// Replaces the base commute or migration matrix while it is active. `days_of_cycle` are days of a seven day cycle
+/// starting with the first day of the simulation (0 to 6), and `days_of_week` weekdays of the calendar dates.
+/// `from_date` and `to_date` are inclusive, `from_hour` is inclusive and `to_hour` exclusive. Without a `start_date`
+/// there are no dates, so entries with weekdays or dates are never active
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+pub struct ScheduledMatrix {
+    #[serde(default)]
+    pub days_of_cycle: Vec<Day>,
+    #[serde(default)]
+    pub days_of_week: Vec<Weekday>,
+    #[serde(default)]
+    pub from_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub to_date: Option<NaiveDate>,
+    pub from_hour: Option<Hour>,
+    pub to_hour: Option<Hour>,
+    pub matrix: Vec<Vec<u32>>,
+}
+
+impl ScheduledMatrix {
+    pub fn is_active(&self, hour: Hour, calendar: &Calendar) -> bool {
+        let day_of_cycle = (hour / constants::HOURS_IN_A_DAY) % DAYS_IN_A_CYCLE;
+        let date = calendar.date_at(hour);
+        (self.days_of_cycle.is_empty() || self.days_of_cycle.contains(&day_of_cycle)) &&
+            (self.days_of_week.is_empty() || matches!(date, Some(date) if self.days_of_week.contains(&date.weekday()))) &&
+            self.from_date.iter().all(|from| matches!(date, Some(date) if date >= *from)) &&
+            self.to_date.iter().all(|to| matches!(date, Some(date) if date <= *to)) &&
+            self.from_hour.iter().all(|from| hour >= *from) &&
+            self.to_hour.iter().all(|to| hour < *to)
+    }
+}
+
+/// The first active matrix in the schedule, or the base matrix when none is active
+pub fn matrix_at<'a>(base: &'a [Vec<u32>], schedule: &'a [ScheduledMatrix], hour: Hour, calendar: &Calendar) -> &'a [Vec<u32>] {
+    schedule.iter()
+        .find(|scheduled| scheduled.is_active(hour, calendar))
+        .map_or(base, |scheduled| &scheduled.matrix)
+}
+
+/// Element-wise maximum of the base and all the scheduled matrices
+pub fn max_matrix(base: &[Vec<u32>], schedule: &[ScheduledMatrix]) -> Vec<Vec<u32>> {
+    let mut max = base.to_vec();
+    for scheduled in schedule {
+        for (max_row, row) in max.iter_mut().zip(scheduled.matrix.iter()) {
+            for (max_val, val) in max_row.iter_mut().zip(row.iter()) {
+                *max_val = (*max_val).max(*val);
+            }
+        }
+    }
+    max
+}
 
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct MigrationPlan {
@@ -41,9 +100,11 @@ impl MigrationPlan {
         row.iter().sum()
     }
 
-    pub fn incoming_regions_count(&self, engine_id: &str) -> u32 {
+    pub fn incoming_regions_count(&self, engine_id: &str, restrictions: &TravelRestrictions) -> u32 {
         let index = self.get_position(engine_id);
-        self.column(index).filter(|val| *val > 0).count() as u32
+        self.column(index).zip(self.regions.iter())
+            .filter(|(val, region)| *val > 0 && !restrictions.is_blocked(region, engine_id))
+            .count() as u32
     }
 
     // pub fn get_total_incoming(&self, engine_id: String) -> i32 {
@@ -95,8 +156,11 @@ impl EngineMigrationPlan {
         }
     }
 
-    pub fn alloc_outgoing_to_regions(&self, outgoing: &Vec<(Point, Migrator)>) -> (Vec<MigratorsByRegion>, Vec<(Point, Migrator)>)  {
-        let mut migrators: Vec<Migrator> = outgoing.iter().map(|x| x.1).collect();
+    /// Migrators turned back by the restrictions stay in the current region
+    pub fn alloc_outgoing_to_regions(&self, outgoing: &[(Point, Migrator)], restrictions: &TravelRestrictions)
+                                     -> (Vec<MigratorsByRegion>, Vec<(Point, Migrator)>)  {
+        let (mut migrators, turned_back): (Vec<Migrator>, Vec<Migrator>) = outgoing.iter().map(|x| x.1.clone())
+            .partition(|migrator| restrictions.allows(&migrator.state_machine));
         let total_outgoing = migrators.len();
         let outgoing_by_region = match &self.migration_plan {
             None => { Vec::new() }
@@ -104,14 +168,17 @@ impl EngineMigrationPlan {
                 tp.regions.iter()
                     .filter(|region| !self.engine_id.eq(*region))
                     .filter(|region| tp.get_outgoing(self.engine_id(), region) > 0)
+                    .filter(|region| !restrictions.is_blocked(self.engine_id(), region))
                     .map(|region| {
                         let mut outgoing_by_region = MigratorsByRegion::create(region);
-                        outgoing_by_region.alloc_citizens(&mut migrators, tp, &self.engine_id, total_outgoing as i32);
+                        let scale = restrictions.scale(self.engine_id(), region);
+                        outgoing_by_region.alloc_citizens(&mut migrators, tp, &self.engine_id, total_outgoing as i32, scale);
                         outgoing_by_region
                     }).collect()
             }
         };
-        let actual_outgoing_migrators: Vec<(Point, Migrator)> = outgoing.iter().filter(|x|  !migrators.contains(&x.1)).map( |y| (y.0, y.1)).collect();
+        let actual_outgoing_migrators: Vec<(Point, Migrator)> = outgoing.iter()
+            .filter(|x| !migrators.contains(&x.1) && !turned_back.contains(&x.1)).map( |y| (y.0, y.1.clone())).collect();
 
         //assign remaining citizens (if any) to last region
         // for remaining in travellers {
@@ -121,10 +188,14 @@ impl EngineMigrationPlan {
         (outgoing_by_region, actual_outgoing_migrators)
     }
 
-    pub fn incoming_regions_count(&self) -> u32 {
+    pub fn set_migration_plan(&mut self, migration_plan: MigrationPlan) {
+        self.migration_plan = Some(migration_plan);
+    }
+
+    pub fn incoming_regions_count(&self, restrictions: &TravelRestrictions) -> u32 {
         match &self.migration_plan {
             None => { 0 }
-            Some(tp) => { tp.incoming_regions_count(self.engine_id()) }
+            Some(tp) => { tp.incoming_regions_count(self.engine_id(), restrictions) }
         }
     }
 
@@ -153,10 +224,11 @@ impl MigratorsByRegion {
         (percent_outgoing * (total_outgoing as f64)) as i32
     }
 
-    /// Note that this function mutates (drains) the total list of outgoing citizens
+    /// Note that this function mutates (drains) the total list of outgoing citizens.
+    /// The scale comes from travel restrictions, and is 1.0 when the flow is not restricted
     pub fn alloc_citizens(&mut self, citizens: &mut Vec<Migrator>, travel_plan: &MigrationPlan,
-                          engine_id: &String, total_outgoing: i32) {
-        let mut count = self.actual_outgoing_count(travel_plan, total_outgoing, engine_id) as usize;
+                          engine_id: &String, total_outgoing: i32, scale: f64) {
+        let mut count = (self.actual_outgoing_count(travel_plan, total_outgoing, engine_id) as f64 * scale) as usize;
         if count > citizens.len() {
             debug!("Limiting outgoing citizens to {} instead of {}", citizens.len(), count);
             count = citizens.len();
@@ -189,7 +261,7 @@ impl MigratorsByRegion {
     }
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Migrator {
     pub id: Uuid,
     pub immunity: i32,
@@ -197,6 +269,12 @@ pub struct Migrator {
     pub uses_public_transport: bool,
     pub working: bool,
     pub state_machine: DiseaseStateMachine,
+    #[serde(default)]
+    pub age: Option<u32>,
+    #[serde(default)]
+    pub sex: Option<Sex>,
+    #[serde(default)]
+    pub comorbidities: Vec<String>,
 }
 
 impl Migrator {
@@ -208,7 +286,10 @@ impl Migrator {
             vaccinated: false,
             uses_public_transport: false,
             working: false,
-            state_machine: DiseaseStateMachine::new()
+            state_machine: DiseaseStateMachine::new(),
+            age: None,
+            sex: None,
+            comorbidities: Vec::new(),
         }
     }
 }
@@ -221,7 +302,10 @@ impl From<&Citizen> for Migrator {
             vaccinated: citizen.is_vaccinated(),
             uses_public_transport: citizen.uses_public_transport,
             working: citizen.is_working(),
-            state_machine: citizen.state_machine
+            state_machine: citizen.state_machine,
+            age: citizen.get_age(),
+            sex: citizen.get_sex(),
+            comorbidities: citizen.get_comorbidities().to_vec(),
         }
     }
 }
@@ -236,7 +320,10 @@ impl From<&Citizen> for Commuter {
             vaccinated: citizen.is_vaccinated(),
             uses_public_transport: citizen.uses_public_transport,
             working: citizen.is_working(),
-            state_machine: citizen.state_machine
+            state_machine: citizen.state_machine,
+            age: citizen.get_age(),
+            sex: citizen.get_sex(),
+            comorbidities: citizen.get_comorbidities().to_vec(),
         }
     }
 }
@@ -249,7 +336,10 @@ impl PartialEq for Migrator {
 
 #[cfg(test)]
 mod tests {
+    use crate::interventions::travel_restriction::FlowRestriction;
+
     use super::*;
+    use crate::config::CalendarConfig;
 
     #[test]
     fn should_get_total_outgoing() {
@@ -278,9 +368,16 @@ mod tests {
             ],
         };
 
-        assert_eq!(2, migration_plan.incoming_regions_count( "engine1"));
-        assert_eq!(1, migration_plan.incoming_regions_count("engine2"));
-        assert_eq!(0, migration_plan.incoming_regions_count("engine3"));
+        let no_restrictions = TravelRestrictions::default();
+        assert_eq!(2, migration_plan.incoming_regions_count( "engine1", &no_restrictions));
+        assert_eq!(1, migration_plan.incoming_regions_count("engine2", &no_restrictions));
+        assert_eq!(0, migration_plan.incoming_regions_count("engine3", &no_restrictions));
+
+        let restrictions = TravelRestrictions::new(vec![FlowRestriction {
+            from: Some("engine3".to_string()), to: None, scale: 0.0
+        }], false);
+        assert_eq!(1, migration_plan.incoming_regions_count( "engine1", &restrictions));
+        assert_eq!(0, migration_plan.incoming_regions_count("engine2", &restrictions));
     }
 
     #[test]
@@ -305,13 +402,54 @@ mod tests {
             outgoing.push((Point::new(1, 1), create_traveller()));
         }
 
-        let (outgoing_by_region, _) = engine_travel_plan.alloc_outgoing_to_regions(&outgoing);
+        let (outgoing_by_region, _) = engine_travel_plan.alloc_outgoing_to_regions(&outgoing, &TravelRestrictions::default());
 
         assert_eq!(2, outgoing_by_region.len());
         assert_eq!(156, outgoing_by_region.get(0).unwrap().migrators.len());
         assert_eq!(24, outgoing_by_region.get(1).unwrap().migrators.len());
     }
 
+    #[test]
+    fn should_scale_and_block_restricted_flows() {
+        let engine_travel_plan = create_engine_with_travel_plan();
+        let mut outgoing = Vec::new();
+
+        for _i in 0..180 {
+            outgoing.push((Point::new(1, 1), create_traveller()));
+        }
+        let restrictions = TravelRestrictions::new(vec![
+            FlowRestriction { from: Some("engine1".to_string()), to: Some("engine2".to_string()), scale: 0.5 },
+            FlowRestriction { from: None, to: Some("engine3".to_string()), scale: 0.0 },
+        ], false);
+
+        let (outgoing_by_region, actual_outgoing) = engine_travel_plan.alloc_outgoing_to_regions(&outgoing, &restrictions);
+
+        assert_eq!(1, outgoing_by_region.len());
+        assert_eq!("engine2", outgoing_by_region[0].to_engine_id());
+        assert_eq!(78, outgoing_by_region[0].migrators.len());
+        assert_eq!(78, actual_outgoing.len());
+    }
+
+    #[test]
+    fn should_turn_back_symptomatic_migrators() {
+        let engine_travel_plan = create_engine_with_travel_plan();
+        let mut outgoing = Vec::new();
+
+        for i in 0..180 {
+            let mut migrator = create_traveller();
+            if i % 2 == 0 {
+                migrator.state_machine.set_mild_symptomatic();
+            }
+            outgoing.push((Point::new(1, 1), migrator));
+        }
+        let restrictions = TravelRestrictions::new(vec![], true);
+
+        let (_, actual_outgoing) = engine_travel_plan.alloc_outgoing_to_regions(&outgoing, &restrictions);
+
+        assert_eq!(90, actual_outgoing.len());
+        assert!(actual_outgoing.iter().all(|(_, migrator)| !migrator.state_machine.is_symptomatic()));
+    }
+
     // Removed the logic of adding remaining traveller in the last engine temporarily.
     // #[test]
     // fn should_handle_outgoing_with_actual_total_less_than_planned() {
@@ -345,6 +483,56 @@ mod tests {
     //     assert_eq!(27, outgoing_by_region.get(1).unwrap().travellers.len());
     // }
 
+    #[test]
+    fn should_pick_active_matrix_from_schedule() {
+        let base = vec![vec![0, 10], vec![10, 0]];
+        let schedule = vec![
+            ScheduledMatrix { days_of_cycle: vec![5, 6], matrix: vec![vec![0, 2], vec![2, 0]], ..ScheduledMatrix::default() },
+            ScheduledMatrix { from_hour: Some(240), to_hour: Some(264), matrix: vec![vec![0, 50], vec![5, 0]], ..ScheduledMatrix::default() },
+        ];
+        let calendar = Calendar::new(None, &CalendarConfig::default());
+
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 7, &calendar));
+        assert_eq!(schedule[0].matrix.as_slice(), matrix_at(&base, &schedule, 5 * 24 + 7, &calendar));
+        assert_eq!(schedule[0].matrix.as_slice(), matrix_at(&base, &schedule, 13 * 24 + 23, &calendar));
+        assert_eq!(schedule[1].matrix.as_slice(), matrix_at(&base, &schedule, 240, &calendar));
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 264, &calendar));
+    }
+
+    #[test]
+    fn should_pick_matrix_by_weekday_and_date_range_of_calendar() {
+        let base = vec![vec![0, 10], vec![10, 0]];
+        let schedule = vec![
+            ScheduledMatrix { days_of_week: vec![Weekday::Sat, Weekday::Sun], matrix: vec![vec![0, 2], vec![2, 0]], ..ScheduledMatrix::default() },
+            ScheduledMatrix { from_date: Some(NaiveDate::from_ymd(2020, 3, 10)), to_date: Some(NaiveDate::from_ymd(2020, 3, 11)),
+                matrix: vec![vec![0, 50], vec![5, 0]], ..ScheduledMatrix::default() },
+        ];
+        let calendar = Calendar::new(Some(NaiveDate::from_ymd(2020, 3, 6)), &CalendarConfig::default());
+
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 7, &calendar));
+        assert_eq!(schedule[0].matrix.as_slice(), matrix_at(&base, &schedule, 24 + 7, &calendar));
+        assert_eq!(schedule[0].matrix.as_slice(), matrix_at(&base, &schedule, 2 * 24 + 7, &calendar));
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 3 * 24 + 7, &calendar));
+        assert_eq!(schedule[1].matrix.as_slice(), matrix_at(&base, &schedule, 4 * 24, &calendar));
+        assert_eq!(schedule[1].matrix.as_slice(), matrix_at(&base, &schedule, 5 * 24 + 23, &calendar));
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 6 * 24, &calendar));
+
+        let undated = Calendar::new(None, &CalendarConfig::default());
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 24 + 7, &undated));
+        assert_eq!(base.as_slice(), matrix_at(&base, &schedule, 4 * 24, &undated));
+    }
+
+    #[test]
+    fn should_get_max_of_scheduled_matrices() {
+        let base = vec![vec![0, 10], vec![10, 0]];
+        let schedule = vec![
+            ScheduledMatrix { days_of_cycle: vec![6], matrix: vec![vec![0, 2], vec![2, 0]], ..ScheduledMatrix::default() },
+            ScheduledMatrix { from_hour: Some(240), matrix: vec![vec![0, 50], vec![5, 0]], ..ScheduledMatrix::default() },
+        ];
+
+        assert_eq!(vec![vec![0, 50], vec![10, 0]], max_matrix(&base, &schedule));
+    }
+
     fn create_travel_plan() -> MigrationPlan {
         MigrationPlan {
             regions: vec!["engine1".to_string(), "engine2".to_string(), "engine3".to_string()],
@@ -17,20 +17,31 @@
  *
  */
 
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
 
+use chrono::NaiveDate;
 use serde_json::Value;
 use validator::Validate;
 use crate::config::Population::Auto;
 
 use crate::custom_types::{Count, Percentage, Size, validate_percentage};
 use crate::travel_plan::TravelPlan;
+use crate::engine_packing;
+use crate::interventions::InterventionConfig;
+use crate::ticks::CommuteHours;
 
-pub fn read_simulation_conf(filename: &str) -> String {
+/// The config as sent to the engines, with the travel plan matrices read from their csv files
+/// and the regions hosted by each engine when they are packed into fewer engines
+pub fn read_simulation_conf(filename: &str, configuration: &Configuration) -> String {
     let reader = File::open(filename).unwrap();
-    let config: Value = serde_json::from_reader(reader).unwrap();
-    let sim = config.as_object().unwrap();
+    let mut config: Value = serde_json::from_reader(reader).unwrap();
+    let sim = config.as_object_mut().unwrap();
+    sim.insert("travel_plan".to_string(), serde_json::to_value(configuration.get_travel_plan()).unwrap());
+    if let Some(region_assignment) = configuration.region_assignment() {
+        sim.insert("region_assignment".to_string(), serde_json::to_value(region_assignment).unwrap());
+    }
     serde_json::to_string(sim).unwrap()
 }
 
@@ -47,6 +58,11 @@ pub fn get_hours(filename: &str) -> i64 {
 pub struct Configuration {
     engine_configs: Vec<EngineConfig>,
     travel_plan: TravelPlan,
+    output_file: Option<String>,
+    #[serde(default)]
+    interventions: Vec<InterventionConfig>,
+    #[serde(default)]
+    engine_processes: Vec<String>,
 }
 
 impl Configuration {
@@ -54,16 +70,51 @@ impl Configuration {
         &self.travel_plan
     }
 
+    pub fn get_output_file(&self) -> Option<String> {
+        self.output_file.clone()
+    }
+
+    pub fn get_interventions(&self) -> &[InterventionConfig] {
+        &self.interventions
+    }
+
+    /// Without engine processes in the config, every region runs in an engine of its own
+    pub fn region_assignment(&self) -> Option<BTreeMap<String, Vec<String>>> {
+        if self.engine_processes.is_empty() {
+            return None;
+        }
+        let populations: Vec<(String, u32)> = self.engine_configs.iter()
+            .map(|eng_conf| (eng_conf.engine_id.clone(), eng_conf.config.population.size()))
+            .collect();
+        Some(engine_packing::pack_regions(&populations, &self.engine_processes))
+    }
+
+    /// Engines exchange commuters at the hours set by their worker schedule, which all of them need to share
+    pub fn commute_hours(&self) -> CommuteHours {
+        let commute_hours = self.engine_configs[0].config.schedules.commute_hours();
+        if self.engine_configs.iter().any(|eng_conf| eng_conf.config.schedules.commute_hours() != commute_hours) {
+            panic!("All engines should have the same transport blocks in their worker schedule");
+        }
+        commute_hours
+    }
+
     pub fn get_engine_ids(&self) -> Vec<String> {
         self.engine_configs.iter().map(|s| s.engine_id.clone()).collect()
     }
 
     pub fn read(filename: &str) -> Result<Configuration, Box<dyn Error>> {
         let reader = File::open(filename)?;
-        let config: Configuration = serde_json::from_reader(reader)?;
+        let mut config: Configuration = serde_json::from_reader(reader)?;
         if !config.travel_plan.validate_regions(&config.get_engine_ids()) {
             panic!("Engine names should match regions in travel plan");
         }
+        config.travel_plan.load_matrix_files()?;
+        if !config.travel_plan.validate_matrices() {
+            panic!("Every travel matrix should have a row and a column for each region");
+        }
+        if config.travel_plan.is_dated() && config.engine_configs.iter().any(|e| e.config.start_date.is_none()) {
+            panic!("Every engine should have a start_date for travel scheduled by weekday or date");
+        }
         Ok(config)
     }
 
@@ -74,34 +125,46 @@ impl Configuration {
             let min_accepted_ratio = 3;
             let travel_plan = self.get_travel_plan();
 
-            let mut total_population = 0;
+            let mut total_population: i64 = 0;
 
 
-            if let Auto(x) = population {
-                total_population += &x.number_of_agents;
+            match population {
+                Auto(x) => total_population += x.number_of_agents as i64,
+                Population::Synthetic(x) => total_population += x.number_of_agents as i64,
+                Population::Csv(_) => {}
             }
 
+            // the scheduled matrices are checked as well, taking the one with the largest net inflow
             if travel_plan.commute.enabled {
-                let commute_plan = travel_plan.commute_plan();
-                let incoming_commuters = commute_plan.get_total_incoming(&eng_conf.engine_id);
-                let outgoing_commuters = commute_plan.get_total_outgoing(&eng_conf.engine_id);
+                let net_incoming = travel_plan.commute_plans().iter().map(|commute_plan| {
+                    let incoming_commuters = commute_plan.get_total_incoming(&eng_conf.engine_id);
+                    let outgoing_commuters = commute_plan.get_total_outgoing(&eng_conf.engine_id);
 
-                debug!("Total incoming commuters: {}, Total outgoing commuters: {}", incoming_commuters, outgoing_commuters);
+                    debug!("Total incoming commuters: {}, Total outgoing commuters: {}", incoming_commuters, outgoing_commuters);
 
-                total_population += incoming_commuters - outgoing_commuters;
+                    incoming_commuters as i64 - outgoing_commuters as i64
+                }).max().unwrap();
+
+                total_population += net_incoming;
             }
 
             if travel_plan.migration.enabled {
-                let migration_plan = travel_plan.migration_plan();
-                let incoming_migrators = migration_plan.get_total_incoming(&eng_conf.engine_id);
-                let outgoing_migrators = migration_plan.get_total_outgoing(&eng_conf.engine_id);
+                let net_incoming = travel_plan.migration_plans().iter().map(|migration_plan| {
+                    let incoming_migrators = migration_plan.get_total_incoming(&eng_conf.engine_id);
+                    let outgoing_migrators = migration_plan.get_total_outgoing(&eng_conf.engine_id);
+
+                    debug!("Total incoming migrators: {}, Total outgoing migrators: {}", incoming_migrators, outgoing_migrators);
 
-                debug!("Total incoming migrators: {}, Total outgoing migrators: {}", incoming_migrators, outgoing_migrators);
+                    incoming_migrators as i64 - outgoing_migrators as i64
+                }).max().unwrap();
 
-                total_population += incoming_migrators - outgoing_migrators;
+                total_population += net_incoming;
             }
 
-            let actual_ratio = (grid_size * grid_size) / total_population;
+            if total_population < 0 {
+                panic!("{}: More commuters/migrators leave than the population of the region", eng_conf.engine_id);
+            }
+            let actual_ratio = (grid_size * grid_size) as i64 / total_population.max(1);
             if actual_ratio < min_accepted_ratio {
                 debug!("grid size: {}, total population: {}", actual_ratio, total_population);
                 panic!("{}: Not enough space to accumulate the migrators/commuters", eng_conf.engine_id);
@@ -114,6 +177,51 @@ impl Configuration {
 struct Config {
     population: Population,
     geography_parameters: GeographyParameters,
+    #[serde(default)]
+    schedules: Schedules,
+    #[serde(default)]
+    start_date: Option<NaiveDate>,
+}
+
+/// Only the worker schedule of the engines matters here, for the hours commuters travel
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+struct Schedules {
+    #[serde(default)]
+    worker: Vec<ScheduleBlock>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+struct ScheduleBlock {
+    from: i64,
+    to: i64,
+    activity: Activity,
+}
+
+/// The activities of the engines' schedules
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+enum Activity {
+    Sleep,
+    Home,
+    Transport,
+    Work,
+    School,
+    Neighbourhood,
+    Hospital,
+    OffShift,
+}
+
+impl Schedules {
+    /// As the engines do, from the first and the last transport block, or the default hours without two of them
+    fn commute_hours(&self) -> CommuteHours {
+        let transport: Vec<i64> = self.worker.iter()
+            .filter(|block| block.activity == Activity::Transport)
+            .map(|block| block.from)
+            .collect();
+        match (transport.first(), transport.last()) {
+            (Some(to_work), Some(back_home)) if to_work < back_home => CommuteHours { to_work: *to_work, back_home: *back_home },
+            _ => CommuteHours::default(),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Validate)]
@@ -128,12 +236,28 @@ pub struct GeographyParameters {
 pub enum Population {
     Csv(CsvPopulation),
     Auto(AutoPopulation),
+    Synthetic(SyntheticPopulation),
+}
+
+impl Population {
+    /// Number of agents, for csv populations the number of rows in the file
+    pub fn size(&self) -> u32 {
+        match self {
+            Auto(x) => x.number_of_agents,
+            Population::Synthetic(x) => x.number_of_agents,
+            Population::Csv(x) => {
+                let mut reader = csv::Reader::from_path(&x.file)
+                    .unwrap_or_else(|e| panic!("Could not read population file {}: {}", x.file, e));
+                reader.records().count() as u32
+            }
+        }
+    }
 }
 
+/// The engines read the citizens through the columns of the file, only its rows are counted here
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct CsvPopulation {
     pub file: String,
-    pub cols: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone, Validate)]
@@ -145,6 +269,12 @@ pub struct AutoPopulation {
     pub working_percentage: Percentage,
 }
 
+/// The engines fit the synthetic population to its margins, only its size matters here
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct SyntheticPopulation {
+    pub number_of_agents: Count,
+}
+
 // just a struct for easier parsing
 #[derive(Deserialize, Serialize)]
 struct EngineConfig {
@@ -169,8 +299,110 @@ mod tests {
 
     #[test]
     fn should_read_config_for_engines() {
-        let config_for_engines = read_simulation_conf("config/test/travel_plan.json");
-        assert!(!config_for_engines.is_empty())
+        let config = Configuration::read("config/test/travel_plan.json").unwrap();
+        let config_for_engines = read_simulation_conf("config/test/travel_plan.json", &config);
+        assert!(!config_for_engines.is_empty());
+        let sim: Value = serde_json::from_str(&config_for_engines).unwrap();
+        assert!(sim.get("region_assignment").is_none());
+    }
+
+    #[test]
+    fn should_send_scheduled_matrices_inline_to_engines() {
+        let config = Configuration::read("config/test/travel_plan_with_schedule.json").unwrap();
+        let config_for_engines = read_simulation_conf("config/test/travel_plan_with_schedule.json", &config);
+        let sim: Value = serde_json::from_str(&config_for_engines).unwrap();
+
+        let scheduled = &sim["travel_plan"]["commute"]["schedule"][0];
+        assert_eq!(serde_json::json!([[0, 8, 1], [5, 0, 14], [4, 3, 0]]), scheduled["matrix"]);
+        assert_eq!(serde_json::json!([5, 6]), scheduled["days_of_cycle"]);
+        assert!(scheduled.get("days_of_week").is_none());
+    }
+
+    fn write_dated_schedule(name: &str, start_date: Option<&str>) -> String {
+        let mut json: Value = serde_json::from_reader(File::open("config/test/travel_plan_with_schedule.json").unwrap()).unwrap();
+        json["travel_plan"]["commute"]["schedule"][0] = serde_json::json!({"days_of_week": ["Sat", "Sun"], "from_date": "2020-03-01",
+            "to_date": "2020-04-30", "matrix_file": "config/test/weekend_commute.csv"});
+        if let Some(start_date) = start_date {
+            json["engine_configs"].as_array_mut().unwrap().iter_mut().for_each(|e| e["config"]["start_date"] = serde_json::json!(start_date));
+        }
+        let path = std::env::temp_dir().join(name);
+        serde_json::to_writer(File::create(&path).unwrap(), &json).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn should_send_weekdays_and_dates_of_schedule_to_engines() {
+        let path = write_dated_schedule("travel_plan_with_dated_schedule.json", Some("2020-03-06"));
+        let config = Configuration::read(&path).unwrap();
+        let sim: Value = serde_json::from_str(&read_simulation_conf(&path, &config)).unwrap();
+
+        let scheduled = &sim["travel_plan"]["commute"]["schedule"][0];
+        assert_eq!(serde_json::json!(["Sat", "Sun"]), scheduled["days_of_week"]);
+        assert_eq!(serde_json::json!("2020-03-01"), scheduled["from_date"]);
+        assert_eq!(serde_json::json!("2020-04-30"), scheduled["to_date"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Every engine should have a start_date for travel scheduled by weekday or date")]
+    fn should_not_accept_dated_schedule_without_start_date() {
+        let path = write_dated_schedule("travel_plan_with_undated_engines.json", None);
+        Configuration::read(&path).unwrap();
+    }
+
+    #[test]
+    fn should_send_packed_regions_to_engines() {
+        let mut config = Configuration::read("config/test/travel_plan.json").unwrap();
+        config.engine_processes = vec!["engine-a".to_string(), "engine-b".to_string()];
+
+        let config_for_engines = read_simulation_conf("config/test/travel_plan.json", &config);
+        let sim: Value = serde_json::from_str(&config_for_engines).unwrap();
+
+        assert_eq!(serde_json::json!({"engine-a": ["engine1", "engine3"], "engine-b": ["engine2"]}), sim["region_assignment"]);
+    }
+
+    #[test]
+    fn should_take_commute_hours_from_worker_schedule() {
+        let mut config = Configuration::read("config/test/travel_plan.json").unwrap();
+        assert_eq!(config.commute_hours(), CommuteHours { to_work: 7, back_home: 17 });
+
+        let schedules: Schedules = serde_json::from_str(r#"{"worker": [{"from": 1, "to": 5, "activity": "Sleep"},
+            {"from": 6, "to": 6, "activity": "Transport"}, {"from": 7, "to": 14, "activity": "Work"},
+            {"from": 15, "to": 16, "activity": "Transport"}]}"#).unwrap();
+        config.engine_configs.iter_mut().for_each(|eng_conf| eng_conf.config.schedules = schedules.clone());
+        assert_eq!(config.commute_hours(), CommuteHours { to_work: 6, back_home: 15 });
+    }
+
+    #[test]
+    #[should_panic(expected = "All engines should have the same transport blocks in their worker schedule")]
+    fn should_not_accept_engines_commuting_at_different_hours() {
+        let mut config = Configuration::read("config/test/travel_plan.json").unwrap();
+        config.engine_configs[1].config.schedules = Schedules {
+            worker: vec![ScheduleBlock { from: 6, to: 6, activity: Activity::Transport },
+                         ScheduleBlock { from: 15, to: 16, activity: Activity::Transport }]
+        };
+
+        config.commute_hours();
+    }
+
+    #[test]
+    fn should_not_read_unknown_activity_in_worker_schedule() {
+        let schedules = serde_json::from_str::<Schedules>(r#"{"worker": [{"from": 6, "to": 6, "activity": "transport"}]}"#);
+
+        assert!(schedules.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "engine1: More commuters/migrators leave than the population of the region")]
+    fn should_not_accept_more_travellers_leaving_than_population() {
+        let mut config = Configuration::read("config/test/travel_plan.json").unwrap();
+        config.engine_configs[0].config.population = Auto(AutoPopulation {
+            number_of_agents: 10, public_transport_percentage: 0.2, working_percentage: 0.7,
+        });
+        config.travel_plan.migration.enabled = false;
+        config.travel_plan.commute.enabled = true;
+        config.travel_plan.commute.matrix = Some(vec![vec![0, 50, 0], vec![0, 0, 0], vec![0, 0, 0]]);
+
+        config.validate();
     }
 
     #[test]
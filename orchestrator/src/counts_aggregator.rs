@@ -0,0 +1,261 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local};
+use csv::Writer;
+
+use crate::ticks::{Counts, TickAcks};
+
+const COUNT_COLUMNS: [&str; 18] = ["susceptible", "exposed", "infected", "hospitalized", "recovered", "deceased",
+    "new_exposures", "new_infections", "new_symptomatic", "new_hospital_admissions", "new_recoveries", "new_deaths",
+    "daily_new_exposures", "daily_new_infections", "daily_new_symptomatic", "daily_new_hospital_admissions",
+    "daily_new_recoveries", "daily_new_deaths"];
+
+pub fn output_file_format(output_file_prefix: Option<String>) -> String {
+    let now: DateTime<Local> = SystemTime::now().into();
+    let prefix = output_file_prefix.unwrap_or_else(|| "simulation".to_string());
+    format!("output/{}_orchestrator_{}", prefix, now.format("%Y-%m-%dT%H:%M:%S"))
+}
+
+/// Peak, attack rate and deaths for a region (or the whole city) over the run
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RegionSummary {
+    region: String,
+    population: i32,
+    peak_infected: i32,
+    peak_infected_hour: i64,
+    attack_rate: f64,
+    deaths: i32,
+}
+
+impl RegionSummary {
+    fn new(region: String) -> RegionSummary {
+        RegionSummary { region, population: 0, peak_infected: 0, peak_infected_hour: 0, attack_rate: 0.0, deaths: 0 }
+    }
+
+    /// Infected includes the hospitalized, since the engine counts them separately
+    fn update(&mut self, hour: i64, counts: &Counts) {
+        let active_infections = counts.get_infected() + counts.get_hospitalized();
+        if active_infections > self.peak_infected {
+            self.peak_infected = active_infections;
+            self.peak_infected_hour = hour;
+        }
+        self.population = counts.total();
+        self.deaths = counts.get_deceased();
+        self.attack_rate = if self.population == 0 { 0.0 } else {
+            (self.population - counts.get_susceptible()) as f64 / self.population as f64
+        };
+    }
+}
+
+#[derive(Serialize)]
+struct RunSummary<'a> {
+    city: &'a RegionSummary,
+    regions: &'a [RegionSummary],
+}
+
+/// Combines the counts received in the tick acks of all engines into city-wide outputs. The counts are written
+/// as each tick completes, and the summary once the simulation ends
+pub struct CountsAggregator {
+    regions: Vec<String>,
+    output_path: PathBuf,
+    city_writer: Writer<File>,
+    regions_writer: Writer<File>,
+    city_summary: RegionSummary,
+    region_summaries: Vec<RegionSummary>,
+}
+
+impl CountsAggregator {
+    /// The outputs are named after the file of `output_path`, which need not exist
+    pub fn new(regions: &[String], output_path: &Path) -> CountsAggregator {
+        if let Some(dir) = output_path.parent() {
+            fs::create_dir_all(dir).expect("Failed to create output directory");
+        }
+        let city_writer = Writer::from_path(CountsAggregator::with_suffix(output_path, "_city.csv"))
+            .expect("Failed to create city-wide counts file");
+        let mut regions_writer = Writer::from_path(CountsAggregator::with_suffix(output_path, "_regions.csv"))
+            .expect("Failed to create counts by region file");
+        CountsAggregator::write_regions_header(&mut regions_writer, regions).expect("Failed to write counts by region");
+        CountsAggregator {
+            regions: regions.to_owned(),
+            output_path: output_path.to_path_buf(),
+            city_writer,
+            regions_writer,
+            city_summary: RegionSummary::new("city".to_string()),
+            region_summaries: regions.iter().map(|r| RegionSummary::new(r.clone())).collect(),
+        }
+    }
+
+    /// Should be called once all the acks for the hour have been received
+    pub fn record(&mut self, hour: i64, acks: &TickAcks) {
+        let counts: Vec<Counts> = self.regions.iter()
+            .map(|region| acks.get_counts(region).expect("Missing ack for region"))
+            .collect();
+        let city_counts = Counts::sum(hour as i32, &counts);
+
+        self.city_summary.update(hour, &city_counts);
+        self.region_summaries.iter_mut().zip(counts.iter())
+            .for_each(|(summary, region_counts)| summary.update(hour, region_counts));
+
+        self.write_city_counts(&city_counts).expect("Failed to write city-wide counts");
+        self.write_region_counts(hour, &counts).expect("Failed to write counts by region");
+    }
+
+    pub fn simulation_ended(&self) {
+        self.write_summary(&CountsAggregator::with_suffix(&self.output_path, "_summary.json"))
+            .expect("Failed to write run summary");
+    }
+
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut file_name = path.file_name().unwrap().to_os_string();
+        file_name.push(suffix);
+        path.with_file_name(file_name)
+    }
+
+    fn write_city_counts(&mut self, counts: &Counts) -> Result<(), Box<dyn Error>> {
+        self.city_writer.serialize(counts)?;
+        self.city_writer.flush()?;
+        Ok(())
+    }
+
+    /// Wide table with one row per acked hour and one column per region and state
+    fn write_regions_header(wtr: &mut Writer<File>, regions: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut header = vec!["hour".to_string()];
+        for region in regions {
+            header.extend(COUNT_COLUMNS.iter().map(|col| format!("{}_{}", region, col)));
+        }
+        wtr.write_record(&header)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    fn write_region_counts(&mut self, hour: i64, counts: &[Counts]) -> Result<(), Box<dyn Error>> {
+        let mut record = vec![hour.to_string()];
+        for c in counts {
+            record.extend(c.values().iter().map(|v| v.to_string()));
+        }
+        self.regions_writer.write_record(&record)?;
+        self.regions_writer.flush()?;
+        Ok(())
+    }
+
+    fn write_summary(&self, file_path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)?;
+        let summary = RunSummary { city: &self.city_summary, regions: &self.region_summaries };
+        serde_json::to_writer_pretty(file, &summary)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::ticks::TickAck;
+
+    use super::*;
+
+    fn acks_at(hour: i64, engine1: Counts, engine2: Counts) -> TickAcks {
+        let mut acks = TickAcks::new(&["engine1".to_string(), "engine2".to_string()]);
+        acks.reset(hour);
+        acks.push(TickAck::new("engine1".to_string(), hour, engine1));
+        acks.push(TickAck::new("engine2".to_string(), hour, engine2));
+        acks
+    }
+
+    fn output_path(name: &str) -> PathBuf {
+        env::temp_dir().join(name)
+    }
+
+    fn aggregator(output_path: &Path) -> CountsAggregator {
+        let mut aggregator = CountsAggregator::new(&["engine1".to_string(), "engine2".to_string()], output_path);
+        aggregator.record(1, &acks_at(1, Counts::new(1, 99, 1, 0, 0, 0, 0), Counts::new(1, 100, 0, 0, 0, 0, 0)));
+        aggregator.record(7, &acks_at(7, Counts::new(7, 80, 5, 10, 5, 0, 0), Counts::new(7, 96, 2, 2, 0, 0, 0)));
+        aggregator.record(17, &acks_at(17, Counts::new(17, 70, 5, 5, 5, 13, 2), Counts::new(17, 90, 2, 6, 0, 2, 0)));
+        aggregator
+    }
+
+    fn read_lines(output_path: &Path, suffix: &str) -> Vec<String> {
+        let contents = fs::read_to_string(CountsAggregator::with_suffix(output_path, suffix)).unwrap();
+        contents.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn should_sum_counts_of_all_regions() {
+        let output_path = output_path("test_aggregated_sum");
+        aggregator(&output_path);
+
+        let city_counts = read_lines(&output_path, "_city.csv");
+        assert_eq!(4, city_counts.len());
+        assert!(city_counts[0].starts_with("hour,susceptible,exposed,infected,hospitalized,recovered,deceased,new_exposures,"));
+        assert!(city_counts[2].starts_with("7,176,7,12,5,0,0,"));
+        let region_counts = read_lines(&output_path, "_regions.csv");
+        assert!(region_counts[3].starts_with("17,70,5,5,5,13,2,"));
+        assert!(region_counts[3].contains(",90,2,6,0,2,0,"));
+    }
+
+    #[test]
+    fn should_summarize_peak_attack_rate_and_deaths() {
+        let aggregator = aggregator(&output_path("test_aggregated_summary"));
+
+        let engine1 = &aggregator.region_summaries[0];
+        assert_eq!(15, engine1.peak_infected);
+        assert_eq!(7, engine1.peak_infected_hour);
+        assert_eq!(2, engine1.deaths);
+        assert_eq!(0.3, engine1.attack_rate);
+
+        let engine2 = &aggregator.region_summaries[1];
+        assert_eq!(6, engine2.peak_infected);
+        assert_eq!(17, engine2.peak_infected_hour);
+        assert_eq!(0.1, engine2.attack_rate);
+
+        let city = &aggregator.city_summary;
+        assert_eq!(200, city.population);
+        assert_eq!(17, city.peak_infected);
+        assert_eq!(2, city.deaths);
+        assert_eq!(0.2, city.attack_rate);
+    }
+
+    #[test]
+    fn should_write_counts_as_ticks_complete_and_summary_at_the_end() {
+        let output_path = output_path("test_aggregated_outputs");
+        let summary_file = CountsAggregator::with_suffix(&output_path, "_summary.json");
+        let _ = fs::remove_file(&summary_file);
+        let aggregator = aggregator(&output_path);
+
+        let region_counts = read_lines(&output_path, "_regions.csv");
+        let header = &region_counts[0];
+        assert!(header.starts_with("hour,engine1_susceptible,engine1_exposed,engine1_infected,engine1_hospitalized,\
+        engine1_recovered,engine1_deceased,engine1_new_exposures,"));
+        assert!(header.ends_with(",engine2_daily_new_recoveries,engine2_daily_new_deaths"));
+        assert_eq!(37, header.split(',').count());
+        assert_eq!("1,99,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,100,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0", region_counts[1]);
+        assert!(!summary_file.exists());
+
+        aggregator.simulation_ended();
+
+        assert!(summary_file.exists());
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use std::collections::BTreeMap;
+
+/// Assigns the regions to the engines so that every engine simulates a similar population. The largest
+/// regions are placed first, each on the engine with the smallest population so far
+pub fn pack_regions(populations: &[(String, u32)], engines: &[String]) -> BTreeMap<String, Vec<String>> {
+    if engines.is_empty() {
+        panic!("At least one engine is needed to pack the regions");
+    }
+    let mut sorted = populations.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut loads: Vec<(u64, Vec<String>)> = engines.iter().map(|_| (0, Vec::new())).collect();
+    for (region, population) in sorted {
+        let (load, regions) = loads.iter_mut().min_by_key(|(load, _)| *load).unwrap();
+        *load += population as u64;
+        regions.push(region);
+    }
+
+    engines.iter().cloned().zip(loads.into_iter().map(|(load, regions)| {
+        info!("Regions {:?} with a population of {}", regions, load);
+        regions
+    })).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn populations() -> Vec<(String, u32)> {
+        vec![("ward1".to_string(), 100), ("ward2".to_string(), 5000), ("ward3".to_string(), 2000),
+             ("ward4".to_string(), 2500), ("ward5".to_string(), 400)]
+    }
+
+    #[test]
+    fn should_balance_population_across_engines() {
+        let assignment = pack_regions(&populations(), &["engine-a".to_string(), "engine-b".to_string()]);
+
+        assert_eq!(vec!["ward2".to_string()], assignment["engine-a"]);
+        assert_eq!(vec!["ward4".to_string(), "ward3".to_string(), "ward5".to_string(), "ward1".to_string()],
+                   assignment["engine-b"]);
+    }
+
+    #[test]
+    fn should_leave_engines_without_regions_when_there_are_more_engines() {
+        let populations = vec![("ward1".to_string(), 100)];
+        let assignment = pack_regions(&populations, &["engine-a".to_string(), "engine-b".to_string()]);
+
+        assert_eq!(vec!["ward1".to_string()], assignment["engine-a"]);
+        assert!(assignment["engine-b"].is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+/*
+ * EpiRust
+ * Copyright (c) 2020  ThoughtWorks, Inc.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ */
+
+use crate::custom_types::{Count, Percentage};
+use crate::ticks::Counts;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename = "Intervention")]
+pub enum InterventionConfig {
+    Lockdown(LockdownConfig),
+    TravelRestriction(TravelRestrictionConfig),
+}
+
+/// City-wide lockdown, applied when the infections across all regions cross the threshold
+#[derive(Debug, PartialEq, Serialize, Deserialize, Copy, Clone)]
+pub struct LockdownConfig {
+    pub at_number_of_infections: Count,
+    pub essential_workers_population: Percentage,
+    #[serde(default)]
+    pub lift_below_number_of_infections: Option<Count>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TravelRestrictionConfig {
+    pub at_number_of_infections: Count,
+    #[serde(default)]
+    pub lift_below_number_of_infections: Option<Count>,
+    #[serde(flatten)]
+    pub restrictions: TravelRestrictions,
+}
+
+/// Scales the flow between two regions, a scale of 0 blocks it. Without `from` it applies to the flows
+/// from all regions into `to`, and without `to` to all flows out of `from`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FlowRestriction {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    pub scale: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TravelRestrictions {
+    #[serde(default)]
+    pub flows: Vec<FlowRestriction>,
+    #[serde(default)]
+    pub screen_symptomatic: bool,
+}
+
+/// Sent to the engines along with the next tick
+#[derive(Debug, PartialEq, Serialize, Clone)]
+pub enum InterventionCommand {
+    Lockdown { essential_workers_population: Percentage },
+    LiftLockdown {},
+    RestrictTravel(TravelRestrictions),
+    LiftTravelRestrictions {},
+}
+
+pub struct GlobalInterventions {
+    lockdown: Option<LockdownConfig>,
+    is_locked_down: bool,
+    travel_restriction: Option<TravelRestrictionConfig>,
+    is_travel_restricted: bool,
+}
+
+impl GlobalInterventions {
+    pub fn new(configs: &[InterventionConfig]) -> GlobalInterventions {
+        let lockdown = configs.iter().filter_map(|config| match config {
+            InterventionConfig::Lockdown(x) => Some(*x),
+            _ => None
+        }).next();
+        let travel_restriction = configs.iter().filter_map(|config| match config {
+            InterventionConfig::TravelRestriction(x) => Some(x.clone()),
+            _ => None
+        }).next();
+        GlobalInterventions { lockdown, is_locked_down: false, travel_restriction, is_travel_restricted: false }
+    }
+
+    /// Evaluates the rules over the counts of the whole city, and returns the commands to broadcast
+    pub fn evaluate(&mut self, city_counts: &Counts) -> Vec<InterventionCommand> {
+        let mut commands = Vec::new();
+        let infected = city_counts.get_infected();
+        if let Some(lockdown) = self.lockdown {
+            match GlobalInterventions::transition(self.is_locked_down, infected, lockdown.at_number_of_infections,
+                                                  lockdown.lift_below_number_of_infections) {
+                Some(true) => {
+                    self.is_locked_down = true;
+                    info!("Applying city-wide lockdown at hour {}, infected: {}", city_counts.get_hour(), infected);
+                    commands.push(InterventionCommand::Lockdown {
+                        essential_workers_population: lockdown.essential_workers_population
+                    });
+                }
+                Some(false) => {
+                    self.is_locked_down = false;
+                    info!("Lifting city-wide lockdown at hour {}, infected: {}", city_counts.get_hour(), infected);
+                    commands.push(InterventionCommand::LiftLockdown {});
+                }
+                None => {}
+            }
+        }
+        if let Some(restriction) = &self.travel_restriction {
+            match GlobalInterventions::transition(self.is_travel_restricted, infected, restriction.at_number_of_infections,
+                                                  restriction.lift_below_number_of_infections) {
+                Some(true) => {
+                    self.is_travel_restricted = true;
+                    info!("Restricting travel at hour {}, infected: {}", city_counts.get_hour(), infected);
+                    commands.push(InterventionCommand::RestrictTravel(restriction.restrictions.clone()));
+                }
+                Some(false) => {
+                    self.is_travel_restricted = false;
+                    info!("Lifting travel restrictions at hour {}, infected: {}", city_counts.get_hour(), infected);
+                    commands.push(InterventionCommand::LiftTravelRestrictions {});
+                }
+                None => {}
+            }
+        }
+        commands
+    }
+
+    /// Some(true) when the intervention should be applied, Some(false) when it should be lifted
+    fn transition(is_active: bool, infected: i32, at_number_of_infections: Count,
+                  lift_below_number_of_infections: Option<Count>) -> Option<bool> {
+        let should_lift = match lift_below_number_of_infections {
+            Some(threshold) => infected < threshold as i32,
+            None => false
+        };
+        if !is_active && infected > at_number_of_infections as i32 {
+            Some(true)
+        } else if is_active && should_lift {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockdown(lift_below_number_of_infections: Option<Count>) -> GlobalInterventions {
+        GlobalInterventions::new(&[InterventionConfig::Lockdown(LockdownConfig {
+            at_number_of_infections: 20,
+            essential_workers_population: 0.1,
+            lift_below_number_of_infections,
+        })])
+    }
+
+    #[test]
+    fn should_apply_lockdown_once_above_threshold() {
+        let mut interventions = lockdown(None);
+
+        assert!(interventions.evaluate(&Counts::new(24, 80, 0, 20, 0, 0, 0)).is_empty());
+        assert_eq!(vec![InterventionCommand::Lockdown { essential_workers_population: 0.1 }],
+                   interventions.evaluate(&Counts::new(48, 79, 0, 21, 0, 0, 0)));
+        assert!(interventions.evaluate(&Counts::new(72, 70, 0, 30, 0, 0, 0)).is_empty());
+        assert!(interventions.evaluate(&Counts::new(96, 70, 0, 0, 0, 30, 0)).is_empty());
+    }
+
+    #[test]
+    fn should_lift_lockdown_below_threshold() {
+        let mut interventions = lockdown(Some(5));
+
+        interventions.evaluate(&Counts::new(48, 79, 0, 21, 0, 0, 0));
+        assert!(interventions.evaluate(&Counts::new(72, 75, 0, 5, 0, 20, 0)).is_empty());
+        assert_eq!(vec![InterventionCommand::LiftLockdown {}],
+                   interventions.evaluate(&Counts::new(96, 75, 0, 4, 0, 21, 0)));
+    }
+
+    #[test]
+    fn should_restrict_travel_and_lift_restrictions() {
+        let restrictions = TravelRestrictions {
+            flows: vec![FlowRestriction { from: None, to: Some("engine1".to_string()), scale: 0.0 }],
+            screen_symptomatic: true,
+        };
+        let mut interventions = GlobalInterventions::new(&[InterventionConfig::TravelRestriction(TravelRestrictionConfig {
+            at_number_of_infections: 50,
+            lift_below_number_of_infections: Some(10),
+            restrictions: restrictions.clone(),
+        })]);
+
+        assert!(interventions.evaluate(&Counts::new(24, 80, 0, 20, 0, 0, 0)).is_empty());
+        assert_eq!(vec![InterventionCommand::RestrictTravel(restrictions)],
+                   interventions.evaluate(&Counts::new(48, 49, 0, 51, 0, 0, 0)));
+        assert!(interventions.evaluate(&Counts::new(72, 40, 0, 40, 0, 20, 0)).is_empty());
+        assert_eq!(vec![InterventionCommand::LiftTravelRestrictions {}],
+                   interventions.evaluate(&Counts::new(96, 40, 0, 9, 0, 51, 0)));
+    }
+
+    #[test]
+    fn should_read_travel_restriction_config() {
+        let json = r#"{"TravelRestriction": {"at_number_of_infections": 100, "screen_symptomatic": true,
+                       "flows": [{"from": "engine1", "to": "engine2", "scale": 0.5}]}}"#;
+        let expected = InterventionConfig::TravelRestriction(TravelRestrictionConfig {
+            at_number_of_infections: 100,
+            lift_below_number_of_infections: None,
+            restrictions: TravelRestrictions {
+                flows: vec![FlowRestriction { from: Some("engine1".to_string()), to: Some("engine2".to_string()), scale: 0.5 }],
+                screen_symptomatic: true,
+            },
+        });
+
+        assert_eq!(expected, serde_json::from_str(json).unwrap());
+    }
+
+    #[test]
+    fn should_not_send_commands_without_global_interventions() {
+        let mut interventions = GlobalInterventions::new(&[]);
+
+        assert!(interventions.evaluate(&Counts::new(48, 0, 0, 100, 0, 0, 0)).is_empty());
+    }
+
+    #[test]
+    fn should_serialize_commands_like_engine_expects() {
+        let commands = vec![InterventionCommand::Lockdown { essential_workers_population: 0.1 }, InterventionCommand::LiftLockdown {}];
+
+        assert_eq!(r#"[{"Lockdown":{"essential_workers_population":0.1}},{"LiftLockdown":{}}]"#,
+                   serde_json::to_string(&commands).unwrap());
+    }
+}
@@ -33,7 +33,9 @@ use rdkafka::client::DefaultClientContext;
 use rdkafka::ClientConfig;
 
 use crate::config::{Configuration, get_hours};
+use crate::interventions::GlobalInterventions;
 use crate::kafka_producer::KafkaProducer;
+use crate::ticks::CommuteHours;
 use crate::travel_plan::TravelPlan;
 
 mod kafka_producer;
@@ -43,6 +45,9 @@ mod environment;
 mod travel_plan;
 mod config;
 mod custom_types;
+mod counts_aggregator;
+mod engine_packing;
+mod interventions;
 
 #[tokio::main]
 async fn main() {
@@ -62,14 +67,17 @@ async fn main() {
     let config_path = matches.value_of("config").unwrap_or("config/simulation.json");
 
     let config = Configuration::read(config_path).expect("Error while reading config");
-    let sim_conf = config::read_simulation_conf(config_path);
+    let sim_conf = config::read_simulation_conf(config_path, &config);
     let travel_plan = config.get_travel_plan();
+    let output_file_format = counts_aggregator::output_file_format(config.get_output_file());
+    let global_interventions = GlobalInterventions::new(config.get_interventions());
 
     let hours = 1..get_hours(config_path);
+    let commute_hours = config.commute_hours();
 
     config.validate();
     cleanup(travel_plan.get_regions()).await;
-    start(travel_plan, hours, &sim_conf).await;
+    start(travel_plan, hours, commute_hours, &sim_conf, output_file_format, global_interventions).await;
 }
 
 async fn cleanup(regions: &Vec<String>) {
@@ -113,11 +121,12 @@ async fn cleanup(regions: &Vec<String>) {
     }
 }
 
-async fn start(travel_plan: &TravelPlan, hours: Range<i64>, sim_conf: &String) {
+async fn start(travel_plan: &TravelPlan, hours: Range<i64>, commute_hours: CommuteHours, sim_conf: &String,
+               output_file_format: String, global_interventions: GlobalInterventions) {
     let mut producer = KafkaProducer::new();
 
     match producer.start_request(sim_conf).await.unwrap() {
-        Ok(_) => { ticks::start_ticking(travel_plan, hours).await; }
+        Ok(_) => { ticks::start_ticking(travel_plan, hours, commute_hours, output_file_format, global_interventions).await; }
         Err(_) => { panic!("Failed to send simulation request to engines"); }
     }
 }
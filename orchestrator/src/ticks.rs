@@ -26,14 +26,39 @@ use std::error::Error;
 use rdkafka::Message;
 use futures::StreamExt;
 use crate::travel_plan::TravelPlan;
+use crate::counts_aggregator::CountsAggregator;
+use crate::environment;
+use crate::interventions::{GlobalInterventions, InterventionCommand};
 
 const ROUTINE_TRAVEL_START_TIME: i64 = 7;
 const ROUTINE_TRAVEL_END_TIME: i64 = 17;
 
+/// The hours of the day commuters travel to their work in another region and back home
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CommuteHours {
+    pub to_work: i64,
+    pub back_home: i64,
+}
+
+impl CommuteHours {
+    fn includes(&self, hour_of_day: i64) -> bool {
+        hour_of_day == self.to_work || hour_of_day == self.back_home
+    }
+}
+
+impl Default for CommuteHours {
+    fn default() -> Self {
+        CommuteHours { to_work: ROUTINE_TRAVEL_START_TIME, back_home: ROUTINE_TRAVEL_END_TIME }
+    }
+}
+
 //Note: these ticks are safe, they don't cause Lyme disease
 
-pub async fn start_ticking(travel_plan: &TravelPlan, hours: Range<i64>) {
+pub async fn start_ticking(travel_plan: &TravelPlan, hours: Range<i64>, commute_hours: CommuteHours, output_file_format: String,
+                           mut global_interventions: GlobalInterventions) {
     let mut acks: TickAcks = TickAcks::new(travel_plan.get_regions());
+    let mut aggregator = CountsAggregator::new(travel_plan.get_regions(), &environment::output_dir().join(output_file_format));
+    let mut intervention_commands: Vec<InterventionCommand> = Vec::new();
     let mut producer = KafkaProducer::new();
     let consumer = KafkaConsumer::new();
     let mut message_stream = consumer.start_message_stream();
@@ -41,19 +66,17 @@ pub async fn start_ticking(travel_plan: &TravelPlan, hours: Range<i64>) {
     let is_commute_enabled = travel_plan.commute.enabled;
     let is_migration_enabled = travel_plan.migration.enabled;
     for h in hours {
-        if !is_commute_enabled {
-            if h % 24 == ROUTINE_TRAVEL_END_TIME || h % 24 == ROUTINE_TRAVEL_START_TIME {
-                continue;
-            }
+        if !is_commute_enabled && commute_hours.includes(h % 24) {
+            continue;
         }
         if !is_migration_enabled && h % 24 == 0 {
             continue;
         }
-        if h > 1 && h % 24 != 0 && h % 24 != ROUTINE_TRAVEL_START_TIME && h % 24 != ROUTINE_TRAVEL_END_TIME {
+        if h > 1 && h % 24 != 0 && !commute_hours.includes(h % 24) {
             continue;
         }
         acks.reset(h);
-        let tick = Tick::new(h,  should_terminate);
+        let tick = Tick::new(h, should_terminate, std::mem::take(&mut intervention_commands));
 
         match producer.send_tick(&tick).await.unwrap() {
             Ok(_) => {
@@ -70,6 +93,8 @@ pub async fn start_ticking(travel_plan: &TravelPlan, hours: Range<i64>) {
                         Ok(ack) => {
                             acks.push(ack);
                             if acks.all_received() {
+                                aggregator.record(h, &acks);
+                                intervention_commands = global_interventions.evaluate(&acks.city_counts());
                                 should_terminate = acks.should_terminate();
                                 break;
                             }
@@ -80,19 +105,22 @@ pub async fn start_ticking(travel_plan: &TravelPlan, hours: Range<i64>) {
             Err(_) => { panic!("Failed to send simulation request to engines"); }
         }
     }
+    aggregator.simulation_ended();
 }
 
 #[derive(Debug, Serialize)]
 pub struct Tick {
     hour: i64,
     terminate: bool,
+    interventions: Vec<InterventionCommand>,
 }
 
 impl Tick {
-    pub fn new(hour: i64, terminate: bool) -> Tick {
+    pub fn new(hour: i64, terminate: bool, interventions: Vec<InterventionCommand>) -> Tick {
         Tick {
             hour,
-            terminate
+            terminate,
+            interventions,
         }
     }
 }
@@ -105,6 +133,11 @@ pub struct TickAck {
 }
 
 impl TickAck {
+    #[cfg(test)]
+    pub fn new(engine_id: String, hour: i64, counts: Counts) -> TickAck {
+        TickAck { engine_id, hour, counts }
+    }
+
     pub fn parse_message(message: Result<BorrowedMessage, KafkaError>) -> Result<TickAck, Box<dyn Error>> {
         let borrowed_message = message?;
         let parsed_message = borrowed_message.payload_view::<str>().unwrap()?;
@@ -113,7 +146,7 @@ impl TickAck {
     }
 }
 
-#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Default)]
 pub struct Counts {
     hour: i32,
     susceptible: i32,
@@ -122,6 +155,30 @@ pub struct Counts {
     hospitalized: i32,
     recovered: i32,
     deceased: i32,
+    #[serde(default)]
+    new_exposures: i32,
+    #[serde(default)]
+    new_infections: i32,
+    #[serde(default)]
+    new_symptomatic: i32,
+    #[serde(default)]
+    new_hospital_admissions: i32,
+    #[serde(default)]
+    new_recoveries: i32,
+    #[serde(default)]
+    new_deaths: i32,
+    #[serde(default)]
+    daily_new_exposures: i32,
+    #[serde(default)]
+    daily_new_infections: i32,
+    #[serde(default)]
+    daily_new_symptomatic: i32,
+    #[serde(default)]
+    daily_new_hospital_admissions: i32,
+    #[serde(default)]
+    daily_new_recoveries: i32,
+    #[serde(default)]
+    daily_new_deaths: i32,
 }
 
 impl Counts {
@@ -135,8 +192,65 @@ impl Counts {
             hospitalized: h,
             recovered: r,
             deceased: d,
+            ..Default::default()
         }
     }
+
+    pub fn sum(hour: i32, counts: &[Counts]) -> Counts {
+        counts.iter().fold(Counts { hour, ..Default::default() },
+                           |total, c| Counts {
+                               hour,
+                               susceptible: total.susceptible + c.susceptible,
+                               exposed: total.exposed + c.exposed,
+                               infected: total.infected + c.infected,
+                               hospitalized: total.hospitalized + c.hospitalized,
+                               recovered: total.recovered + c.recovered,
+                               deceased: total.deceased + c.deceased,
+                               new_exposures: total.new_exposures + c.new_exposures,
+                               new_infections: total.new_infections + c.new_infections,
+                               new_symptomatic: total.new_symptomatic + c.new_symptomatic,
+                               new_hospital_admissions: total.new_hospital_admissions + c.new_hospital_admissions,
+                               new_recoveries: total.new_recoveries + c.new_recoveries,
+                               new_deaths: total.new_deaths + c.new_deaths,
+                               daily_new_exposures: total.daily_new_exposures + c.daily_new_exposures,
+                               daily_new_infections: total.daily_new_infections + c.daily_new_infections,
+                               daily_new_symptomatic: total.daily_new_symptomatic + c.daily_new_symptomatic,
+                               daily_new_hospital_admissions: total.daily_new_hospital_admissions + c.daily_new_hospital_admissions,
+                               daily_new_recoveries: total.daily_new_recoveries + c.daily_new_recoveries,
+                               daily_new_deaths: total.daily_new_deaths + c.daily_new_deaths,
+                           })
+    }
+
+    pub fn get_hour(&self) -> i32 {
+        self.hour
+    }
+
+    pub fn get_susceptible(&self) -> i32 {
+        self.susceptible
+    }
+
+    pub fn get_infected(&self) -> i32 {
+        self.infected
+    }
+
+    pub fn get_hospitalized(&self) -> i32 {
+        self.hospitalized
+    }
+
+    pub fn get_deceased(&self) -> i32 {
+        self.deceased
+    }
+
+    pub fn total(&self) -> i32 {
+        self.susceptible + self.exposed + self.infected + self.hospitalized + self.recovered + self.deceased
+    }
+
+    /// The counts in csv column order, without the hour
+    pub fn values(&self) -> [i32; 18] {
+        [self.susceptible, self.exposed, self.infected, self.hospitalized, self.recovered, self.deceased,
+         self.new_exposures, self.new_infections, self.new_symptomatic, self.new_hospital_admissions, self.new_recoveries, self.new_deaths,
+         self.daily_new_exposures, self.daily_new_infections, self.daily_new_symptomatic, self.daily_new_hospital_admissions, self.daily_new_recoveries, self.daily_new_deaths]
+    }
 }
 
 /// stores a record of all the acks received for a tick
@@ -177,6 +291,15 @@ impl TickAcks {
         self.acks.insert(ack.engine_id, cloned);
     }
 
+    pub fn get_counts(&self, engine_id: &str) -> Option<Counts> {
+        self.acks.get(engine_id).map(|ack| ack.counts)
+    }
+
+    pub fn city_counts(&self) -> Counts {
+        let counts: Vec<Counts> = self.acks.values().map(|ack| ack.counts).collect();
+        Counts::sum(self.current_hour as i32, &counts)
+    }
+
     pub fn all_received(&self) -> bool {
         self.acks.keys().count() == self.engines.len()
     }
@@ -204,6 +327,25 @@ mod tests {
         assert_eq!(*acks.acks.get("engine1").unwrap(), ack);
     }
 
+    #[test]
+    fn should_read_incidence_from_engine_ack() {
+        let ack = r#"{"engine_id":"engine1","hour":24,"counts":{"hour":24,"date":"2020-03-02","susceptible":90,
+        "exposed":4,"infected":3,"hospitalized":1,"recovered":1,"deceased":1,"in_icu":0,"turned_away":0,
+        "excess_deaths":0,"new_deaths":1,"new_hospital_admissions":0,"new_exposures":2,"new_infections":1,
+        "new_symptomatic":1,"new_recoveries":0,"daily_new_exposures":5,"daily_new_infections":3,
+        "daily_new_symptomatic":2,"daily_new_hospital_admissions":1,"daily_new_recoveries":1,"daily_new_deaths":1},
+        "locked_down":false}"#;
+        let ack: TickAck = serde_json::from_str(ack).unwrap();
+        let counts = ack.counts;
+
+        assert_eq!(24, counts.get_hour());
+        assert_eq!([90, 4, 3, 1, 1, 1, 2, 1, 1, 0, 0, 1, 5, 3, 2, 1, 1, 1], counts.values());
+        assert_eq!(counts, serde_json::from_str(&serde_json::to_string(&counts).unwrap()).unwrap());
+
+        let city = Counts::sum(24, &[counts, Counts::new(24, 100, 0, 0, 0, 0, 0), counts]);
+        assert_eq!([280, 8, 6, 2, 2, 2, 4, 2, 2, 0, 0, 2, 10, 6, 4, 2, 2, 2], city.values());
+    }
+
     #[test]
     fn should_reset_current_hr() {
         let engines = vec!["engine1".to_string(), "engine2".to_string()];
@@ -18,18 +18,55 @@
  */
 
 
+use std::error::Error;
+
+use chrono::{NaiveDate, Weekday};
+use csv::ReaderBuilder;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Migration {
     pub enabled: bool,
     matrix: Option<Vec<Vec<u32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    matrix_file: Option<String>,
     start_migration_hour: Option<u32>,
     end_migration_hour: Option<u32>,
+    #[serde(default)]
+    schedule: Vec<ScheduledMatrix>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Commute {
     pub enabled: bool,
     pub matrix: Option<Vec<Vec<u32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    matrix_file: Option<String>,
+    #[serde(default)]
+    schedule: Vec<ScheduledMatrix>,
+}
+
+/// Replaces the base matrix while it is active. `days_of_cycle` are days of a seven day cycle starting with the first
+/// day of the simulation (0 to 6), and `days_of_week` weekdays of the engines' calendar dates. `from_date` and `to_date`
+/// are inclusive, `from_hour` is inclusive and `to_hour` exclusive. The matrix can be inline, or in a csv file with the
+/// region names as the header and the first column
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ScheduledMatrix {
+    #[serde(default)]
+    days_of_cycle: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    days_of_week: Vec<Weekday>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to_date: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from_hour: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to_hour: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    matrix: Option<Vec<Vec<u32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    matrix_file: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -43,6 +80,59 @@ pub struct TravelPlan {
 }
 
 
+/// Reads a matrix from a csv file, ordering the rows and columns as the travel plan regions
+fn read_matrix(file: &str, regions: &[String]) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
+    let mut reader = ReaderBuilder::new().has_headers(true).from_path(file)?;
+    let header: Vec<String> = reader.headers()?.iter().skip(1).map(|h| h.trim().to_string()).collect();
+    let column_positions = positions(&header, regions)
+        .map_err(|region| format!("{}: missing column for region {}", file, region))?;
+
+    let mut rows: Vec<(String, Vec<u32>)> = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let row_number = i + 2;
+        let region = record.get(0).map(|r| r.trim().to_string()).unwrap_or_default();
+        let values = record.iter().skip(1)
+            .map(|val| val.trim().parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|e| format!("{}, row {}: {}", file, row_number, e))?;
+        if values.len() != header.len() {
+            return Err(format!("{}, row {}: expected {} values, found {}", file, row_number, header.len(), values.len()).into());
+        }
+        rows.push((region, values));
+    }
+    let row_regions: Vec<String> = rows.iter().map(|(region, _)| region.clone()).collect();
+    let row_positions = positions(&row_regions, regions)
+        .map_err(|region| format!("{}: missing row for region {}", file, region))?;
+
+    Ok(row_positions.iter()
+        .map(|row| column_positions.iter().map(|col| rows[*row].1[*col]).collect())
+        .collect())
+}
+
+fn positions(names: &[String], regions: &[String]) -> Result<Vec<usize>, String> {
+    regions.iter()
+        .map(|region| names.iter().position(|name| name == region).ok_or_else(|| region.clone()))
+        .collect()
+}
+
+fn load_matrix(matrix: &mut Option<Vec<Vec<u32>>>, matrix_file: &Option<String>, regions: &[String]) -> Result<(), Box<dyn Error>> {
+    if let Some(file) = matrix_file {
+        if matrix.is_some() {
+            return Err(format!("Both matrix and matrix_file are set for {}", file).into());
+        }
+        *matrix = Some(read_matrix(file, regions)?);
+    }
+    Ok(())
+}
+
+fn is_square(matrix: &Option<Vec<Vec<u32>>>, size: usize) -> bool {
+    match matrix {
+        None => false,
+        Some(m) => m.len() == size && m.iter().all(|row| row.len() == size)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct CommutePlan {
     pub regions: Vec<String>,
@@ -117,14 +207,57 @@ impl TravelPlan {
                 .all(|x| x)
     }
 
+    /// Reads the matrices set as csv files, so that the engines receive all of them inline
+    pub fn load_matrix_files(&mut self) -> Result<(), Box<dyn Error>> {
+        let regions = self.regions.clone();
+        load_matrix(&mut self.commute.matrix, &self.commute.matrix_file, &regions)?;
+        load_matrix(&mut self.migration.matrix, &self.migration.matrix_file, &regions)?;
+        for scheduled in self.commute.schedule.iter_mut().chain(self.migration.schedule.iter_mut()) {
+            load_matrix(&mut scheduled.matrix, &scheduled.matrix_file, &regions)?;
+        }
+        Ok(())
+    }
+
+    /// Whether any scheduled matrix is by weekday or date, which the engines can only tell with a start date
+    pub fn is_dated(&self) -> bool {
+        self.commute.schedule.iter().chain(self.migration.schedule.iter())
+            .any(|s| !s.days_of_week.is_empty() || s.from_date.is_some() || s.to_date.is_some())
+    }
+
+    /// Every matrix of the enabled travel, including the scheduled ones, should have a row and column per region
+    pub fn validate_matrices(&self) -> bool {
+        let size = self.regions.len();
+        let commute_valid = !self.commute.enabled || (is_square(&self.commute.matrix, size) &&
+            self.commute.schedule.iter().all(|s| is_square(&s.matrix, size)));
+        let migration_valid = !self.migration.enabled || (is_square(&self.migration.matrix, size) &&
+            self.migration.schedule.iter().all(|s| is_square(&s.matrix, size)));
+        commute_valid && migration_valid
+    }
+
     pub fn commute_plan(&self) -> CommutePlan {
         CommutePlan { regions: self.regions.clone(), matrix: self.commute.matrix.as_ref().unwrap().clone() }
     }
 
+    /// The plans for the base matrix and for each of the scheduled ones
+    pub fn commute_plans(&self) -> Vec<CommutePlan> {
+        let mut plans = vec![self.commute_plan()];
+        plans.extend(self.commute.schedule.iter()
+            .map(|s| CommutePlan { regions: self.regions.clone(), matrix: s.matrix.as_ref().unwrap().clone() }));
+        plans
+    }
+
     pub fn migration_plan(&self) -> MigrationPlan {
         MigrationPlan::new(self.regions.clone(), self.migration.matrix.as_ref().unwrap().clone())
     }
 
+    /// The plans for the base matrix and for each of the scheduled ones
+    pub fn migration_plans(&self) -> Vec<MigrationPlan> {
+        let mut plans = vec![self.migration_plan()];
+        plans.extend(self.migration.schedule.iter()
+            .map(|s| MigrationPlan::new(self.regions.clone(), s.matrix.as_ref().unwrap().clone())));
+        plans
+    }
+
     // pub fn update_with_lockdowns(&self, lockdown_status: &HashMap<String, bool>) -> TravelPlan {
     //     let locked_regions: Vec<&String> = lockdown_status.iter().filter(|(_k, v)| **v).map(|(k, _v)| k)
     //         .collect();
@@ -154,6 +287,8 @@ impl TravelPlan {
 mod tests {
     use crate::config::Configuration;
 
+    use super::*;
+
     #[test]
     fn should_validate_regions() {
         let config = Configuration::read("config/test/travel_plan.json").unwrap();
@@ -166,4 +301,51 @@ mod tests {
         assert!(!travel_plan.validate_regions(&vec!["engine1".to_string(), "engine2".to_string(),
                                                     "engine3".to_string(), "engine4".to_string()]));
     }
+
+    #[test]
+    fn should_load_scheduled_matrices_from_csv() {
+        let config = Configuration::read("config/test/travel_plan_with_schedule.json").unwrap();
+        let travel_plan = config.get_travel_plan();
+
+        assert!(travel_plan.validate_matrices());
+        let commute_plans = travel_plan.commute_plans();
+        assert_eq!(2, commute_plans.len());
+        assert_eq!(vec![vec![0, 8, 1], vec![5, 0, 14], vec![4, 3, 0]], commute_plans[1].matrix);
+        assert_eq!(3, travel_plan.migration_plans().len());
+        assert_eq!(vec![vec![0, 300, 10], vec![200, 0, 290], vec![90, 75, 0]], travel_plan.migration_plans()[2].matrix);
+    }
+
+    #[test]
+    fn should_order_csv_matrix_by_regions() {
+        let regions = vec!["engine3".to_string(), "engine1".to_string(), "engine2".to_string()];
+        let matrix = read_matrix("config/test/weekend_commute.csv", &regions).unwrap();
+
+        assert_eq!(vec![vec![0, 4, 3], vec![1, 0, 8], vec![14, 5, 0]], matrix);
+    }
+
+    #[test]
+    fn should_report_row_with_invalid_values() {
+        let regions = vec!["engine1".to_string(), "engine2".to_string(), "engine3".to_string()];
+        let error = read_matrix("config/test/invalid_commute.csv", &regions).unwrap_err();
+
+        assert!(error.to_string().contains("row 3"));
+    }
+
+    #[test]
+    fn should_not_validate_matrix_of_wrong_size() {
+        let mut travel_plan = Configuration::read("config/test/travel_plan.json").unwrap().get_travel_plan().clone();
+        assert!(travel_plan.validate_matrices());
+
+        travel_plan.commute.schedule.push(ScheduledMatrix {
+            days_of_cycle: vec![6],
+            days_of_week: vec![],
+            from_date: None,
+            to_date: None,
+            from_hour: None,
+            to_hour: None,
+            matrix: Some(vec![vec![0, 1], vec![1, 0]]),
+            matrix_file: None,
+        });
+        assert!(!travel_plan.validate_matrices());
+    }
 }